@@ -0,0 +1,135 @@
+use chrono::NaiveDate;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use market_data::database::Database;
+use market_data::calendar::TradingCalendar;
+use market_data::source::StockRecord;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::path::PathBuf;
+use tokio::runtime::Runtime;
+
+// Roughly the number of rows in a daily NSE CM bhavcopy
+const SYMBOLS_PER_DAY: usize = 2500;
+
+fn synthetic_day(date: NaiveDate, symbols: usize) -> Vec<StockRecord> {
+    (0..symbols)
+        .map(|i| {
+            let base = 100.0 + i as f64;
+            StockRecord {
+                exchange: "NSE".to_string(),
+                symbol: format!("SYM{:05}", i),
+                series: if i % 10 == 0 { "BE".to_string() } else { "EQ".to_string() },
+                open: base,
+                high: base * 1.02,
+                low: base * 0.98,
+                close: base * 1.01,
+                last: base * 1.01,
+                prevclose: base,
+                tottrdqty: 10_000 + i as i64,
+                tottrdval: base * 10_000.0,
+                timestamp: date,
+                totaltrades: 500,
+                isin: format!("INE{:06}01010", i),
+            }
+        })
+        .collect()
+}
+
+async fn fresh_database(name: &str) -> (Database, PathBuf) {
+    let path = std::env::temp_dir().join(format!("market-data-bench-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let options = SqliteConnectOptions::new().filename(&path).create_if_missing(true);
+    let pool = SqlitePoolOptions::new().connect_with(options).await.unwrap();
+    let db = Database::from_pool(pool);
+    db.initialize().await.unwrap();
+    (db, path)
+}
+
+/// One bhavcopy written with the per-row upserts versus the batched path.
+fn bench_single_day(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+    let records = synthetic_day(date, SYMBOLS_PER_DAY);
+
+    let mut group = c.benchmark_group("single_day");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(records.len() as u64));
+
+    group.bench_function("per_row", |b| {
+        b.to_async(&runtime).iter_custom(|iters| {
+            let records = records.clone();
+            async move {
+                let (db, path) = fresh_database("per-row").await;
+                let start = std::time::Instant::now();
+                for _ in 0..iters {
+                    for record in &records {
+                        let company_id = db
+                            .upsert_company(&record.exchange, &record.symbol, &record.isin, &record.series, record.timestamp)
+                            .await
+                            .unwrap();
+                        db.upsert_daily_price(company_id, record).await.unwrap();
+                    }
+                }
+                let elapsed = start.elapsed();
+                let _ = std::fs::remove_file(path);
+                elapsed
+            }
+        })
+    });
+
+    group.bench_function("batched", |b| {
+        b.to_async(&runtime).iter_custom(|iters| {
+            let records = records.clone();
+            async move {
+                let (db, path) = fresh_database("batched").await;
+                let start = std::time::Instant::now();
+                for _ in 0..iters {
+                    db.store_stock_records(&records).await.unwrap();
+                }
+                let elapsed = start.elapsed();
+                let _ = std::fs::remove_file(path);
+                elapsed
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Multi-year backfill into an empty database, one transaction per date.
+fn bench_backfill(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let from = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+    let days: Vec<Vec<StockRecord>> = TradingCalendar::bundled("NSE")
+        .trading_dates_in_range(from, to)
+        .into_iter()
+        .map(|date| synthetic_day(date, SYMBOLS_PER_DAY))
+        .collect();
+    let total_rows: usize = days.iter().map(|day| day.len()).sum();
+
+    let mut group = c.benchmark_group("backfill");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(total_rows as u64));
+
+    group.bench_with_input(BenchmarkId::new("batched", format!("{}_days", days.len())), &days, |b, days| {
+        b.to_async(&runtime).iter_custom(|iters| async move {
+            let mut total = std::time::Duration::ZERO;
+            for _ in 0..iters {
+                let (db, path) = fresh_database("backfill").await;
+                let start = std::time::Instant::now();
+                for day in days {
+                    db.store_stock_records(day).await.unwrap();
+                }
+                total += start.elapsed();
+                let _ = std::fs::remove_file(path);
+            }
+            total
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_single_day, bench_backfill);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+-- Initial market data schema
+
+CREATE TABLE IF NOT EXISTS exchanges (
+    code TEXT PRIMARY KEY,
+    name TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO exchanges (code, name) VALUES
+    ('NSE', 'National Stock Exchange of India'),
+    ('BSE', 'BSE Limited');
+
+-- One row per listing: the same security traded on NSE and BSE has two
+-- rows sharing an ISIN
+CREATE TABLE IF NOT EXISTS companies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    exchange TEXT NOT NULL REFERENCES exchanges(code),
+    symbol TEXT NOT NULL,
+    isin TEXT,
+    series TEXT NOT NULL DEFAULT '',
+    name TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (exchange, symbol, series)
+);
+
+CREATE INDEX IF NOT EXISTS idx_companies_isin ON companies(isin);
+
+CREATE TABLE IF NOT EXISTS daily_prices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    exchange TEXT NOT NULL REFERENCES exchanges(code),
+    trade_date DATE NOT NULL,
+    open_price REAL,
+    high_price REAL,
+    low_price REAL,
+    close_price REAL,
+    last_price REAL,
+    prev_close REAL,
+    total_traded_qty INTEGER,
+    total_traded_value REAL,
+    total_trades INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (company_id, trade_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_daily_prices_trade_date ON daily_prices(trade_date);
+CREATE INDEX IF NOT EXISTS idx_daily_prices_exchange_date ON daily_prices(exchange, trade_date);
+
+CREATE TABLE IF NOT EXISTS ingestion_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL,
+    file_name TEXT,
+    trade_date DATE,
+    records_processed INTEGER DEFAULT 0,
+    records_rejected INTEGER DEFAULT 0,
+    status TEXT NOT NULL CHECK (status IN ('success', 'partial', 'failed')),
+    error_message TEXT,
+    started_at DATETIME,
+    completed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_ingestion_log_trade_date ON ingestion_log(trade_date);
@@ -0,0 +1,11 @@
+-- Exchange holidays and special sessions imported with `holidays import`.
+-- They override the calendar bundled with the binary for the same date.
+CREATE TABLE IF NOT EXISTS market_holidays (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    exchange TEXT NOT NULL REFERENCES exchanges(code),
+    date DATE NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'holiday' CHECK (kind IN ('holiday', 'special_session')),
+    description TEXT NOT NULL DEFAULT '',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (exchange, date)
+);
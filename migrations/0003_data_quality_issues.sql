@@ -0,0 +1,18 @@
+-- Records that broke a validation rule during ingestion. Rejected records
+-- are not in daily_prices; warnings are stored alongside the price
+CREATE TABLE IF NOT EXISTS data_quality_issues (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ingestion_id INTEGER NOT NULL REFERENCES ingestion_log(id),
+    exchange TEXT NOT NULL REFERENCES exchanges(code),
+    trade_date DATE NOT NULL,
+    symbol TEXT NOT NULL,
+    series TEXT NOT NULL DEFAULT '',
+    rule TEXT NOT NULL,
+    severity TEXT NOT NULL CHECK (severity IN ('warning', 'rejection')),
+    message TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_data_quality_issues_trade_date ON data_quality_issues(trade_date);
+CREATE INDEX IF NOT EXISTS idx_data_quality_issues_symbol ON data_quality_issues(symbol, trade_date);
+CREATE INDEX IF NOT EXISTS idx_data_quality_issues_ingestion ON data_quality_issues(ingestion_id);
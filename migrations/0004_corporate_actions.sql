@@ -0,0 +1,27 @@
+-- Splits, bonuses, dividends and rights issues, from NSE's corporate
+-- actions file or imported by hand. `isin` links an action to every listing
+-- of the security in `companies`
+CREATE TABLE IF NOT EXISTS corporate_actions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    exchange TEXT NOT NULL REFERENCES exchanges(code),
+    symbol TEXT NOT NULL,
+    series TEXT NOT NULL DEFAULT '',
+    isin TEXT,
+    company_name TEXT,
+    ex_date DATE NOT NULL,
+    record_date DATE,
+    purpose TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('split', 'bonus', 'dividend', 'rights', 'other')),
+    old_face_value REAL,
+    new_face_value REAL,
+    ratio_new INTEGER,
+    ratio_held INTEGER,
+    amount REAL,
+    source TEXT NOT NULL DEFAULT 'manual',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (exchange, symbol, ex_date, purpose)
+);
+
+CREATE INDEX IF NOT EXISTS idx_corporate_actions_isin ON corporate_actions(isin, ex_date);
+CREATE INDEX IF NOT EXISTS idx_corporate_actions_ex_date ON corporate_actions(ex_date);
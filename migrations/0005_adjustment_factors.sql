@@ -0,0 +1,56 @@
+-- Per-listing price adjustments derived from corporate_actions: one row
+-- per ex-date with a split, bonus or dividend. Prices before `ex_date` are
+-- multiplied by the factors of every later ex-date
+CREATE TABLE IF NOT EXISTS adjustment_factors (
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    ex_date DATE NOT NULL,
+    split_factor REAL NOT NULL DEFAULT 1.0,
+    dividend_factor REAL NOT NULL DEFAULT 1.0,
+    dividend REAL,
+    reference_close REAL,
+    computed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (company_id, ex_date)
+);
+
+-- Securities whose factors need recomputing because their actions changed
+CREATE TABLE IF NOT EXISTS stale_adjustments (
+    isin TEXT PRIMARY KEY
+);
+
+CREATE TRIGGER IF NOT EXISTS corporate_actions_stale_on_insert
+AFTER INSERT ON corporate_actions
+WHEN NEW.isin IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO stale_adjustments (isin) VALUES (NEW.isin);
+END;
+
+CREATE TRIGGER IF NOT EXISTS corporate_actions_stale_on_update
+AFTER UPDATE ON corporate_actions
+WHEN NEW.isin IS NOT OLD.isin
+    OR NEW.ex_date IS NOT OLD.ex_date
+    OR NEW.kind IS NOT OLD.kind
+    OR NEW.old_face_value IS NOT OLD.old_face_value
+    OR NEW.new_face_value IS NOT OLD.new_face_value
+    OR NEW.ratio_new IS NOT OLD.ratio_new
+    OR NEW.ratio_held IS NOT OLD.ratio_held
+    OR NEW.amount IS NOT OLD.amount
+BEGIN
+    INSERT OR IGNORE INTO stale_adjustments (isin) SELECT NEW.isin WHERE NEW.isin IS NOT NULL;
+    INSERT OR IGNORE INTO stale_adjustments (isin) SELECT OLD.isin WHERE OLD.isin IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS corporate_actions_stale_on_delete
+AFTER DELETE ON corporate_actions
+WHEN OLD.isin IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO stale_adjustments (isin) VALUES (OLD.isin);
+END;
+
+-- A listing that changes ISIN belongs to a different security's actions
+CREATE TRIGGER IF NOT EXISTS companies_isin_change_stale
+AFTER UPDATE OF isin ON companies
+WHEN NEW.isin IS NOT OLD.isin
+BEGIN
+    DELETE FROM adjustment_factors WHERE company_id = NEW.id;
+    INSERT OR IGNORE INTO stale_adjustments (isin) SELECT NEW.isin WHERE NEW.isin IS NOT NULL AND NEW.isin != '';
+END;
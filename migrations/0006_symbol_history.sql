@@ -0,0 +1,29 @@
+-- Every symbol and ISIN a listing has traded under. A listing keeps its
+-- `companies.id` when NSE renames its symbol or its ISIN changes, so its
+-- prices stay under one id; `companies` holds the latest symbol and ISIN.
+--
+-- `valid_from` and `valid_to` are the first and last trade dates seen under
+-- the symbol and ISIN, or the dates given by NSE's symbol change file. NULL
+-- means unbounded: `valid_to` is NULL for the listing's current symbol.
+CREATE TABLE IF NOT EXISTS symbol_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    exchange TEXT NOT NULL REFERENCES exchanges(code),
+    symbol TEXT NOT NULL,
+    series TEXT NOT NULL DEFAULT '',
+    isin TEXT NOT NULL DEFAULT '',
+    valid_from DATE,
+    valid_to DATE,
+    source TEXT NOT NULL DEFAULT 'bhavcopy' CHECK (source IN ('bhavcopy', 'symbol_change')),
+    UNIQUE (company_id, symbol, isin)
+);
+
+CREATE INDEX IF NOT EXISTS idx_symbol_history_symbol ON symbol_history(exchange, symbol, series);
+CREATE INDEX IF NOT EXISTS idx_symbol_history_isin ON symbol_history(isin);
+
+INSERT OR IGNORE INTO symbol_history (company_id, exchange, symbol, series, isin, valid_from, valid_to)
+SELECT
+    c.id, c.exchange, c.symbol, c.series, COALESCE(c.isin, ''),
+    (SELECT MIN(p.trade_date) FROM daily_prices p WHERE p.company_id = c.id),
+    NULL
+FROM companies c;
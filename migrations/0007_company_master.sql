@@ -0,0 +1,16 @@
+-- Master data from NSE's equity list and sector classification files.
+-- Listings are never deleted: one that leaves the equity list, or appears
+-- in a suspension or delisting file, is flagged through `status`
+ALTER TABLE companies ADD COLUMN listing_date DATE;
+ALTER TABLE companies ADD COLUMN paid_up_value REAL;
+ALTER TABLE companies ADD COLUMN market_lot INTEGER;
+ALTER TABLE companies ADD COLUMN face_value REAL;
+ALTER TABLE companies ADD COLUMN sector TEXT;
+ALTER TABLE companies ADD COLUMN industry TEXT;
+ALTER TABLE companies ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'suspended', 'delisted'));
+ALTER TABLE companies ADD COLUMN status_date DATE;
+-- Date of the latest equity list that contained the listing
+ALTER TABLE companies ADD COLUMN master_list_date DATE;
+
+CREATE INDEX IF NOT EXISTS idx_companies_name ON companies(name);
@@ -0,0 +1,15 @@
+-- Security-wise delivery positions from NSE's MTO_DDMMYYYY.DAT, one row per
+-- listing and trade date. A listing is one (exchange, symbol, series), so
+-- the series is that of `company_id`.
+CREATE TABLE IF NOT EXISTS delivery_positions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    trade_date DATE NOT NULL,
+    traded_qty INTEGER,
+    deliverable_qty INTEGER,
+    delivery_pct REAL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (company_id, trade_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_delivery_positions_trade_date ON delivery_positions(trade_date);
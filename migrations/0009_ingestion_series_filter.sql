@@ -0,0 +1,4 @@
+-- The series a run was limited to with `--series`, comma separated in
+-- order, or NULL when it stored every series. A date only counts as
+-- ingested for the series its runs stored
+ALTER TABLE ingestion_log ADD COLUMN series_filter TEXT;
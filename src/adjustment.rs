@@ -0,0 +1,226 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::corporate_actions::{ActionKind, CorporateAction};
+
+/// Which price series a query returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Adjustment {
+    /// Prices as published in the bhavcopy
+    #[default]
+    Raw,
+    /// Prices and volumes before each split or bonus scaled to the shares
+    /// outstanding after it
+    Split,
+    /// Split-adjusted, and prices before each ex-dividend date scaled down
+    /// by the dividend, as if it had been reinvested
+    TotalReturn,
+}
+
+impl Adjustment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Adjustment::Raw => "raw",
+            Adjustment::Split => "split",
+            Adjustment::TotalReturn => "total-return",
+        }
+    }
+}
+
+impl fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Adjustment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "raw" | "none" => Ok(Adjustment::Raw),
+            "split" | "split-adjusted" => Ok(Adjustment::Split),
+            "total-return" | "tr" => Ok(Adjustment::TotalReturn),
+            other => Err(anyhow!("Unknown adjustment '{}', expected raw, split or total-return", other)),
+        }
+    }
+}
+
+/// The adjustment a listing's ex-date applies to every earlier price.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct AdjustmentFactor {
+    pub company_id: i64,
+    pub ex_date: NaiveDate,
+    /// Product of the split and bonus price factors effective on `ex_date`
+    pub split_factor: f64,
+    /// `(reference_close - dividend) / reference_close`
+    pub dividend_factor: f64,
+    /// Total dividend per share going ex on `ex_date`
+    pub dividend: Option<f64>,
+    /// The listing's last close before `ex_date`; `None` until it is stored
+    pub reference_close: Option<f64>,
+}
+
+/// Works out one listing's factors from the actions on its security.
+/// `reference_close` gives the listing's last stored close before a date.
+/// An action recorded by more than one exchange (e.g. the same bonus on NSE
+/// and BSE) is applied once.
+pub fn compute_factors(
+    company_id: i64,
+    actions: &[CorporateAction],
+    reference_close: impl Fn(NaiveDate) -> Option<f64>,
+) -> Vec<AdjustmentFactor> {
+    let mut by_date: Vec<(NaiveDate, Vec<&CorporateAction>)> = Vec::new();
+    for action in actions {
+        if !matches!(action.terms.kind, ActionKind::Split | ActionKind::Bonus | ActionKind::Dividend) {
+            continue;
+        }
+        match by_date.iter_mut().find(|(date, _)| *date == action.ex_date) {
+            Some((_, same_day)) => {
+                let recorded_elsewhere = same_day
+                    .iter()
+                    .any(|other| other.exchange != action.exchange && other.terms == action.terms);
+                if !recorded_elsewhere {
+                    same_day.push(action);
+                }
+            },
+            None => by_date.push((action.ex_date, vec![action])),
+        }
+    }
+    by_date.sort_by_key(|(date, _)| *date);
+
+    by_date
+        .into_iter()
+        .map(|(ex_date, same_day)| {
+            let split_factor: f64 = same_day
+                .iter()
+                .filter_map(|action| action.terms.price_factor())
+                .product();
+            let dividends: Vec<f64> = same_day
+                .iter()
+                .filter(|action| action.terms.kind == ActionKind::Dividend)
+                .filter_map(|action| action.terms.amount)
+                .collect();
+            let dividend = (!dividends.is_empty()).then(|| dividends.iter().sum::<f64>());
+            let reference = dividend.and_then(|_| reference_close(ex_date));
+            let dividend_factor = match (dividend, reference) {
+                (Some(dividend), Some(close)) if close > dividend && dividend > 0.0 => (close - dividend) / close,
+                _ => 1.0,
+            };
+            AdjustmentFactor {
+                company_id,
+                ex_date,
+                split_factor,
+                dividend_factor,
+                dividend,
+                reference_close: reference,
+            }
+        })
+        .collect()
+}
+
+/// One listing's day, raw or adjusted.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct PriceBar {
+    #[serde(skip)]
+    pub company_id: i64,
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub trade_date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub last: f64,
+    pub prev_close: f64,
+    pub volume: f64,
+    pub traded_value: f64,
+    pub trades: i64,
+    /// Quantity marked for delivery; `None` without a stored delivery file
+    pub deliverable_qty: Option<f64>,
+    pub delivery_pct: Option<f64>,
+}
+
+/// Backward-adjusts `bars` in place: each bar is scaled by the factors of
+/// every later ex-date on its listing, so the latest prices are unchanged.
+/// Adjusted values are rounded to four decimal places.
+pub fn adjust_prices(bars: &mut [PriceBar], factors: &[AdjustmentFactor], adjustment: Adjustment) {
+    if adjustment == Adjustment::Raw {
+        return;
+    }
+
+    let mut by_company: HashMap<i64, Vec<&AdjustmentFactor>> = HashMap::new();
+    for factor in factors {
+        by_company.entry(factor.company_id).or_default().push(factor);
+    }
+
+    for bar in bars {
+        let Some(listing_factors) = by_company.get(&bar.company_id) else {
+            continue;
+        };
+        let later = listing_factors.iter().filter(|factor| factor.ex_date > bar.trade_date);
+        let (split, dividend) = later.fold((1.0, 1.0), |(split, dividend), factor| {
+            (split * factor.split_factor, dividend * factor.dividend_factor)
+        });
+        let price_factor = match adjustment {
+            Adjustment::TotalReturn => split * dividend,
+            _ => split,
+        };
+
+        if price_factor == 1.0 {
+            continue;
+        }
+        for price in [&mut bar.open, &mut bar.high, &mut bar.low, &mut bar.close, &mut bar.last, &mut bar.prev_close] {
+            *price = round4(*price * price_factor);
+        }
+        if split > 0.0 {
+            bar.volume = round4(bar.volume / split);
+            bar.deliverable_qty = bar.deliverable_qty.map(|qty| round4(qty / split));
+        }
+    }
+}
+
+fn round4(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// Writes `bars` as CSV. The header row is written even when there are no
+/// bars.
+pub fn write_csv(bars: &[PriceBar], writer: impl Write) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(writer);
+    writer.write_record([
+        "exchange",
+        "symbol",
+        "series",
+        "trade_date",
+        "open",
+        "high",
+        "low",
+        "close",
+        "last",
+        "prev_close",
+        "volume",
+        "traded_value",
+        "trades",
+        "deliverable_qty",
+        "delivery_pct",
+    ])?;
+    for bar in bars {
+        writer.serialize(bar)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `bars` as a pretty-printed JSON array.
+pub fn write_json(bars: &[PriceBar], mut writer: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut writer, bars)?;
+    writeln!(writer)?;
+    Ok(())
+}
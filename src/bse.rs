@@ -0,0 +1,183 @@
+use reqwest::header::{HeaderMap, HeaderValue, REFERER};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use tracing::info;
+
+use crate::source::{
+    ColumnMap, ColumnNames, MarketDataSource, ParsedBhavcopy, RawFile, decode_bhavcopy_payload,
+    parse_bhavcopy_csv,
+};
+use crate::calendar::TradingCalendar;
+use crate::http::{HttpConfig, HttpFetcher};
+
+pub struct BseClient {
+    http: HttpFetcher,
+    calendar: TradingCalendar,
+}
+
+impl Default for BseClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BseClient {
+    pub fn new() -> Self {
+        Self::with_config(HttpConfig::default())
+    }
+
+    pub fn with_config(config: HttpConfig) -> Self {
+        // BSE rejects archive downloads that do not come from its own pages
+        let mut headers = HeaderMap::new();
+        headers.insert(REFERER, HeaderValue::from_static("https://www.bseindia.com/"));
+
+        BseClient {
+            http: HttpFetcher::new(config, headers, None),
+            calendar: TradingCalendar::bundled("BSE"),
+        }
+    }
+
+    pub async fn fetch_bhavcopy(&self, date: NaiveDate) -> Result<RawFile> {
+        let url = self.construct_bhavcopy_url(date);
+        info!("Downloading BSE bhavcopy from: {}", url);
+
+        let response = self.http.get(&url).await?;
+
+        if response.body.is_empty() {
+            return Err(anyhow!("Downloaded file is empty"));
+        }
+
+        info!("Successfully downloaded BSE bhavcopy for {}", date);
+        Ok(RawFile {
+            file_name: url,
+            content_type: response.content_type,
+            body: response.body,
+        })
+    }
+
+    pub fn construct_bhavcopy_url(&self, date: NaiveDate) -> String {
+        match BseBhavcopyFormat::for_date(date) {
+            // Legacy format:
+            // https://www.bseindia.com/download/BhavCopy/Equity/EQ{DDMMYY}_CSV.ZIP
+            BseBhavcopyFormat::Legacy => format!(
+                "https://www.bseindia.com/download/BhavCopy/Equity/EQ{}_CSV.ZIP",
+                date.format("%d%m%y")
+            ),
+            // UDiFF format, served as plain CSV:
+            // https://www.bseindia.com/download/BhavCopy/Equity/BhavCopy_BSE_CM_0_0_0_{YYYYMMDD}_F_0000.CSV
+            BseBhavcopyFormat::Udiff => format!(
+                "https://www.bseindia.com/download/BhavCopy/Equity/BhavCopy_BSE_CM_0_0_0_{}_F_0000.CSV",
+                date.format("%Y%m%d")
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl MarketDataSource for BseClient {
+    fn name(&self) -> &'static str {
+        "bse"
+    }
+
+    fn exchange(&self) -> &'static str {
+        "BSE"
+    }
+
+    fn calendar(&self) -> &TradingCalendar {
+        &self.calendar
+    }
+
+    fn set_calendar(&mut self, calendar: TradingCalendar) {
+        self.calendar = calendar;
+    }
+
+    async fn fetch(&self, date: NaiveDate) -> Result<RawFile> {
+        self.fetch_bhavcopy(date).await
+    }
+
+    fn parse(&self, raw: &RawFile, date: NaiveDate) -> Result<ParsedBhavcopy> {
+        let content = decode_bhavcopy_payload(&raw.body, raw.content_type.as_deref())?;
+        parse_bse_csv_data(&content, date)
+    }
+}
+
+/// First trading day on which BSE published the equity bhavcopy in UDiFF
+/// format; earlier dates are served as `EQ{DDMMYY}_CSV.ZIP`.
+pub fn bse_udiff_cutover_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 7, 8).expect("valid cutover date")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BseBhavcopyFormat {
+    /// `EQ{DDMMYY}.CSV` with SC_CODE, SC_GROUP, OPEN, ... columns
+    Legacy,
+    /// `BhavCopy_BSE_CM_0_0_0_{YYYYMMDD}_F_0000.CSV` with FinInstrmId, SctySrs, OpnPric, ... columns
+    Udiff,
+}
+
+impl BseBhavcopyFormat {
+    pub fn for_date(date: NaiveDate) -> Self {
+        if date >= bse_udiff_cutover_date() {
+            BseBhavcopyFormat::Udiff
+        } else {
+            BseBhavcopyFormat::Legacy
+        }
+    }
+
+    pub fn detect(headers: &csv::StringRecord) -> Option<Self> {
+        if headers.iter().any(|h| h == "FinInstrmId") {
+            Some(BseBhavcopyFormat::Udiff)
+        } else if headers.iter().any(|h| h == "SC_CODE") {
+            Some(BseBhavcopyFormat::Legacy)
+        } else {
+            None
+        }
+    }
+
+    // Both layouts are keyed by the numeric scrip code, so a scrip keeps
+    // the same symbol across the format change.
+    fn column_names(&self) -> &'static ColumnNames {
+        match self {
+            BseBhavcopyFormat::Legacy => &ColumnNames {
+                symbol: "SC_CODE",
+                series: "SC_GROUP",
+                open: "OPEN",
+                high: "HIGH",
+                low: "LOW",
+                close: "CLOSE",
+                last: "LAST",
+                prevclose: "PREVCLOSE",
+                tottrdqty: "NO_OF_SHRS",
+                tottrdval: "NET_TURNOV",
+                totaltrades: "NO_TRADES",
+                isin: "ISIN_CODE",
+                timestamp: "TRADING_DATE",
+            },
+            BseBhavcopyFormat::Udiff => &ColumnNames {
+                symbol: "FinInstrmId",
+                series: "SctySrs",
+                open: "OpnPric",
+                high: "HghPric",
+                low: "LwPric",
+                close: "ClsPric",
+                last: "LastPric",
+                prevclose: "PrvsClsgPric",
+                tottrdqty: "TtlTradgVol",
+                tottrdval: "TtlTrfVal",
+                totaltrades: "TtlNbOfTxsExctd",
+                isin: "ISIN",
+                timestamp: "TradDt",
+            },
+        }
+    }
+}
+
+pub fn parse_bse_csv_data(csv_content: &str, date: NaiveDate) -> Result<ParsedBhavcopy> {
+    parse_bhavcopy_csv(csv_content, date, "BSE", |headers| {
+        let format = BseBhavcopyFormat::detect(headers)
+            .ok_or_else(|| anyhow!("Unrecognised BSE bhavcopy header: {:?}", headers))?;
+        info!("Detected {:?} BSE bhavcopy format for {}", format, date);
+        Ok(ColumnMap::resolve(format.column_names(), headers))
+    })
+}
@@ -0,0 +1,233 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::source::{MarketDataSource, RawFile, StaleFile};
+
+/// Content-addressed archive of downloaded files.
+///
+/// Bodies live under `objects/<first two hex digits>/<sha256>`, so a file
+/// downloaded twice is stored once. `index/<source>/<YYYY-MM-DD>.json`
+/// records which object holds a source's file for a trade date.
+pub struct RawFileCache {
+    root: PathBuf,
+}
+
+/// Where a cached file came from and its checksum.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub sha256: String,
+    pub url: String,
+    pub content_type: Option<String>,
+    pub fetched_at: NaiveDateTime,
+}
+
+impl CacheEntry {
+    /// Value recorded as `ingestion_log.file_name`.
+    pub fn log_file_name(&self) -> String {
+        format!("{} sha256:{}", self.url, self.sha256)
+    }
+}
+
+/// How ingestion uses the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Use the cached file when there is one, otherwise download and cache it
+    #[default]
+    Prefer,
+    /// Always download, replacing the cached file
+    Refresh,
+    /// Never download; dates that are not cached fail
+    Offline,
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl RawFileCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        RawFileCache { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        self.root.join("objects").join(&sha256[..2]).join(sha256)
+    }
+
+    fn index_path(&self, source: &str, date: NaiveDate) -> PathBuf {
+        self.root
+            .join("index")
+            .join(source)
+            .join(format!("{}.json", date.format("%Y-%m-%d")))
+    }
+
+    /// Archives `raw` as `source`'s file for `date`.
+    pub fn store(&self, source: &str, date: NaiveDate, raw: &RawFile) -> Result<CacheEntry> {
+        let sha256 = sha256_hex(&raw.body);
+        let object_path = self.object_path(&sha256);
+        if !object_path.exists() {
+            write_atomically(&object_path, &raw.body)?;
+        }
+
+        let entry = CacheEntry {
+            sha256,
+            url: raw.file_name.clone(),
+            content_type: raw.content_type.clone(),
+            fetched_at: chrono::Local::now().naive_local(),
+        };
+        write_atomically(&self.index_path(source, date), &serde_json::to_vec_pretty(&entry)?)?;
+
+        Ok(entry)
+    }
+
+    /// The cached file for `date`, checked against its recorded checksum.
+    pub fn load(&self, source: &str, date: NaiveDate) -> Result<Option<(RawFile, CacheEntry)>> {
+        let index_path = self.index_path(source, date);
+        if !index_path.exists() {
+            return Ok(None);
+        }
+
+        let entry: CacheEntry = serde_json::from_slice(&fs::read(&index_path)?)
+            .with_context(|| format!("Corrupt cache index {}", index_path.display()))?;
+        let object_path = self.object_path(&entry.sha256);
+        let body = match fs::read(&object_path) {
+            Ok(body) => body,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                warn!("Cache index {} points at missing object {}", index_path.display(), entry.sha256);
+                return Ok(None);
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        if sha256_hex(&body) != entry.sha256 {
+            return Err(anyhow!("Cached file {} does not match its checksum", object_path.display()));
+        }
+
+        let raw = RawFile {
+            file_name: entry.url.clone(),
+            content_type: entry.content_type.clone(),
+            body,
+        };
+        Ok(Some((raw, entry)))
+    }
+
+    /// Drops `source`'s cached file for `date` when parsing it failed with a
+    /// [`StaleFile`], so the next fetch downloads it again instead of reusing
+    /// the wrong day's file. The object stays in case another date uses it.
+    pub fn evict_if_stale<T>(&self, source: &str, date: NaiveDate, parsed: &Result<T>) -> Result<()> {
+        let Err(e) = parsed else { return Ok(()) };
+        if e.downcast_ref::<StaleFile>().is_none() {
+            return Ok(());
+        }
+
+        let index_path = self.index_path(source, date);
+        match fs::remove_file(&index_path) {
+            Ok(()) => {
+                info!("Evicted stale {} file for {} from the cache", source, date);
+                Ok(())
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Trade dates between `from` and `to` with a cached file, in order.
+    pub fn cached_dates(&self, source: &str, from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>> {
+        let dir = self.root.join("index").join(source);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut dates = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let date = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+                .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok());
+            if let Some(date) = date.filter(|date| *date >= from && *date <= to) {
+                dates.push(date);
+            }
+        }
+        dates.sort();
+        Ok(dates)
+    }
+}
+
+/// Gets `source`'s file for `date`, going to the network only as `mode`
+/// allows, and archives anything downloaded.
+pub async fn fetch_with_cache(
+    source: &dyn MarketDataSource,
+    cache: &RawFileCache,
+    date: NaiveDate,
+    mode: CacheMode,
+) -> Result<(RawFile, CacheEntry)> {
+    fetch_named_with_cache(cache, source.name(), date, mode, || source.fetch(date)).await
+}
+
+/// Gets `source`'s delivery position file for `date`, archived under its
+/// delivery source name.
+pub async fn fetch_delivery_with_cache(
+    source: &dyn MarketDataSource,
+    cache: &RawFileCache,
+    date: NaiveDate,
+    mode: CacheMode,
+) -> Result<(RawFile, CacheEntry)> {
+    let name = source
+        .delivery_source()
+        .ok_or_else(|| anyhow!("{} does not publish delivery data", source.name()))?;
+    fetch_named_with_cache(cache, name, date, mode, || source.fetch_delivery(date)).await
+}
+
+async fn fetch_named_with_cache<F>(
+    cache: &RawFileCache,
+    name: &str,
+    date: NaiveDate,
+    mode: CacheMode,
+    fetch: impl FnOnce() -> F,
+) -> Result<(RawFile, CacheEntry)>
+where
+    F: Future<Output = Result<RawFile>>,
+{
+    if mode != CacheMode::Refresh
+        && let Some((raw, entry)) = cache.load(name, date)?
+    {
+        info!("Using cached {} file for {} ({})", name, date, entry.sha256);
+        return Ok((raw, entry));
+    }
+
+    if mode == CacheMode::Offline {
+        return Err(anyhow!(
+            "No cached {} file for {} in {} (offline mode)",
+            name,
+            date,
+            cache.root.display()
+        ));
+    }
+
+    let raw = fetch().await?;
+    let entry = cache.store(name, date, &raw)?;
+    Ok((raw, entry))
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
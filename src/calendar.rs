@@ -0,0 +1,276 @@
+use anyhow::{Result, anyhow};
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// NSE equity-segment holidays and special sessions shipped with the binary.
+/// BSE publishes the same equity holiday list, so both exchanges use it.
+const BUNDLED_NSE_CALENDAR: &str = include_str!("../data/holidays/nse.csv");
+
+/// Indian Standard Time (Asia/Kolkata), which has no daylight saving.
+pub fn exchange_timezone() -> FixedOffset {
+    FixedOffset::east_opt(5 * 3600 + 30 * 60).expect("valid IST offset")
+}
+
+/// The current wall-clock time in Asia/Kolkata, whatever the host timezone.
+pub fn exchange_now() -> NaiveDateTime {
+    Utc::now().with_timezone(&exchange_timezone()).naive_local()
+}
+
+/// Time of day (IST) after which the day's bhavcopy is normally published.
+pub fn default_publication_cutoff() -> NaiveTime {
+    NaiveTime::from_hms_opt(18, 30, 0).expect("valid cutoff")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionKind {
+    /// The exchange is closed on a day that would otherwise be a weekday
+    Holiday,
+    /// The exchange trades on a day that would otherwise be closed, e.g.
+    /// Muhurat trading on a Sunday
+    SpecialSession,
+}
+
+impl SessionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionKind::Holiday => "holiday",
+            SessionKind::SpecialSession => "special_session",
+        }
+    }
+}
+
+impl fmt::Display for SessionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SessionKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+            "" | "holiday" | "closed" => Ok(SessionKind::Holiday),
+            "special_session" | "special" | "muhurat" => Ok(SessionKind::SpecialSession),
+            other => Err(anyhow!("Unknown market day kind '{}'", other)),
+        }
+    }
+}
+
+/// A holiday or special session on one exchange.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketDay {
+    pub exchange: String,
+    pub date: NaiveDate,
+    pub kind: SessionKind,
+    pub description: String,
+}
+
+/// Trading days of one exchange: weekdays, minus holidays, plus special
+/// sessions. Years without any holiday list are weekends-only.
+#[derive(Debug, Clone, Default)]
+pub struct TradingCalendar {
+    holidays: HashMap<NaiveDate, String>,
+    special_sessions: HashMap<NaiveDate, String>,
+    covered_years: BTreeSet<i32>,
+}
+
+impl TradingCalendar {
+    /// Calendar with only weekends closed.
+    pub fn weekends_only() -> Self {
+        Self::default()
+    }
+
+    /// The bundled holiday list for `exchange`.
+    pub fn bundled(exchange: &str) -> Self {
+        let mut calendar = Self::default();
+        if matches!(exchange.to_ascii_uppercase().as_str(), "NSE" | "BSE") {
+            let days = parse_calendar_csv(BUNDLED_NSE_CALENDAR, exchange)
+                .expect("bundled holiday calendar is valid");
+            calendar.extend(&days);
+        }
+        calendar
+    }
+
+    /// Adds (or overrides) holidays and special sessions. Their years count
+    /// as covered by a holiday list.
+    pub fn extend(&mut self, days: &[MarketDay]) {
+        for day in days {
+            self.covered_years.insert(day.date.year());
+            match day.kind {
+                SessionKind::Holiday => {
+                    self.special_sessions.remove(&day.date);
+                    self.holidays.insert(day.date, day.description.clone());
+                },
+                SessionKind::SpecialSession => {
+                    self.holidays.remove(&day.date);
+                    self.special_sessions.insert(day.date, day.description.clone());
+                },
+            }
+        }
+    }
+
+    /// Years between `from` and `to` that no holiday list covers, where only
+    /// weekends are treated as closed.
+    pub fn uncovered_years(&self, from: NaiveDate, to: NaiveDate) -> Vec<i32> {
+        (from.year()..=to.year())
+            .filter(|year| !self.covered_years.contains(year))
+            .collect()
+    }
+
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        if self.special_sessions.contains_key(&date) {
+            return true;
+        }
+        !is_weekend(date) && !self.holidays.contains_key(&date)
+    }
+
+    /// Description of the holiday on `date`, if the exchange is closed for one.
+    pub fn holiday(&self, date: NaiveDate) -> Option<&str> {
+        self.holidays.get(&date).map(|s| s.as_str())
+    }
+
+    pub fn special_session(&self, date: NaiveDate) -> Option<&str> {
+        self.special_sessions.get(&date).map(|s| s.as_str())
+    }
+
+    pub fn trading_dates_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        from.iter_days()
+            .take_while(|date| *date <= to)
+            .filter(|date| self.is_trading_day(*date))
+            .collect()
+    }
+
+    /// The last trading day on or before `date`.
+    pub fn trading_day_on_or_before(&self, date: NaiveDate) -> NaiveDate {
+        let mut current = date;
+        while !self.is_trading_day(current) {
+            match current.pred_opt() {
+                Some(previous) => current = previous,
+                None => return date,
+            }
+        }
+        current
+    }
+
+    /// The most recent trading day whose bhavcopy should be out at `now`
+    /// (IST): today once `cutoff` has passed, otherwise the trading day
+    /// before.
+    pub fn latest_published_date(&self, now: NaiveDateTime, cutoff: NaiveTime) -> NaiveDate {
+        let today = now.date();
+        if self.is_trading_day(today) && now.time() < cutoff {
+            self.previous_trading_day(today)
+        } else {
+            self.trading_day_on_or_before(today)
+        }
+    }
+
+    /// The last trading day strictly before `date`.
+    pub fn previous_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        date.pred_opt()
+            .map(|previous| self.trading_day_on_or_before(previous))
+            .unwrap_or(date)
+    }
+
+    /// The first trading day strictly after `date`.
+    pub fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut current = date;
+        loop {
+            match current.succ_opt() {
+                Some(next) => current = next,
+                None => return date,
+            }
+            if self.is_trading_day(current) {
+                return current;
+            }
+        }
+    }
+
+    /// Holidays falling on weekdays between `from` and `to`, in date order.
+    pub fn holidays_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, &str)> {
+        let mut holidays: Vec<_> = self
+            .holidays
+            .iter()
+            .filter(|(date, _)| **date >= from && **date <= to && !is_weekend(**date))
+            .map(|(date, description)| (*date, description.as_str()))
+            .collect();
+        holidays.sort_by_key(|(date, _)| *date);
+        holidays
+    }
+}
+
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarRow {
+    date: String,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    exchange: Option<String>,
+}
+
+impl CalendarRow {
+    fn into_market_day(self, default_exchange: &str) -> Result<MarketDay> {
+        let date = parse_calendar_date(&self.date)?;
+        Ok(MarketDay {
+            exchange: self
+                .exchange
+                .filter(|e| !e.trim().is_empty())
+                .unwrap_or_else(|| default_exchange.to_string())
+                .trim()
+                .to_ascii_uppercase(),
+            date,
+            kind: self.kind.as_deref().unwrap_or("").parse()?,
+            description: self.description.unwrap_or_default().trim().to_string(),
+        })
+    }
+}
+
+fn parse_calendar_date(raw: &str) -> Result<NaiveDate> {
+    let raw = raw.trim();
+    // NSE's own circulars use 26-Jan-2025 style dates
+    ["%Y-%m-%d", "%d-%b-%Y", "%d-%m-%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(raw, format).ok())
+        .ok_or_else(|| anyhow!("Invalid calendar date '{}'", raw))
+}
+
+/// Parses a `date,kind,description[,exchange]` CSV. `kind` defaults to
+/// holiday; `exchange` defaults to `default_exchange`.
+pub fn parse_calendar_csv(content: &str, default_exchange: &str) -> Result<Vec<MarketDay>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(Cursor::new(content));
+
+    let mut days = Vec::new();
+    for (index, row) in reader.deserialize::<CalendarRow>().enumerate() {
+        let day = row
+            .map_err(anyhow::Error::from)
+            .and_then(|row| row.into_market_day(default_exchange))
+            .map_err(|e| anyhow!("Calendar row {}: {}", index + 2, e))?;
+        days.push(day);
+    }
+    Ok(days)
+}
+
+/// Parses a JSON array of `{"date", "kind", "description", "exchange"}`
+/// objects with the same defaults as the CSV format.
+pub fn parse_calendar_json(content: &str, default_exchange: &str) -> Result<Vec<MarketDay>> {
+    let rows: Vec<CalendarRow> = serde_json::from_str(content)?;
+    rows.into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            row.into_market_day(default_exchange)
+                .map_err(|e| anyhow!("Calendar entry {}: {}", index, e))
+        })
+        .collect()
+}
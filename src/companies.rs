@@ -0,0 +1,287 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+use crate::corporate_actions::parse_action_date;
+
+/// Whether a listing still trades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListingStatus {
+    #[default]
+    Active,
+    /// Trading halted by the exchange; the listing may resume
+    Suspended,
+    /// Removed from the exchange, or missing from its latest equity list
+    Delisted,
+}
+
+impl ListingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListingStatus::Active => "active",
+            ListingStatus::Suspended => "suspended",
+            ListingStatus::Delisted => "delisted",
+        }
+    }
+}
+
+impl fmt::Display for ListingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ListingStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "active" | "listed" => Ok(ListingStatus::Active),
+            "suspended" => Ok(ListingStatus::Suspended),
+            "delisted" => Ok(ListingStatus::Delisted),
+            other => Err(anyhow!("Unknown listing status '{}', expected active, suspended or delisted", other)),
+        }
+    }
+}
+
+/// One security from NSE's equity list (`EQUITY_L.csv`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasterRecord {
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub isin: String,
+    pub name: String,
+    pub listing_date: Option<NaiveDate>,
+    pub paid_up_value: Option<f64>,
+    pub market_lot: Option<i64>,
+    pub face_value: Option<f64>,
+}
+
+/// A security's sector and industry, from an NSE index constituents file
+/// or a file in the same layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub symbol: Option<String>,
+    pub isin: Option<String>,
+    pub name: Option<String>,
+    pub sector: Option<String>,
+    pub industry: Option<String>,
+}
+
+/// A listing to flag as suspended or delisted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    pub exchange: String,
+    pub symbol: String,
+    pub isin: Option<String>,
+    pub status: ListingStatus,
+    /// Date of the suspension or delisting, if the file gives one
+    pub date: Option<NaiveDate>,
+}
+
+/// What importing an equity list did.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MasterImportSummary {
+    pub imported: usize,
+    /// Listings that had not been ingested yet
+    pub new_listings: usize,
+    /// Listings in an earlier equity list but not in this one
+    pub delisted: usize,
+    /// Delisted listings that are back in the list
+    pub relisted: usize,
+}
+
+/// A listing's master data, for `companies search` and `companies show`.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct CompanyProfile {
+    pub id: i64,
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub isin: Option<String>,
+    pub name: Option<String>,
+    pub listing_date: Option<NaiveDate>,
+    pub paid_up_value: Option<f64>,
+    pub market_lot: Option<i64>,
+    pub face_value: Option<f64>,
+    pub sector: Option<String>,
+    pub industry: Option<String>,
+    pub status: String,
+    pub status_date: Option<NaiveDate>,
+    pub first_trade_date: Option<NaiveDate>,
+    pub last_trade_date: Option<NaiveDate>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty() && value != "-")
+}
+
+fn reader(content: &str) -> csv::Reader<Cursor<&str>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(Cursor::new(content))
+}
+
+#[derive(Debug, Deserialize)]
+struct MasterRow {
+    #[serde(alias = "SYMBOL")]
+    symbol: String,
+    #[serde(alias = "NAME OF COMPANY")]
+    name: String,
+    #[serde(default, alias = "SERIES")]
+    series: Option<String>,
+    #[serde(default, alias = "DATE OF LISTING")]
+    listing_date: Option<String>,
+    #[serde(default, alias = "PAID UP VALUE")]
+    paid_up_value: Option<String>,
+    #[serde(default, alias = "MARKET LOT")]
+    market_lot: Option<String>,
+    #[serde(alias = "ISIN NUMBER", alias = "ISIN")]
+    isin: String,
+    #[serde(default, alias = "FACE VALUE")]
+    face_value: Option<String>,
+}
+
+impl MasterRow {
+    fn into_record(self) -> Result<MasterRecord> {
+        let symbol = self.symbol.trim().to_ascii_uppercase();
+        if symbol.is_empty() {
+            return Err(anyhow!("Missing symbol"));
+        }
+        let number = |value: Option<String>, column: &str| -> Result<Option<f64>> {
+            non_empty(value)
+                .map(|raw| raw.parse().map_err(|_| anyhow!("Invalid {} '{}'", column, raw)))
+                .transpose()
+        };
+        Ok(MasterRecord {
+            exchange: "NSE".to_string(),
+            symbol,
+            series: non_empty(self.series).unwrap_or_default().to_ascii_uppercase(),
+            isin: self.isin.trim().to_ascii_uppercase(),
+            name: self.name.trim().to_string(),
+            listing_date: non_empty(self.listing_date)
+                .map(|raw| parse_action_date(&raw))
+                .transpose()?,
+            paid_up_value: number(self.paid_up_value, "paid up value")?,
+            market_lot: number(self.market_lot, "market lot")?.map(|lot| lot as i64),
+            face_value: number(self.face_value, "face value")?,
+        })
+    }
+}
+
+/// Whether `content` is NSE's equity list rather than a classification file.
+pub fn is_equity_list(content: &str) -> bool {
+    content
+        .lines()
+        .next()
+        .is_some_and(|header| header.to_ascii_uppercase().contains("NAME OF COMPANY"))
+}
+
+/// Parses NSE's `EQUITY_L.csv` (`SYMBOL`, `NAME OF COMPANY`, `SERIES`,
+/// `DATE OF LISTING`, `PAID UP VALUE`, `MARKET LOT`, `ISIN NUMBER`,
+/// `FACE VALUE`).
+pub fn parse_equity_list_csv(content: &str) -> Result<Vec<MasterRecord>> {
+    let mut records = Vec::new();
+    for (index, row) in reader(content).deserialize::<MasterRow>().enumerate() {
+        let record = row
+            .map_err(anyhow::Error::from)
+            .and_then(MasterRow::into_record)
+            .map_err(|e| anyhow!("Equity list row {}: {}", index + 2, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[derive(Debug, Deserialize)]
+struct ClassificationRow {
+    #[serde(default, alias = "Symbol", alias = "SYMBOL")]
+    symbol: Option<String>,
+    #[serde(default, alias = "ISIN Code", alias = "ISIN", alias = "ISIN NUMBER")]
+    isin: Option<String>,
+    #[serde(default, alias = "Company Name", alias = "COMPANY NAME")]
+    name: Option<String>,
+    #[serde(default, alias = "Sector", alias = "SECTOR")]
+    sector: Option<String>,
+    #[serde(default, alias = "Industry", alias = "INDUSTRY")]
+    industry: Option<String>,
+}
+
+/// Parses a sector and industry file: NSE's index constituent lists
+/// (`Company Name`, `Industry`, `Symbol`, `Series`, `ISIN Code`) or any CSV
+/// with a symbol or ISIN column and a sector or industry column.
+pub fn parse_classification_csv(content: &str) -> Result<Vec<Classification>> {
+    let mut classifications = Vec::new();
+    for (index, row) in reader(content).deserialize::<ClassificationRow>().enumerate() {
+        let row = row.map_err(|e| anyhow!("Classification row {}: {}", index + 2, e))?;
+        let classification = Classification {
+            symbol: non_empty(row.symbol).map(|symbol| symbol.to_ascii_uppercase()),
+            isin: non_empty(row.isin).map(|isin| isin.to_ascii_uppercase()),
+            name: non_empty(row.name),
+            sector: non_empty(row.sector),
+            industry: non_empty(row.industry),
+        };
+        if classification.symbol.is_none() && classification.isin.is_none() {
+            return Err(anyhow!("Classification row {}: Missing symbol or ISIN", index + 2));
+        }
+        if classification.sector.is_none() && classification.industry.is_none() {
+            return Err(anyhow!("Classification row {}: Missing sector or industry", index + 2));
+        }
+        classifications.push(classification);
+    }
+    Ok(classifications)
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusRow {
+    #[serde(alias = "SYMBOL", alias = "Symbol")]
+    symbol: String,
+    #[serde(default, alias = "ISIN", alias = "ISIN NUMBER", alias = "ISIN Code")]
+    isin: Option<String>,
+    #[serde(
+        default,
+        alias = "DATE",
+        alias = "Date",
+        alias = "SUSPENSION DATE",
+        alias = "Date of Suspension",
+        alias = "DELISTING DATE",
+        alias = "Date of Delisting"
+    )]
+    date: Option<String>,
+    #[serde(default, alias = "EXCHANGE")]
+    exchange: Option<String>,
+}
+
+/// Parses a list of suspended or delisted securities: a CSV with a symbol
+/// column and optional `isin`, `date` and `exchange` columns.
+pub fn parse_status_csv(content: &str, status: ListingStatus, default_exchange: &str) -> Result<Vec<StatusChange>> {
+    let mut changes = Vec::new();
+    for (index, row) in reader(content).deserialize::<StatusRow>().enumerate() {
+        let change = row
+            .map_err(anyhow::Error::from)
+            .and_then(|row| {
+                let symbol = row.symbol.trim().to_ascii_uppercase();
+                if symbol.is_empty() {
+                    return Err(anyhow!("Missing symbol"));
+                }
+                Ok(StatusChange {
+                    exchange: non_empty(row.exchange)
+                        .unwrap_or_else(|| default_exchange.to_string())
+                        .to_ascii_uppercase(),
+                    symbol,
+                    isin: non_empty(row.isin).map(|isin| isin.to_ascii_uppercase()),
+                    status,
+                    date: non_empty(row.date).map(|raw| parse_action_date(&raw)).transpose()?,
+                })
+            })
+            .map_err(|e| anyhow!("Status row {}: {}", index + 2, e))?;
+        changes.push(change);
+    }
+    Ok(changes)
+}
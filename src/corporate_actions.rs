@@ -0,0 +1,385 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+use crate::source::parse_trade_date;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// Face value split or consolidation
+    Split,
+    Bonus,
+    Dividend,
+    Rights,
+    /// Anything else: AGMs, buy-backs, demergers, ...
+    Other,
+}
+
+impl ActionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionKind::Split => "split",
+            ActionKind::Bonus => "bonus",
+            ActionKind::Dividend => "dividend",
+            ActionKind::Rights => "rights",
+            ActionKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ActionKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "split" | "consolidation" => Ok(ActionKind::Split),
+            "bonus" => Ok(ActionKind::Bonus),
+            "dividend" => Ok(ActionKind::Dividend),
+            "rights" => Ok(ActionKind::Rights),
+            "other" => Ok(ActionKind::Other),
+            other => Err(anyhow!("Unknown corporate action kind '{}'", other)),
+        }
+    }
+}
+
+/// What a purpose string such as "Bonus 1:1" says happens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionTerms {
+    pub kind: ActionKind,
+    /// Splits: face value before and after, in rupees
+    pub old_face_value: Option<f64>,
+    pub new_face_value: Option<f64>,
+    /// Bonus and rights issues: `ratio_new` new shares for every
+    /// `ratio_held` held
+    pub ratio_new: Option<i64>,
+    pub ratio_held: Option<i64>,
+    /// Dividend per share, or the premium over face value of a rights issue,
+    /// in rupees
+    pub amount: Option<f64>,
+}
+
+impl ActionTerms {
+    fn new(kind: ActionKind) -> Self {
+        ActionTerms {
+            kind,
+            old_face_value: None,
+            new_face_value: None,
+            ratio_new: None,
+            ratio_held: None,
+            amount: None,
+        }
+    }
+
+    /// Multiplier that puts prices before the ex-date on the same footing as
+    /// prices after it, for actions that change the share count: 0.2 for a
+    /// Rs 10 to Rs 2 split, 0.5 for a 1:1 bonus.
+    pub fn price_factor(&self) -> Option<f64> {
+        match self.kind {
+            ActionKind::Split => match (self.old_face_value, self.new_face_value) {
+                (Some(old), Some(new)) if old > 0.0 && new > 0.0 => Some(new / old),
+                _ => None,
+            },
+            ActionKind::Bonus => match (self.ratio_new, self.ratio_held) {
+                (Some(new), Some(held)) if new > 0 && held > 0 => Some(held as f64 / (new + held) as f64),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ActionTerms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, self.old_face_value, self.new_face_value, self.ratio_new, self.ratio_held, self.amount) {
+            (ActionKind::Split, Some(old), Some(new), ..) => write!(f, "split Rs {} -> Rs {}", old, new),
+            (ActionKind::Bonus, _, _, Some(new), Some(held), _) => write!(f, "bonus {}:{}", new, held),
+            (ActionKind::Rights, _, _, Some(new), Some(held), Some(premium)) => {
+                write!(f, "rights {}:{} @ premium Rs {}", new, held, premium)
+            },
+            (ActionKind::Rights, _, _, Some(new), Some(held), None) => write!(f, "rights {}:{}", new, held),
+            (ActionKind::Dividend, .., Some(amount)) => write!(f, "dividend Rs {}", amount),
+            (kind, ..) => write!(f, "{}", kind),
+        }
+    }
+}
+
+/// A corporate action on one listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorporateAction {
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    /// Links the action to every listing of the security in `companies`
+    pub isin: Option<String>,
+    pub company_name: Option<String>,
+    pub ex_date: NaiveDate,
+    pub record_date: Option<NaiveDate>,
+    /// The purpose text the terms were parsed from
+    pub purpose: String,
+    pub terms: ActionTerms,
+    /// `nse` for NSE's corporate actions file, `manual` otherwise
+    pub source: String,
+}
+
+/// Splits a purpose that announces several actions, e.g.
+/// "Annual General Meeting/Dividend - Rs 3.50 Per Share", and parses each.
+/// Parts that announce no split, bonus, dividend or rights are dropped
+/// unless nothing else is left.
+pub fn parse_purpose(purpose: &str, face_value: Option<f64>) -> Vec<(String, ActionTerms)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let chars: Vec<(usize, char)> = purpose.char_indices().collect();
+    for (index, (offset, c)) in chars.iter().enumerate() {
+        // "/" separates actions, but "Rs 10/- Per Share" is one amount
+        let next = chars.get(index + 1).map(|(_, c)| *c);
+        if *c == '/' && next.is_some_and(|next| next.is_alphabetic() || next == ' ') {
+            parts.push(&purpose[start..*offset]);
+            start = offset + 1;
+        }
+    }
+    parts.push(&purpose[start..]);
+
+    let parsed: Vec<(String, ActionTerms)> = parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| (part.to_string(), parse_purpose_part(part, face_value)))
+        .collect();
+
+    if parsed.iter().any(|(_, terms)| terms.kind != ActionKind::Other) {
+        parsed.into_iter().filter(|(_, terms)| terms.kind != ActionKind::Other).collect()
+    } else {
+        vec![(purpose.trim().to_string(), ActionTerms::new(ActionKind::Other))]
+    }
+}
+
+fn parse_purpose_part(part: &str, face_value: Option<f64>) -> ActionTerms {
+    let text = part.to_ascii_lowercase();
+
+    if text.contains("split") || text.contains("sub-division") || text.contains("subdivision") || text.contains("consolidation") {
+        let mut terms = ActionTerms::new(ActionKind::Split);
+        let amounts = rupee_amounts(&text);
+        if let [old, new, ..] = amounts[..] {
+            terms.old_face_value = Some(old);
+            terms.new_face_value = Some(new);
+        }
+        return terms;
+    }
+
+    if text.contains("bonus") {
+        let mut terms = ActionTerms::new(ActionKind::Bonus);
+        if let Some((new, held)) = share_ratio(&text) {
+            terms.ratio_new = Some(new);
+            terms.ratio_held = Some(held);
+        }
+        return terms;
+    }
+
+    if text.contains("rights") {
+        let mut terms = ActionTerms::new(ActionKind::Rights);
+        if let Some((new, held)) = share_ratio(&text) {
+            terms.ratio_new = Some(new);
+            terms.ratio_held = Some(held);
+        }
+        if let Some((_, after)) = text.split_once("premium") {
+            terms.amount = rupee_amounts(after).first().copied();
+        }
+        return terms;
+    }
+
+    if text.contains("dividend") || text.contains("div ") || text.ends_with("div") {
+        let mut terms = ActionTerms::new(ActionKind::Dividend);
+        terms.amount = rupee_amounts(&text).first().copied().or_else(|| {
+            // Older announcements give the dividend as a percentage of face value
+            let percent = percentage(&text)?;
+            Some(face_value? * percent / 100.0)
+        });
+        return terms;
+    }
+
+    ActionTerms::new(ActionKind::Other)
+}
+
+/// Numbers written as rupee amounts: "Rs 10", "Rs.2/-", "Re 0.50", "Rs10".
+fn rupee_amounts(text: &str) -> Vec<f64> {
+    let normalised = text.replace("/-", " ").replace("rs.", "rs ").replace("re.", "re ");
+    let tokens: Vec<&str> = normalised
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '(' || c == ')')
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    let mut amounts = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let value = match *token {
+            "rs" | "re" | "inr" => tokens.get(index + 1).and_then(|next| parse_amount(next)),
+            _ => token
+                .strip_prefix("rs")
+                .or_else(|| token.strip_prefix("re"))
+                .and_then(parse_amount),
+        };
+        if let Some(value) = value {
+            amounts.push(value);
+        }
+    }
+    amounts
+}
+
+fn parse_amount(token: &str) -> Option<f64> {
+    token
+        .trim_end_matches(['/', ',', '.'])
+        .replace(',', "")
+        .parse()
+        .ok()
+}
+
+/// The first "a:b" ratio, allowing spaces around the colon.
+fn share_ratio(text: &str) -> Option<(i64, i64)> {
+    let (before, after) = text.split_once(':')?;
+    let new: String = before
+        .trim_end()
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    let held: String = after.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+    Some((new.parse().ok()?, held.parse().ok()?))
+}
+
+fn percentage(text: &str) -> Option<f64> {
+    let (before, _) = text.split_once('%')?;
+    let digits: String = before
+        .trim_end()
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    digits.parse().ok()
+}
+
+/// A row of NSE's corporate actions CSV or of the manual import format.
+/// Both name the same fields, NSE in upper case.
+#[derive(Debug, Deserialize)]
+struct ActionRow {
+    #[serde(alias = "SYMBOL")]
+    symbol: String,
+    #[serde(default, alias = "SERIES")]
+    series: Option<String>,
+    #[serde(default, alias = "ISIN")]
+    isin: Option<String>,
+    #[serde(default, alias = "COMPANY NAME", alias = "COMPANY")]
+    company_name: Option<String>,
+    #[serde(alias = "PURPOSE")]
+    purpose: String,
+    #[serde(default, alias = "FACE VALUE")]
+    face_value: Option<String>,
+    #[serde(alias = "EX-DATE", alias = "EX DATE")]
+    ex_date: String,
+    #[serde(default, alias = "RECORD DATE")]
+    record_date: Option<String>,
+    #[serde(default, alias = "EXCHANGE")]
+    exchange: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty() && value != "-")
+}
+
+/// Parses the date formats NSE uses in its corporate announcement files:
+/// `2025-01-15`, `15-Jan-2025`, `15-JAN-25` and `15-01-2025`.
+pub fn parse_action_date(raw: &str) -> Result<NaiveDate> {
+    let raw = raw.trim();
+    parse_trade_date(raw)
+        .or_else(|| NaiveDate::parse_from_str(raw, "%d-%m-%Y").ok())
+        .ok_or_else(|| anyhow!("Invalid date '{}'", raw))
+}
+
+impl ActionRow {
+    fn into_actions(self, default_exchange: &str, source: &str) -> Result<Vec<CorporateAction>> {
+        let symbol = self.symbol.trim().to_ascii_uppercase();
+        if symbol.is_empty() {
+            return Err(anyhow!("Missing symbol"));
+        }
+        let ex_date = parse_action_date(&self.ex_date)?;
+        let record_date = non_empty(self.record_date)
+            .map(|raw| parse_action_date(&raw))
+            .transpose()?;
+        let face_value = non_empty(self.face_value).and_then(|raw| raw.parse().ok());
+        let exchange = non_empty(self.exchange)
+            .unwrap_or_else(|| default_exchange.to_string())
+            .to_ascii_uppercase();
+        let series = non_empty(self.series).unwrap_or_default().to_ascii_uppercase();
+        let isin = non_empty(self.isin).map(|isin| isin.to_ascii_uppercase());
+        let company_name = non_empty(self.company_name);
+
+        Ok(parse_purpose(&self.purpose, face_value)
+            .into_iter()
+            .map(|(purpose, terms)| CorporateAction {
+                exchange: exchange.clone(),
+                symbol: symbol.clone(),
+                series: series.clone(),
+                isin: isin.clone(),
+                company_name: company_name.clone(),
+                ex_date,
+                record_date,
+                purpose,
+                terms,
+                source: source.to_string(),
+            })
+            .collect())
+    }
+}
+
+/// Parses NSE's corporate actions CSV (`SYMBOL`, `COMPANY NAME`, `SERIES`,
+/// `PURPOSE`, `FACE VALUE`, `EX-DATE`, `RECORD DATE`, ...) or the manual
+/// `symbol,ex_date,purpose[,isin,series,record_date,exchange]` format.
+pub fn parse_corporate_actions_csv(content: &str, default_exchange: &str) -> Result<Vec<CorporateAction>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(Cursor::new(content));
+    let source = if reader.headers()?.iter().any(|header| header == "EX-DATE") {
+        "nse"
+    } else {
+        "manual"
+    };
+
+    let mut actions = Vec::new();
+    for (index, row) in reader.deserialize::<ActionRow>().enumerate() {
+        let parsed = row
+            .map_err(anyhow::Error::from)
+            .and_then(|row| row.into_actions(default_exchange, source))
+            .map_err(|e| anyhow!("Corporate action row {}: {}", index + 2, e))?;
+        actions.extend(parsed);
+    }
+    Ok(actions)
+}
+
+/// Parses a JSON array of objects in the manual format.
+pub fn parse_corporate_actions_json(content: &str, default_exchange: &str) -> Result<Vec<CorporateAction>> {
+    let rows: Vec<ActionRow> = serde_json::from_str(content)?;
+    let mut actions = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let parsed = row
+            .into_actions(default_exchange, "manual")
+            .map_err(|e| anyhow!("Corporate action {}: {}", index, e))?;
+        actions.extend(parsed);
+    }
+    Ok(actions)
+}
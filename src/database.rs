@@ -1,41 +1,140 @@
-use sqlx::{SqlitePool, Row};
-use anyhow::Result;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool, Row};
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Mutex;
 use tracing::{info, error};
 
+use crate::adjustment::{self, Adjustment, AdjustmentFactor, PriceBar};
+use crate::calendar::{MarketDay, TradingCalendar};
+use crate::companies::{Classification, CompanyProfile, ListingStatus, MasterImportSummary, MasterRecord, StatusChange};
+use crate::corporate_actions::{ActionKind, ActionTerms, CorporateAction};
+use crate::delivery::DeliveryRecord;
+use crate::reconcile::ClosingPrice;
+use crate::source::{SeriesFilter, StockRecord};
+use crate::symbols::{SymbolChange, SymbolChangeSummary, SymbolHistoryEntry};
+use crate::validation::{QualityIssue, Rule, Severity};
+
+// Rows per multi-row INSERT, keeping bound parameters well under SQLite's limit
+const COMPANY_BATCH_SIZE: usize = 500;
+const PRICE_BATCH_SIZE: usize = 500;
+const ISSUE_BATCH_SIZE: usize = 500;
+
+/// (exchange, symbol, series) identifying one listing in `companies`
+type ListingKey = (String, String, String);
+
 pub struct Database {
     pub pool: SqlitePool,
+    /// Listing key -> (company id, isin) for listings already committed
+    company_cache: Mutex<HashMap<ListingKey, (i64, String)>>,
 }
 
+/// Options for `location`, a file path or a `sqlite:` URL. The database
+/// file is only created if `create` is set.
+pub fn connect_options(location: &str, create: bool) -> Result<SqliteConnectOptions> {
+    let options = if location.starts_with("sqlite:") {
+        SqliteConnectOptions::from_str(location)?
+    } else {
+        SqliteConnectOptions::new().filename(location)
+    };
+    Ok(options.create_if_missing(create))
+}
+
+/// Versioned schema migrations embedded from `migrations/`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
 impl Database {
-    pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = SqlitePool::connect(database_url).await?;
-        Ok(Database { pool })
+    /// Connects, creating the database file if it does not exist, and
+    /// brings the schema up to date.
+    pub async fn new(location: &str) -> Result<Self> {
+        let pool = SqlitePool::connect_with(connect_options(location, true)?).await?;
+        let db = Self::from_pool(pool);
+        db.initialize().await?;
+        Ok(db)
+    }
+
+    /// Connects to an existing database without touching the schema.
+    /// `location` is either a file path or a `sqlite:` URL; a database that
+    /// does not exist is an error rather than being created empty.
+    pub async fn open(location: &str) -> Result<Self> {
+        let pool = SqlitePool::connect_with(connect_options(location, false)?)
+            .await
+            .with_context(|| format!("Cannot open database at {}; run 'market-data init-db' to create it", location))?;
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Fails with a clear message unless the schema has been fully migrated.
+    pub async fn ensure_initialized(&self) -> Result<()> {
+        let latest = Self::latest_schema_version();
+        match self.schema_version().await? {
+            None => Err(anyhow!(
+                "Database schema has not been initialised. Run 'market-data init-db' first."
+            )),
+            Some(version) if version < latest => Err(anyhow!(
+                "Database schema is at version {} but this build expects {}. Run 'market-data db migrate'.",
+                version,
+                latest
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        Database {
+            pool,
+            company_cache: Mutex::new(HashMap::new()),
+        }
     }
 
+    /// Applies any pending migrations.
     pub async fn initialize(&self) -> Result<()> {
-        info!("Initializing database schema");
-        
-        // Read and execute the schema
-        let schema = include_str!("../database_schema.sql");
-        
-        // Split the schema into individual statements and execute them
-        for statement in schema.split(';') {
-            let statement = statement.trim();
-            if !statement.is_empty() {
-                sqlx::query(statement)
-                    .execute(&self.pool)
-                    .await
-                    .map_err(|e| {
-                        error!("Failed to execute schema statement: {}", e);
-                        e
-                    })?;
-            }
-        }
-        
-        info!("Database schema initialized successfully");
+        let before = self.schema_version().await?;
+
+        MIGRATOR.run(&self.pool).await.map_err(|e| {
+            error!("Failed to apply database migrations: {}", e);
+            e
+        })?;
+
+        let after = self.schema_version().await?;
+        if before != after {
+            info!(
+                "Migrated database schema from version {} to {}",
+                before.map(|v| v.to_string()).unwrap_or("none".to_string()),
+                after.unwrap_or(0)
+            );
+        }
         Ok(())
     }
 
+    /// Highest successfully applied migration, or `None` for a database that
+    /// has never been migrated.
+    pub async fn schema_version(&self) -> Result<Option<i64>> {
+        let has_migrations_table: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !has_migrations_table {
+            return Ok(None);
+        }
+
+        let version: Option<i64> = sqlx::query_scalar(
+            "SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(version)
+    }
+
+    /// Latest migration version embedded in this binary.
+    pub fn latest_schema_version() -> i64 {
+        MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0)
+    }
+
     pub async fn get_ingestion_logs(&self, limit: Option<i32>) -> Result<Vec<IngestionLog>> {
         let limit = limit.unwrap_or(10);
         let rows = sqlx::query(
@@ -46,6 +145,7 @@ impl Database {
                 file_name,
                 trade_date,
                 records_processed,
+                records_rejected,
                 status,
                 error_message,
                 started_at,
@@ -67,6 +167,7 @@ impl Database {
                 file_name: row.get("file_name"),
                 trade_date: row.get("trade_date"),
                 records_processed: row.get("records_processed"),
+                records_rejected: row.get("records_rejected"),
                 status: row.get("status"),
                 error_message: row.get("error_message"),
                 started_at: row.get("started_at"),
@@ -77,79 +178,573 @@ impl Database {
         Ok(logs)
     }
 
-    pub async fn log_ingestion(&self, log: &IngestionLogInsert) -> Result<()> {
-        sqlx::query(
+    /// Records an ingestion run and returns its id.
+    pub async fn log_ingestion(&self, log: &IngestionLogInsert) -> Result<i64> {
+        let result = sqlx::query(
             r#"
             INSERT INTO ingestion_log (
                 source, file_name, trade_date, records_processed, 
-                status, error_message, started_at, completed_at
+                records_rejected, status, error_message, series_filter, started_at, completed_at
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(&log.source)
         .bind(&log.file_name)
-        .bind(&log.trade_date)
-        .bind(&log.records_processed)
+        .bind(log.trade_date)
+        .bind(log.records_processed)
+        .bind(log.records_rejected)
         .bind(&log.status)
         .bind(&log.error_message)
-        .bind(&log.started_at)
-        .bind(&log.completed_at)
+        .bind(&log.series_filter)
+        .bind(log.started_at)
+        .bind(log.completed_at)
         .execute(&self.pool)
         .await?;
 
-        Ok(())
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Stores the validation issues found by ingestion run `ingestion_id`.
+    pub async fn store_quality_issues(&self, ingestion_id: i64, issues: &[QualityIssue]) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+
+        for chunk in issues.chunks(ISSUE_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                r#"
+                INSERT INTO data_quality_issues (
+                    ingestion_id, exchange, trade_date, symbol, series, rule, severity, message
+                )
+                "#
+            );
+            query.push_values(chunk, |mut row, issue| {
+                row.push_bind(ingestion_id)
+                    .push_bind(&issue.exchange)
+                    .push_bind(issue.trade_date)
+                    .push_bind(&issue.symbol)
+                    .push_bind(&issue.series)
+                    .push_bind(issue.rule.as_str())
+                    .push_bind(issue.severity.as_str())
+                    .push_bind(&issue.message);
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+        Ok(issues.len())
+    }
+
+    /// Stored validation issues matching `filter`, newest trade date first.
+    pub async fn get_quality_issues(&self, filter: &QualityIssueFilter) -> Result<Vec<DataQualityIssue>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT id, ingestion_id, exchange, trade_date, symbol, series, rule, severity, message
+            FROM data_quality_issues
+            WHERE 1 = 1
+            "#
+        );
+        if let Some(exchange) = &filter.exchange {
+            query.push(" AND exchange = ").push_bind(exchange.to_ascii_uppercase());
+        }
+        if let Some(symbol) = &filter.symbol {
+            query.push(" AND symbol = ").push_bind(symbol.to_ascii_uppercase());
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND trade_date >= ").push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND trade_date <= ").push_bind(to);
+        }
+        if let Some(rule) = filter.rule {
+            query.push(" AND rule = ").push_bind(rule.as_str());
+        }
+        if let Some(severity) = filter.severity {
+            query.push(" AND severity = ").push_bind(severity.as_str());
+        }
+        query.push(" ORDER BY trade_date DESC, symbol, series, id");
+        if let Some(limit) = filter.limit {
+            query.push(" LIMIT ").push_bind(limit);
+        }
+
+        let issues = query
+            .build_query_as::<DataQualityIssue>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(issues)
+    }
+
+    /// Stored closes and PREVCLOSEs, ordered by listing and trade date, for
+    /// reconciling one day against the next.
+    pub async fn get_closing_prices(
+        &self,
+        exchange: Option<&str>,
+        symbol: Option<&str>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<ClosingPrice>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT
+                p.company_id, p.exchange, c.symbol, c.series, c.isin,
+                p.trade_date, p.close_price, p.prev_close
+            FROM daily_prices p
+            JOIN companies c ON c.id = p.company_id
+            WHERE p.close_price IS NOT NULL AND p.prev_close IS NOT NULL
+            "#
+        );
+        if let Some(exchange) = exchange {
+            query.push(" AND p.exchange = ").push_bind(exchange.to_ascii_uppercase());
+        }
+        if let Some(symbol) = symbol {
+            push_symbol_filter(&mut query, symbol);
+        }
+        if let Some(from) = from {
+            query.push(" AND p.trade_date >= ").push_bind(from);
+        }
+        if let Some(to) = to {
+            query.push(" AND p.trade_date <= ").push_bind(to);
+        }
+        query.push(" ORDER BY p.company_id, p.trade_date");
+
+        let prices = query
+            .build_query_as::<ClosingPrice>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(prices)
+    }
+
+    /// Imports corporate actions, refreshing any already stored for the same
+    /// listing, ex-date and purpose. Actions without an ISIN take the one
+    /// their symbol had on the ex-date, from `symbol_history`.
+    pub async fn upsert_corporate_actions(&self, actions: &[CorporateAction]) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+
+        for action in actions {
+            sqlx::query(
+                r#"
+                INSERT INTO corporate_actions (
+                    exchange, symbol, series, isin, company_name, ex_date, record_date, purpose,
+                    kind, old_face_value, new_face_value, ratio_new, ratio_held, amount, source
+                )
+                VALUES (
+                    ?, ?, ?,
+                    COALESCE(?, (
+                        SELECT isin FROM symbol_history
+                        WHERE exchange = ? AND symbol = ? AND isin != ''
+                        ORDER BY
+                            (valid_from IS NULL OR valid_from <= ?) AND (valid_to IS NULL OR valid_to >= ?) DESC,
+                            series = 'EQ' DESC,
+                            valid_to IS NULL DESC,
+                            id
+                        LIMIT 1
+                    )),
+                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+                )
+                ON CONFLICT (exchange, symbol, ex_date, purpose) DO UPDATE SET
+                    series = excluded.series,
+                    isin = COALESCE(excluded.isin, corporate_actions.isin),
+                    company_name = COALESCE(excluded.company_name, corporate_actions.company_name),
+                    record_date = excluded.record_date,
+                    kind = excluded.kind,
+                    old_face_value = excluded.old_face_value,
+                    new_face_value = excluded.new_face_value,
+                    ratio_new = excluded.ratio_new,
+                    ratio_held = excluded.ratio_held,
+                    amount = excluded.amount,
+                    source = excluded.source,
+                    updated_at = CURRENT_TIMESTAMP
+                "#
+            )
+            .bind(&action.exchange)
+            .bind(&action.symbol)
+            .bind(&action.series)
+            .bind(&action.isin)
+            .bind(&action.exchange)
+            .bind(&action.symbol)
+            .bind(action.ex_date)
+            .bind(action.ex_date)
+            .bind(&action.company_name)
+            .bind(action.ex_date)
+            .bind(action.record_date)
+            .bind(&action.purpose)
+            .bind(action.terms.kind.as_str())
+            .bind(action.terms.old_face_value)
+            .bind(action.terms.new_face_value)
+            .bind(action.terms.ratio_new)
+            .bind(action.terms.ratio_held)
+            .bind(action.terms.amount)
+            .bind(&action.source)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(actions.len())
+    }
+
+    /// Fills in the ISIN of corporate actions imported before their listing
+    /// was ingested. Returns the number of actions still without one.
+    pub async fn link_corporate_actions(&self) -> Result<i64> {
+        sqlx::query(
+            r#"
+            UPDATE corporate_actions
+            SET isin = COALESCE(
+                (
+                    SELECT h.isin FROM symbol_history h
+                    WHERE h.exchange = corporate_actions.exchange
+                        AND h.symbol = corporate_actions.symbol
+                        AND h.isin != ''
+                        AND (h.valid_from IS NULL OR h.valid_from <= corporate_actions.ex_date)
+                        AND (h.valid_to IS NULL OR h.valid_to >= corporate_actions.ex_date)
+                    ORDER BY h.series = 'EQ' DESC, h.id
+                    LIMIT 1
+                ),
+                (
+                    SELECT h.isin FROM symbol_history h
+                    WHERE h.exchange = corporate_actions.exchange
+                        AND h.symbol = corporate_actions.symbol
+                        AND h.isin != ''
+                    ORDER BY h.series = 'EQ' DESC, h.valid_to IS NULL DESC, h.id
+                    LIMIT 1
+                )
+            ),
+            updated_at = CURRENT_TIMESTAMP
+            WHERE isin IS NULL AND EXISTS (
+                SELECT 1 FROM symbol_history h
+                WHERE h.exchange = corporate_actions.exchange
+                    AND h.symbol = corporate_actions.symbol
+                    AND h.isin != ''
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let unlinked = sqlx::query_scalar("SELECT COUNT(*) FROM corporate_actions WHERE isin IS NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(unlinked)
+    }
+
+    /// Stored corporate actions matching `filter`, by ex-date. A symbol
+    /// matches actions recorded under it and actions on any security it
+    /// shares an ISIN with, e.g. the same company on the other exchange.
+    pub async fn get_corporate_actions(&self, filter: &CorporateActionFilter) -> Result<Vec<CorporateAction>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT
+                exchange, symbol, series, isin, company_name, ex_date, record_date, purpose,
+                kind, old_face_value, new_face_value, ratio_new, ratio_held, amount, source
+            FROM corporate_actions
+            WHERE 1 = 1
+            "#
+        );
+        if let Some(symbol) = &filter.symbol {
+            let symbol = symbol.to_ascii_uppercase();
+            query
+                .push(" AND (symbol = ")
+                .push_bind(symbol.clone())
+                .push(
+                    r#"
+                    OR isin IN (
+                        SELECT other.isin FROM symbol_history h
+                        JOIN symbol_history other ON other.company_id = h.company_id
+                        WHERE other.isin != '' AND h.symbol =
+                    "#
+                )
+                .push_bind(symbol.clone())
+                .push(
+                    r#"
+                    )
+                    OR (exchange, symbol) IN (
+                        SELECT other.exchange, other.symbol FROM symbol_history h
+                        JOIN symbol_history other ON other.company_id = h.company_id
+                        WHERE h.symbol =
+                    "#
+                )
+                .push_bind(symbol)
+                .push("))");
+        }
+        if let Some(isin) = &filter.isin {
+            query.push(" AND isin = ").push_bind(isin.to_ascii_uppercase());
+        }
+        if let Some(kind) = filter.kind {
+            query.push(" AND kind = ").push_bind(kind.as_str());
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND ex_date >= ").push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND ex_date <= ").push_bind(to);
+        }
+        query.push(" ORDER BY ex_date, exchange, symbol, id");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        rows.into_iter()
+            .map(|row| {
+                let kind: String = row.get("kind");
+                Ok(CorporateAction {
+                    exchange: row.get("exchange"),
+                    symbol: row.get("symbol"),
+                    series: row.get("series"),
+                    isin: row.get("isin"),
+                    company_name: row.get("company_name"),
+                    ex_date: row.get("ex_date"),
+                    record_date: row.get("record_date"),
+                    purpose: row.get("purpose"),
+                    terms: ActionTerms {
+                        kind: kind.parse()?,
+                        old_face_value: row.get("old_face_value"),
+                        new_face_value: row.get("new_face_value"),
+                        ratio_new: row.get("ratio_new"),
+                        ratio_held: row.get("ratio_held"),
+                        amount: row.get("amount"),
+                    },
+                    source: row.get("source"),
+                })
+            })
+            .collect()
     }
 
-    pub async fn upsert_company(&self, symbol: &str, isin: &str, series: &str) -> Result<i64> {
-        // Try to get existing company
-        let existing = sqlx::query(
-            "SELECT id FROM companies WHERE symbol = ?"
+    /// Recomputes the adjustment factors of securities whose corporate
+    /// actions changed, of listings that have none yet, and of dividends
+    /// whose reference close has since been stored. Returns the number of
+    /// securities recomputed.
+    pub async fn refresh_adjustment_factors(&self) -> Result<usize> {
+        let stale: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT isin FROM stale_adjustments
+            UNION
+            SELECT h.isin FROM symbol_history h
+            JOIN corporate_actions a ON a.isin = h.isin AND a.kind IN ('split', 'bonus', 'dividend')
+            WHERE NOT EXISTS (SELECT 1 FROM adjustment_factors f WHERE f.company_id = h.company_id)
+            UNION
+            SELECT h.isin FROM adjustment_factors f
+            JOIN symbol_history h ON h.company_id = f.company_id AND h.isin != ''
+            WHERE f.dividend IS NOT NULL AND f.reference_close IS NULL
+                AND EXISTS (
+                    SELECT 1 FROM daily_prices p
+                    WHERE p.company_id = f.company_id AND p.trade_date < f.ex_date
+                        AND p.close_price IS NOT NULL
+                )
+            "#
         )
-        .bind(symbol)
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        if let Some(row) = existing {
-            let id: i64 = row.get("id");
-            // Update the existing record
-            sqlx::query(
-                "UPDATE companies SET isin = ?, series = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+        for isin in &stale {
+            // Every listing that has traded under the ISIN, with the actions
+            // on every ISIN it has had
+            let listings: Vec<i64> = sqlx::query_scalar(
+                "SELECT id FROM companies WHERE isin = ? UNION SELECT company_id FROM symbol_history WHERE isin = ?"
             )
             .bind(isin)
-            .bind(series)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-            
-            Ok(id)
-        } else {
-            // Insert new company
-            let result = sqlx::query(
-                "INSERT INTO companies (symbol, isin, series) VALUES (?, ?, ?)"
-            )
-            .bind(symbol)
             .bind(isin)
-            .bind(series)
-            .execute(&self.pool)
+            .fetch_all(&self.pool)
             .await?;
-            
-            Ok(result.last_insert_rowid())
+
+            let mut factors = Vec::new();
+            for company_id in &listings {
+                let isins: Vec<String> = sqlx::query_scalar(
+                    r#"
+                    SELECT isin FROM companies WHERE id = ? AND isin IS NOT NULL AND isin != ''
+                    UNION
+                    SELECT isin FROM symbol_history WHERE company_id = ? AND isin != ''
+                    "#
+                )
+                .bind(company_id)
+                .bind(company_id)
+                .fetch_all(&self.pool)
+                .await?;
+                let mut actions = Vec::new();
+                for listing_isin in isins {
+                    actions.extend(
+                        self.get_corporate_actions(&CorporateActionFilter {
+                            isin: Some(listing_isin),
+                            ..Default::default()
+                        })
+                        .await?,
+                    );
+                }
+                let mut reference_closes = HashMap::new();
+                for action in actions.iter().filter(|action| action.terms.kind == ActionKind::Dividend) {
+                    let close: Option<f64> = sqlx::query_scalar(
+                        r#"
+                        SELECT close_price FROM daily_prices
+                        WHERE company_id = ? AND trade_date < ? AND close_price IS NOT NULL
+                        ORDER BY trade_date DESC
+                        LIMIT 1
+                        "#
+                    )
+                    .bind(company_id)
+                    .bind(action.ex_date)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                    reference_closes.insert(action.ex_date, close);
+                }
+                factors.extend(adjustment::compute_factors(*company_id, &actions, |date| {
+                    reference_closes.get(&date).copied().flatten()
+                }));
+            }
+
+            let mut tx = self.pool.begin().await?;
+            for company_id in &listings {
+                sqlx::query("DELETE FROM adjustment_factors WHERE company_id = ?")
+                    .bind(company_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            for factor in &factors {
+                sqlx::query(
+                    r#"
+                    INSERT INTO adjustment_factors (
+                        company_id, ex_date, split_factor, dividend_factor, dividend, reference_close
+                    )
+                    VALUES (?, ?, ?, ?, ?, ?)
+                    "#
+                )
+                .bind(factor.company_id)
+                .bind(factor.ex_date)
+                .bind(factor.split_factor)
+                .bind(factor.dividend_factor)
+                .bind(factor.dividend)
+                .bind(factor.reference_close)
+                .execute(&mut *tx)
+                .await?;
+            }
+            sqlx::query("DELETE FROM stale_adjustments WHERE isin = ?")
+                .bind(isin)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+
+        if !stale.is_empty() {
+            info!("Recomputed adjustment factors for {} securities", stale.len());
+        }
+        Ok(stale.len())
+    }
+
+    /// Cached adjustment factors of the given listings, by ex-date.
+    pub async fn get_adjustment_factors(&self, company_ids: &[i64]) -> Result<Vec<AdjustmentFactor>> {
+        let mut factors = Vec::new();
+        for chunk in company_ids.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                r#"
+                SELECT company_id, ex_date, split_factor, dividend_factor, dividend, reference_close
+                FROM adjustment_factors
+                WHERE company_id IN (
+                "#
+            );
+            let mut ids = query.separated(", ");
+            for company_id in chunk {
+                ids.push_bind(company_id);
+            }
+            query.push(") ORDER BY company_id, ex_date");
+            factors.extend(
+                query
+                    .build_query_as::<AdjustmentFactor>()
+                    .fetch_all(&self.pool)
+                    .await?,
+            );
+        }
+        Ok(factors)
+    }
+
+    /// Stored prices matching `filter`, ordered by listing and trade date.
+    pub async fn get_price_bars(&self, filter: &PriceFilter) -> Result<Vec<PriceBar>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT
+                p.company_id, p.exchange, c.symbol, c.series, p.trade_date,
+                COALESCE(p.open_price, 0.0) AS open,
+                COALESCE(p.high_price, 0.0) AS high,
+                COALESCE(p.low_price, 0.0) AS low,
+                COALESCE(p.close_price, 0.0) AS close,
+                COALESCE(p.last_price, 0.0) AS last,
+                COALESCE(p.prev_close, 0.0) AS prev_close,
+                CAST(COALESCE(p.total_traded_qty, 0) AS REAL) AS volume,
+                COALESCE(p.total_traded_value, 0.0) AS traded_value,
+                COALESCE(p.total_trades, 0) AS trades,
+                CAST(d.deliverable_qty AS REAL) AS deliverable_qty,
+                d.delivery_pct
+            FROM daily_prices p
+            JOIN companies c ON c.id = p.company_id
+            LEFT JOIN delivery_positions d ON d.company_id = p.company_id AND d.trade_date = p.trade_date
+            WHERE 1 = 1
+            "#
+        );
+        if let Some(exchange) = &filter.exchange {
+            query.push(" AND p.exchange = ").push_bind(exchange.to_ascii_uppercase());
+        }
+        if let Some(symbol) = &filter.symbol {
+            push_symbol_filter(&mut query, symbol);
+        }
+        if let Some(series) = &filter.series {
+            query.push(" AND c.series = ").push_bind(series.to_ascii_uppercase());
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND p.trade_date >= ").push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND p.trade_date <= ").push_bind(to);
+        }
+        query.push(" ORDER BY p.company_id, p.trade_date");
+
+        let bars = query.build_query_as::<PriceBar>().fetch_all(&self.pool).await?;
+        Ok(bars)
+    }
+
+    /// Stored prices matching `filter` with `adjustment` applied from the
+    /// cached factors. Call [`Database::refresh_adjustment_factors`] first
+    /// so that the factors reflect the latest corporate actions.
+    pub async fn get_adjusted_prices(&self, filter: &PriceFilter, adjustment: Adjustment) -> Result<Vec<PriceBar>> {
+        let mut bars = self.get_price_bars(filter).await?;
+        if adjustment != Adjustment::Raw {
+            let mut company_ids: Vec<i64> = bars.iter().map(|bar| bar.company_id).collect();
+            company_ids.dedup();
+            let factors = self.get_adjustment_factors(&company_ids).await?;
+            adjustment::adjust_prices(&mut bars, &factors, adjustment);
         }
+        Ok(bars)
     }
 
-    pub async fn upsert_daily_price(&self, company_id: i64, price_data: &crate::nse::StockRecord) -> Result<()> {
+    /// Resolves one listing as seen on `trade_date`, the way ingestion does,
+    /// and returns its company id.
+    pub async fn upsert_company(
+        &self,
+        exchange: &str,
+        symbol: &str,
+        isin: &str,
+        series: &str,
+        trade_date: NaiveDate,
+    ) -> Result<i64> {
+        let sighting = ListingSighting {
+            exchange,
+            symbol,
+            series,
+            isin,
+            trade_date,
+        };
+        let mut tx = self.pool.begin().await?;
+        let company_ids = self.upsert_companies(&mut tx, std::slice::from_ref(&sighting)).await?;
+        tx.commit().await?;
+        Ok(company_ids[&sighting.key()])
+    }
+
+    pub async fn upsert_daily_price(&self, company_id: i64, price_data: &StockRecord) -> Result<()> {
         sqlx::query(
             r#"
             INSERT OR REPLACE INTO daily_prices (
-                company_id, trade_date, open_price, high_price, low_price, 
+                company_id, exchange, trade_date, open_price, high_price, low_price, 
                 close_price, last_price, prev_close, total_traded_qty, 
                 total_traded_value, total_trades
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(company_id)
+        .bind(&price_data.exchange)
         .bind(price_data.timestamp)
         .bind(price_data.open)
         .bind(price_data.high)
@@ -166,23 +761,1299 @@ impl Database {
         Ok(())
     }
 
-    pub async fn store_stock_records(&self, records: &[crate::nse::StockRecord]) -> Result<usize> {
-        let mut stored_count = 0;
-        
+    /// Every listing of a security across exchanges and series.
+    pub async fn get_listings_by_isin(&self, isin: &str) -> Result<Vec<Listing>> {
+        let listings = sqlx::query_as::<_, Listing>(
+            r#"
+            SELECT id, exchange, symbol, series, isin
+            FROM companies
+            WHERE isin = ?
+            ORDER BY exchange, symbol, series
+            "#
+        )
+        .bind(isin)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(listings)
+    }
+
+    pub async fn get_exchange_statistics(&self) -> Result<Vec<ExchangeStatistics>> {
+        let stats = sqlx::query_as::<_, ExchangeStatistics>(
+            r#"
+            SELECT
+                e.code AS exchange,
+                (SELECT COUNT(*) FROM companies c WHERE c.exchange = e.code) AS companies,
+                (SELECT COUNT(*) FROM daily_prices p WHERE p.exchange = e.code) AS price_records,
+                (SELECT MAX(trade_date) FROM daily_prices p WHERE p.exchange = e.code) AS latest_trade_date
+            FROM exchanges e
+            ORDER BY e.code
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(stats)
+    }
+
+    /// Number of ISINs listed on more than one exchange.
+    pub async fn count_cross_listed_securities(&self) -> Result<i64> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS count FROM (
+                SELECT isin FROM companies
+                WHERE isin IS NOT NULL AND isin != ''
+                GROUP BY isin
+                HAVING COUNT(DISTINCT exchange) > 1
+            )
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Trade dates between `from` and `to` that are already ingested for the
+    /// series `series_filter` selects: a successful or partial run from
+    /// `source` stored those series, or, for dates no such run was logged
+    /// for, `exchange` has prices in them.
+    pub async fn get_ingested_dates(
+        &self,
+        exchange: &str,
+        source: &str,
+        series_filter: &SeriesFilter,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashSet<NaiveDate>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT DISTINCT p.trade_date FROM daily_prices p
+            JOIN companies c ON c.id = p.company_id
+            WHERE p.exchange = 
+            "#
+        );
+        query.push_bind(exchange);
+        push_date_and_series_filter(&mut query, "p", series_filter, from, to);
+        let stored: Vec<NaiveDate> = query.build_query_scalar().fetch_all(&self.pool).await?;
+
+        self.merge_logged_dates(source, series_filter, from, to, stored).await
+    }
+
+    /// Trade dates between `from` and `to` that already have delivery
+    /// positions for the series `series_filter` selects, with the same rules
+    /// as [`Self::get_ingested_dates`] for runs of the delivery `source`.
+    pub async fn get_delivery_dates(
+        &self,
+        exchange: &str,
+        source: &str,
+        series_filter: &SeriesFilter,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashSet<NaiveDate>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT DISTINCT d.trade_date FROM delivery_positions d
+            JOIN companies c ON c.id = d.company_id
+            WHERE c.exchange = 
+            "#
+        );
+        query.push_bind(exchange);
+        push_date_and_series_filter(&mut query, "d", series_filter, from, to);
+        let stored: Vec<NaiveDate> = query.build_query_scalar().fetch_all(&self.pool).await?;
+
+        self.merge_logged_dates(source, series_filter, from, to, stored).await
+    }
+
+    /// Dates with a successful or partial run of `source` that covered
+    /// `series_filter`, plus the `stored` dates that have no such run at
+    /// all, e.g. data ingested before runs were logged. A date whose runs
+    /// only stored other series is not done.
+    async fn merge_logged_dates(
+        &self,
+        source: &str,
+        series_filter: &SeriesFilter,
+        from: NaiveDate,
+        to: NaiveDate,
+        stored: Vec<NaiveDate>,
+    ) -> Result<HashSet<NaiveDate>> {
+        let runs: Vec<(NaiveDate, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT trade_date, series_filter FROM ingestion_log
+            WHERE source = ? AND status IN ('success', 'partial')
+                AND trade_date BETWEEN ? AND ?
+            "#
+        )
+        .bind(source)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let logged: HashSet<NaiveDate> = runs.iter().map(|(date, _)| *date).collect();
+        let mut dates: HashSet<NaiveDate> = runs
+            .into_iter()
+            .filter(|(_, series)| series_filter.is_covered_by(series.as_deref()))
+            .map(|(date, _)| date)
+            .collect();
+        dates.extend(stored.into_iter().filter(|date| !logged.contains(date)));
+        Ok(dates)
+    }
+
+    /// Imports holidays and special sessions, replacing any existing entry
+    /// for the same exchange and date.
+    pub async fn upsert_market_days(&self, days: &[MarketDay]) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+
+        for day in days {
+            sqlx::query(
+                r#"
+                INSERT INTO market_holidays (exchange, date, kind, description)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(exchange, date) DO UPDATE SET
+                    kind = excluded.kind,
+                    description = excluded.description
+                "#
+            )
+            .bind(&day.exchange)
+            .bind(day.date)
+            .bind(day.kind.as_str())
+            .bind(&day.description)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(days.len())
+    }
+
+    pub async fn get_market_days(&self, exchange: &str) -> Result<Vec<MarketDay>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT exchange, date, kind, description
+            FROM market_holidays
+            WHERE exchange = ?
+            ORDER BY date
+            "#
+        )
+        .bind(exchange)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let kind: String = row.get("kind");
+                Ok(MarketDay {
+                    exchange: row.get("exchange"),
+                    date: row.get("date"),
+                    kind: kind.parse()?,
+                    description: row.get("description"),
+                })
+            })
+            .collect()
+    }
+
+    /// The bundled calendar for `exchange` with imported holidays applied.
+    pub async fn trading_calendar(&self, exchange: &str) -> Result<TradingCalendar> {
+        let mut calendar = TradingCalendar::bundled(exchange);
+        calendar.extend(&self.get_market_days(exchange).await?);
+        Ok(calendar)
+    }
+
+    /// Stores one trade date's records in a single transaction: either every
+    /// record is written or none are.
+    pub async fn store_stock_records(&self, records: &[StockRecord]) -> Result<usize> {
+        self.write_stock_records(records, None).await
+    }
+
+    /// Replaces every price stored for `exchange` on `date` in a series
+    /// matching `series_filter` with `records`, in one transaction, so rows
+    /// a corrected parser no longer produces are dropped. Prices of other
+    /// series are kept.
+    pub async fn replace_stock_records(
+        &self,
+        exchange: &str,
+        date: NaiveDate,
+        series_filter: &SeriesFilter,
+        records: &[StockRecord],
+    ) -> Result<usize> {
+        self.write_stock_records(records, Some((exchange, date, series_filter))).await
+    }
+
+    async fn write_stock_records(
+        &self,
+        records: &[StockRecord],
+        replace: Option<(&str, NaiveDate, &SeriesFilter)>,
+    ) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some((exchange, date, series_filter)) = replace {
+            let stored: Vec<(i64, String)> = sqlx::query_as(
+                r#"
+                SELECT p.id, c.series FROM daily_prices p
+                JOIN companies c ON c.id = p.company_id
+                WHERE p.exchange = ? AND p.trade_date = ?
+                "#
+            )
+            .bind(exchange)
+            .bind(date)
+            .fetch_all(&mut *tx)
+            .await?;
+            let replaced: Vec<i64> = stored
+                .into_iter()
+                .filter(|(_, series)| series_filter.matches(series))
+                .map(|(id, _)| id)
+                .collect();
+            for chunk in replaced.chunks(PRICE_BATCH_SIZE) {
+                let mut query = QueryBuilder::<Sqlite>::new("DELETE FROM daily_prices WHERE id IN (");
+                let mut ids = query.separated(", ");
+                for id in chunk {
+                    ids.push_bind(id);
+                }
+                query.push(")");
+                query.build().execute(&mut *tx).await?;
+            }
+            info!("Replacing {} stored {} prices for {}", replaced.len(), exchange, date);
+        }
+
+        let sightings: Vec<ListingSighting> = records.iter().map(ListingSighting::from).collect();
+        let company_ids = self.upsert_companies(&mut tx, &sightings).await?;
+
+        for chunk in records.chunks(PRICE_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                r#"
+                INSERT OR REPLACE INTO daily_prices (
+                    company_id, exchange, trade_date, open_price, high_price, low_price,
+                    close_price, last_price, prev_close, total_traded_qty,
+                    total_traded_value, total_trades
+                )
+                "#
+            );
+            query.push_values(chunk, |mut row, record| {
+                let company_id = company_ids[&listing_key(record)];
+                row.push_bind(company_id)
+                    .push_bind(&record.exchange)
+                    .push_bind(record.timestamp)
+                    .push_bind(record.open)
+                    .push_bind(record.high)
+                    .push_bind(record.low)
+                    .push_bind(record.close)
+                    .push_bind(record.last)
+                    .push_bind(record.prevclose)
+                    .push_bind(record.tottrdqty)
+                    .push_bind(record.tottrdval)
+                    .push_bind(record.totaltrades);
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+
+        // Only remember ids once they are committed
+        let mut cache = self.company_cache.lock().expect("company cache lock poisoned");
+        for record in records {
+            let key = listing_key(record);
+            let id = company_ids[&key];
+            cache.insert(key, (id, record.isin.clone()));
+        }
+        drop(cache);
+
+        info!("Stored {} stock records in database", records.len());
+        Ok(records.len())
+    }
+
+    /// Stores delivery positions against the listing that traded under each
+    /// record's symbol and series on its trade date, replacing any stored for
+    /// the same day. Delivery files carry no ISIN, so records for listings
+    /// without stored prices are skipped; returns the number stored.
+    pub async fn store_delivery_records(&self, records: &[DeliveryRecord]) -> Result<usize> {
+        let mut stored = 0;
+        let mut tx = self.pool.begin().await?;
+
         for record in records {
-            // First ensure the company exists
-            let company_id = self.upsert_company(&record.symbol, &record.isin, &record.series).await?;
-            
-            // Then store the price data
-            self.upsert_daily_price(company_id, record).await?;
-            stored_count += 1;
+            stored += sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO delivery_positions (
+                    company_id, trade_date, traded_qty, deliverable_qty, delivery_pct
+                )
+                SELECT company_id, ?, ?, ?, ? FROM (
+                    SELECT COALESCE(
+                        (
+                            SELECT h.company_id FROM symbol_history h
+                            WHERE h.exchange = ? AND h.symbol = ? AND h.series = ?
+                                AND (h.valid_from IS NULL OR h.valid_from <= ?)
+                                AND (h.valid_to IS NULL OR h.valid_to >= ?)
+                            ORDER BY h.company_id DESC
+                            LIMIT 1
+                        ),
+                        (SELECT c.id FROM companies c WHERE c.exchange = ? AND c.symbol = ? AND c.series = ?)
+                    ) AS company_id
+                )
+                WHERE company_id IS NOT NULL
+                "#
+            )
+            .bind(record.trade_date)
+            .bind(record.traded_qty)
+            .bind(record.deliverable_qty)
+            .bind(record.delivery_pct)
+            .bind(&record.exchange)
+            .bind(&record.symbol)
+            .bind(&record.series)
+            .bind(record.trade_date)
+            .bind(record.trade_date)
+            .bind(&record.exchange)
+            .bind(&record.symbol)
+            .bind(&record.series)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+        info!("Stored {} of {} delivery records in database", stored, records.len());
+        Ok(stored)
+    }
+
+    /// Resolves the listing of every sighting, returning the company id of
+    /// each listing key. A key not stored yet belongs to an existing listing
+    /// if it traded under that symbol and ISIN before, or if a listing on the
+    /// same exchange and series has its ISIN (the symbol was renamed);
+    /// otherwise a new listing is inserted. Every sighting is then recorded
+    /// in `symbol_history`.
+    async fn upsert_companies(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        sightings: &[ListingSighting<'_>],
+    ) -> Result<HashMap<ListingKey, i64>> {
+        let mut company_ids = HashMap::new();
+        let mut pending: HashMap<ListingKey, &ListingSighting> = HashMap::new();
+        {
+            let cache = self.company_cache.lock().expect("company cache lock poisoned");
+            for sighting in sightings {
+                let key = sighting.key();
+                match cache.get(&key) {
+                    Some((id, isin)) if isin == sighting.isin => {
+                        company_ids.insert(key, *id);
+                    },
+                    _ => {
+                        pending.insert(key, sighting);
+                    },
+                }
+            }
+        }
+
+        let pending: Vec<&ListingSighting> = pending.into_values().collect();
+        if !pending.is_empty() {
+            let found = Self::find_current_listings(tx, &pending).await?;
+            let unresolved: Vec<&ListingSighting> = pending
+                .iter()
+                .copied()
+                .filter(|sighting| !found.contains_key(&sighting.key()))
+                .collect();
+            company_ids.extend(found);
+
+            let aliases = Self::find_listings_by_history(tx, &unresolved).await?;
+            let unresolved: Vec<&ListingSighting> = unresolved
+                .into_iter()
+                .filter(|sighting| !aliases.contains_key(&sighting.key()))
+                .collect();
+            company_ids.extend(aliases);
+
+            let renamed = Self::find_listings_by_isin(tx, &unresolved).await?;
+            let unresolved: Vec<&ListingSighting> = unresolved
+                .into_iter()
+                .filter(|sighting| !renamed.contains_key(&sighting.key()))
+                .collect();
+            company_ids.extend(renamed);
+
+            for chunk in unresolved.chunks(COMPANY_BATCH_SIZE) {
+                let mut query = QueryBuilder::<Sqlite>::new(
+                    "INSERT INTO companies (exchange, symbol, series, isin) "
+                );
+                query.push_values(chunk, |mut row, sighting| {
+                    row.push_bind(sighting.exchange)
+                        .push_bind(sighting.symbol)
+                        .push_bind(sighting.series)
+                        .push_bind(sighting.isin);
+                });
+                query.push(" ON CONFLICT (exchange, symbol, series) DO NOTHING");
+                query.build().execute(&mut **tx).await?;
+            }
+            company_ids.extend(Self::find_current_listings(tx, &unresolved).await?);
+        }
+
+        for chunk in sightings.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO symbol_history (company_id, exchange, symbol, series, isin, valid_from, valid_to) "
+            );
+            query.push_values(chunk, |mut row, sighting| {
+                row.push_bind(company_ids[&sighting.key()])
+                    .push_bind(sighting.exchange)
+                    .push_bind(sighting.symbol)
+                    .push_bind(sighting.series)
+                    .push_bind(sighting.isin)
+                    .push_bind(sighting.trade_date)
+                    .push_bind(sighting.trade_date);
+            });
+            // MIN and MAX return NULL if either side is NULL, which keeps an
+            // unbounded end unbounded
+            query.push(
+                r#"
+                ON CONFLICT (company_id, symbol, isin) DO UPDATE SET
+                    valid_from = MIN(symbol_history.valid_from, excluded.valid_from),
+                    valid_to = MAX(symbol_history.valid_to, excluded.valid_to)
+                WHERE symbol_history.valid_from > excluded.valid_from
+                    OR symbol_history.valid_to < excluded.valid_to
+                "#
+            );
+            query.build().execute(&mut **tx).await?;
+        }
+
+        let mut touched: Vec<i64> = pending.iter().map(|sighting| company_ids[&sighting.key()]).collect();
+        touched.sort_unstable();
+        touched.dedup();
+        Self::sync_current_symbols(tx, &touched).await?;
+
+        Ok(company_ids)
+    }
+
+    /// Listings stored under the sighted (exchange, symbol, series).
+    async fn find_current_listings(
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        sightings: &[&ListingSighting<'_>],
+    ) -> Result<HashMap<ListingKey, i64>> {
+        let mut found = HashMap::new();
+        for chunk in sightings.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "SELECT id, exchange, symbol, series FROM companies WHERE (exchange, symbol, series) IN ("
+            );
+            query.push_values(chunk, |mut row, sighting| {
+                row.push_bind(sighting.exchange)
+                    .push_bind(sighting.symbol)
+                    .push_bind(sighting.series);
+            });
+            query.push(")");
+            let rows = query
+                .build_query_as::<(i64, String, String, String)>()
+                .fetch_all(&mut **tx)
+                .await?;
+            for (id, exchange, symbol, series) in rows {
+                found.insert((exchange, symbol, series), id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Listings that traded under the sighted symbol with the same ISIN
+    /// before they were renamed.
+    async fn find_listings_by_history(
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        sightings: &[&ListingSighting<'_>],
+    ) -> Result<HashMap<ListingKey, i64>> {
+        let mut found = HashMap::new();
+        for chunk in sightings.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "SELECT company_id, exchange, symbol, series FROM symbol_history WHERE (exchange, symbol, series, isin) IN ("
+            );
+            query.push_values(chunk, |mut row, sighting| {
+                row.push_bind(sighting.exchange)
+                    .push_bind(sighting.symbol)
+                    .push_bind(sighting.series)
+                    .push_bind(sighting.isin);
+            });
+            query.push(") ORDER BY company_id");
+            let rows = query
+                .build_query_as::<(i64, String, String, String)>()
+                .fetch_all(&mut **tx)
+                .await?;
+            for (id, exchange, symbol, series) in rows {
+                found.entry((exchange, symbol, series)).or_insert(id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Listings on the sighted exchange and series whose current ISIN is the
+    /// sighted one, i.e. the same security under another symbol.
+    async fn find_listings_by_isin(
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        sightings: &[&ListingSighting<'_>],
+    ) -> Result<HashMap<ListingKey, i64>> {
+        let with_isin: Vec<&ListingSighting> = sightings
+            .iter()
+            .copied()
+            .filter(|sighting| !sighting.isin.is_empty())
+            .collect();
+        let mut by_isin = HashMap::new();
+        for chunk in with_isin.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "SELECT MAX(id), exchange, series, isin FROM companies WHERE (exchange, series, isin) IN ("
+            );
+            query.push_values(chunk, |mut row, sighting| {
+                row.push_bind(sighting.exchange)
+                    .push_bind(sighting.series)
+                    .push_bind(sighting.isin);
+            });
+            query.push(") GROUP BY exchange, series, isin");
+            let rows = query
+                .build_query_as::<(i64, String, String, String)>()
+                .fetch_all(&mut **tx)
+                .await?;
+            for (id, exchange, series, isin) in rows {
+                by_isin.insert((exchange, series, isin), id);
+            }
+        }
+
+        Ok(with_isin
+            .into_iter()
+            .filter_map(|sighting| {
+                let id = by_isin.get(&(
+                    sighting.exchange.to_string(),
+                    sighting.series.to_string(),
+                    sighting.isin.to_string(),
+                ))?;
+                Some((sighting.key(), *id))
+            })
+            .collect())
+    }
+
+    /// Makes each listing's latest `symbol_history` row (by `valid_from`,
+    /// then insertion order) its open-ended current one, closes the rows it
+    /// supersedes the day before it starts, and updates `companies` to the
+    /// current symbol and ISIN.
+    async fn sync_current_symbols(tx: &mut sqlx::Transaction<'_, Sqlite>, company_ids: &[i64]) -> Result<()> {
+        const LATER_ROW: &str = r#"
+            FROM symbol_history later
+            WHERE later.company_id = symbol_history.company_id
+                AND (COALESCE(later.valid_from, '') > COALESCE(symbol_history.valid_from, '')
+                    OR (COALESCE(later.valid_from, '') = COALESCE(symbol_history.valid_from, '')
+                        AND later.id > symbol_history.id))
+        "#;
+
+        for chunk in company_ids.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(format!(
+                r#"
+                UPDATE symbol_history
+                SET valid_to = (
+                    SELECT MAX(COALESCE(symbol_history.valid_from, ''), date(MIN(later.valid_from), '-1 day'))
+                    {LATER_ROW}
+                )
+                WHERE valid_to IS NULL AND EXISTS (SELECT 1 {LATER_ROW})
+                    AND company_id IN (
+                "#
+            ));
+            let mut ids = query.separated(", ");
+            for company_id in chunk {
+                ids.push_bind(company_id);
+            }
+            query.push(")");
+            query.build().execute(&mut **tx).await?;
+
+            let mut query = QueryBuilder::<Sqlite>::new(format!(
+                r#"
+                UPDATE symbol_history
+                SET valid_to = NULL
+                WHERE valid_to IS NOT NULL AND NOT EXISTS (SELECT 1 {LATER_ROW})
+                    AND company_id IN (
+                "#
+            ));
+            let mut ids = query.separated(", ");
+            for company_id in chunk {
+                ids.push_bind(company_id);
+            }
+            query.push(")");
+            query.build().execute(&mut **tx).await?;
+
+            let mut query = QueryBuilder::<Sqlite>::new(
+                r#"
+                UPDATE companies
+                SET symbol = current.symbol, isin = current.isin, updated_at = CURRENT_TIMESTAMP
+                FROM (SELECT company_id, symbol, isin FROM symbol_history WHERE valid_to IS NULL) AS current
+                WHERE current.company_id = companies.id
+                    AND (companies.symbol != current.symbol OR COALESCE(companies.isin, '') != current.isin)
+                    AND companies.id IN (
+                "#
+            );
+            let mut ids = query.separated(", ");
+            for company_id in chunk {
+                ids.push_bind(company_id);
+            }
+            query.push(")");
+            query.build().execute(&mut **tx).await?;
+        }
+        Ok(())
+    }
+
+    /// Applies ticker changes from NSE's symbol change file. The listing that
+    /// traded under the old symbol takes the new one from the effective
+    /// date; if the new symbol was already ingested as a separate listing,
+    /// its prices and history are merged into the old listing first.
+    pub async fn apply_symbol_changes(&self, changes: &[SymbolChange]) -> Result<SymbolChangeSummary> {
+        let mut summary = SymbolChangeSummary::default();
+        let mut tx = self.pool.begin().await?;
+
+        for change in changes {
+            let old_to = change.effective_date.pred_opt().unwrap_or(change.effective_date);
+            let listings: Vec<(i64, String)> = sqlx::query_as(
+                r#"
+                SELECT DISTINCT company_id, series FROM symbol_history
+                WHERE exchange = ? AND symbol = ? AND (valid_from IS NULL OR valid_from < ?)
+                ORDER BY company_id
+                "#
+            )
+            .bind(&change.exchange)
+            .bind(&change.old_symbol)
+            .bind(change.effective_date)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            if listings.is_empty() {
+                // Only the new symbol has been ingested: remember the old
+                // one so that lookups by it find the listing
+                let aliased = sqlx::query(
+                    r#"
+                    INSERT INTO symbol_history (company_id, exchange, symbol, series, isin, valid_from, valid_to, source)
+                    SELECT id, exchange, ?, series, COALESCE(isin, ''), NULL, ?, 'symbol_change'
+                    FROM companies
+                    WHERE exchange = ? AND symbol = ?
+                    ON CONFLICT (company_id, symbol, isin) DO NOTHING
+                    "#
+                )
+                .bind(&change.old_symbol)
+                .bind(old_to)
+                .bind(&change.exchange)
+                .bind(&change.new_symbol)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+                if aliased > 0 {
+                    sqlx::query(
+                        r#"
+                        UPDATE symbol_history SET valid_from = ?
+                        WHERE exchange = ? AND symbol = ? AND (valid_from IS NULL OR valid_from > ?)
+                            AND company_id IN (SELECT company_id FROM symbol_history WHERE exchange = ? AND symbol = ?)
+                        "#
+                    )
+                    .bind(change.effective_date)
+                    .bind(&change.exchange)
+                    .bind(&change.new_symbol)
+                    .bind(change.effective_date)
+                    .bind(&change.exchange)
+                    .bind(&change.old_symbol)
+                    .execute(&mut *tx)
+                    .await?;
+                    summary.applied += 1;
+                } else {
+                    summary.unmatched += 1;
+                }
+                continue;
+            }
+
+            for (company_id, series) in &listings {
+                let duplicate: Option<i64> = sqlx::query_scalar(
+                    "SELECT id FROM companies WHERE exchange = ? AND symbol = ? AND series = ? AND id != ?"
+                )
+                .bind(&change.exchange)
+                .bind(&change.new_symbol)
+                .bind(series)
+                .bind(company_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+                if let Some(duplicate) = duplicate {
+                    Self::merge_listings(&mut tx, duplicate, *company_id).await?;
+                    summary.merged += 1;
+                }
+
+                sqlx::query(
+                    r#"
+                    UPDATE symbol_history SET valid_to = ?
+                    WHERE company_id = ? AND symbol = ? AND (valid_to IS NULL OR valid_to < ?)
+                    "#
+                )
+                .bind(old_to)
+                .bind(company_id)
+                .bind(&change.old_symbol)
+                .bind(old_to)
+                .execute(&mut *tx)
+                .await?;
+
+                let widened = sqlx::query(
+                    r#"
+                    UPDATE symbol_history SET valid_from = ?
+                    WHERE company_id = ? AND symbol = ? AND (valid_from IS NULL OR valid_from > ?)
+                    "#
+                )
+                .bind(change.effective_date)
+                .bind(company_id)
+                .bind(&change.new_symbol)
+                .bind(change.effective_date)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+                let has_new_symbol: bool = sqlx::query_scalar(
+                    "SELECT EXISTS (SELECT 1 FROM symbol_history WHERE company_id = ? AND symbol = ?)"
+                )
+                .bind(company_id)
+                .bind(&change.new_symbol)
+                .fetch_one(&mut *tx)
+                .await?;
+                if widened == 0 && !has_new_symbol {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO symbol_history (company_id, exchange, symbol, series, isin, valid_from, valid_to, source)
+                        SELECT id, exchange, ?, series, COALESCE(isin, ''), ?, NULL, 'symbol_change'
+                        FROM companies WHERE id = ?
+                        "#
+                    )
+                    .bind(&change.new_symbol)
+                    .bind(change.effective_date)
+                    .bind(company_id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                Self::sync_current_symbols(&mut tx, &[*company_id]).await?;
+            }
+            summary.applied += 1;
+        }
+
+        tx.commit().await?;
+        // Merged listings no longer exist and renamed ones have new keys
+        self.company_cache.lock().expect("company cache lock poisoned").clear();
+        Ok(summary)
+    }
+
+    /// Moves the prices, symbol history and master data of listing `from`
+    /// into `into` and deletes `from`. Where both have a price for the same
+    /// day, or both have a master data field, `into`'s is kept. The status
+    /// comes from whichever listing was in the later equity list, e.g. the
+    /// new symbol's after a rename delisted the old one.
+    async fn merge_listings(tx: &mut sqlx::Transaction<'_, Sqlite>, from: i64, into: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE companies
+            SET status = merged.status, status_date = merged.status_date
+            FROM (SELECT status, status_date, master_list_date FROM companies WHERE id = ?) AS merged
+            WHERE companies.id = ?
+                AND (COALESCE(merged.master_list_date, ''), COALESCE(merged.status_date, ''))
+                    > (COALESCE(companies.master_list_date, ''), COALESCE(companies.status_date, ''))
+            "#
+        )
+        .bind(from)
+        .bind(into)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE companies
+            SET
+                name = COALESCE(companies.name, merged.name),
+                listing_date = COALESCE(companies.listing_date, merged.listing_date),
+                paid_up_value = COALESCE(companies.paid_up_value, merged.paid_up_value),
+                market_lot = COALESCE(companies.market_lot, merged.market_lot),
+                face_value = COALESCE(companies.face_value, merged.face_value),
+                sector = COALESCE(companies.sector, merged.sector),
+                industry = COALESCE(companies.industry, merged.industry),
+                master_list_date = COALESCE(
+                    MAX(companies.master_list_date, merged.master_list_date),
+                    companies.master_list_date,
+                    merged.master_list_date
+                ),
+                updated_at = CURRENT_TIMESTAMP
+            FROM (
+                SELECT name, listing_date, paid_up_value, market_lot, face_value, sector, industry, master_list_date
+                FROM companies WHERE id = ?
+            ) AS merged
+            WHERE companies.id = ?
+            "#
+        )
+        .bind(from)
+        .bind(into)
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query("UPDATE OR IGNORE daily_prices SET company_id = ? WHERE company_id = ?")
+            .bind(into)
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("DELETE FROM daily_prices WHERE company_id = ?")
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("UPDATE OR IGNORE delivery_positions SET company_id = ? WHERE company_id = ?")
+            .bind(into)
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("DELETE FROM delivery_positions WHERE company_id = ?")
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE symbol_history
+            SET
+                valid_from = MIN(symbol_history.valid_from, merged.valid_from),
+                valid_to = MAX(symbol_history.valid_to, merged.valid_to)
+            FROM (SELECT symbol, isin, valid_from, valid_to FROM symbol_history WHERE company_id = ?) AS merged
+            WHERE symbol_history.company_id = ?
+                AND merged.symbol = symbol_history.symbol AND merged.isin = symbol_history.isin
+            "#
+        )
+        .bind(from)
+        .bind(into)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query("UPDATE OR IGNORE symbol_history SET company_id = ? WHERE company_id = ?")
+            .bind(into)
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("DELETE FROM symbol_history WHERE company_id = ?")
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+
+        // The merged listing's factors are recomputed over its longer history
+        sqlx::query("DELETE FROM adjustment_factors WHERE company_id IN (?, ?)")
+            .bind(from)
+            .bind(into)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO stale_adjustments (isin)
+            SELECT DISTINCT isin FROM symbol_history WHERE company_id = ? AND isin != ''
+            "#
+        )
+        .bind(into)
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query("DELETE FROM companies WHERE id = ?")
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+        info!("Merged listing {} into listing {}", from, into);
+        Ok(())
+    }
+
+    /// Listings that have traded under `symbol`, now or in the past,
+    /// optionally only those that had it on `on`.
+    pub async fn find_listings_by_symbol(
+        &self,
+        exchange: Option<&str>,
+        symbol: &str,
+        on: Option<NaiveDate>,
+    ) -> Result<Vec<Listing>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT DISTINCT c.id, c.exchange, c.symbol, c.series, c.isin
+            FROM companies c
+            JOIN symbol_history h ON h.company_id = c.id
+            WHERE h.symbol =
+            "#
+        );
+        query.push_bind(symbol.to_ascii_uppercase());
+        if let Some(exchange) = exchange {
+            query.push(" AND h.exchange = ").push_bind(exchange.to_ascii_uppercase());
+        }
+        if let Some(on) = on {
+            query
+                .push(" AND (h.valid_from IS NULL OR h.valid_from <= ")
+                .push_bind(on)
+                .push(") AND (h.valid_to IS NULL OR h.valid_to >= ")
+                .push_bind(on)
+                .push(")");
+        }
+        query.push(" ORDER BY c.exchange, c.symbol, c.series");
+
+        let listings = query.build_query_as::<Listing>().fetch_all(&self.pool).await?;
+        Ok(listings)
+    }
+
+    /// Imports NSE's equity list as of `as_of`, creating listings that have
+    /// not been ingested and filling in their master data. Listings from an
+    /// earlier list that are missing from this one are flagged delisted.
+    pub async fn import_equity_list(&self, records: &[MasterRecord], as_of: NaiveDate) -> Result<MasterImportSummary> {
+        let mut summary = MasterImportSummary {
+            imported: records.len(),
+            ..Default::default()
+        };
+        let mut tx = self.pool.begin().await?;
+
+        let before: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM companies")
+            .fetch_one(&mut *tx)
+            .await?;
+        let sightings: Vec<ListingSighting> = records
+            .iter()
+            .map(|record| ListingSighting {
+                exchange: &record.exchange,
+                symbol: &record.symbol,
+                series: &record.series,
+                isin: &record.isin,
+                trade_date: as_of,
+            })
+            .collect();
+        let company_ids = self.upsert_companies(&mut tx, &sightings).await?;
+        let after: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM companies")
+            .fetch_one(&mut *tx)
+            .await?;
+        summary.new_listings = (after - before) as usize;
+
+        for (record, sighting) in records.iter().zip(&sightings) {
+            let company_id = company_ids[&sighting.key()];
+            // A list older than the latest delisting does not undo it
+            summary.relisted += sqlx::query(
+                r#"
+                UPDATE companies SET status = 'active', status_date = ?
+                WHERE id = ? AND status = 'delisted' AND COALESCE(status_date, ?) <= ?
+                "#
+            )
+            .bind(as_of)
+            .bind(company_id)
+            .bind(as_of)
+            .bind(as_of)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+
+            // The latest list wins; an older one only fills in missing fields
+            let latest: bool = sqlx::query_scalar("SELECT ? >= COALESCE(master_list_date, ?) FROM companies WHERE id = ?")
+                .bind(as_of)
+                .bind(as_of)
+                .bind(company_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let merge = |column: &str| {
+                if latest {
+                    format!("COALESCE(?, {})", column)
+                } else {
+                    format!("COALESCE({}, ?)", column)
+                }
+            };
+            sqlx::query(&format!(
+                r#"
+                UPDATE companies SET
+                    name = {},
+                    listing_date = {},
+                    paid_up_value = {},
+                    market_lot = {},
+                    face_value = {},
+                    master_list_date = MAX(COALESCE(master_list_date, ?), ?),
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = ?
+                "#,
+                merge("name"),
+                merge("listing_date"),
+                merge("paid_up_value"),
+                merge("market_lot"),
+                merge("face_value"),
+            ))
+            .bind(&record.name)
+            .bind(record.listing_date)
+            .bind(record.paid_up_value)
+            .bind(record.market_lot)
+            .bind(record.face_value)
+            .bind(as_of)
+            .bind(as_of)
+            .bind(company_id)
+            .execute(&mut *tx)
+            .await?;
+
+            // The same security on other exchanges shares its name and face value
+            if !record.isin.is_empty() {
+                sqlx::query(
+                    r#"
+                    UPDATE companies SET
+                        name = COALESCE(name, ?),
+                        face_value = COALESCE(face_value, ?),
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE isin = ? AND id != ? AND (name IS NULL OR face_value IS NULL)
+                    "#
+                )
+                .bind(&record.name)
+                .bind(record.face_value)
+                .bind(&record.isin)
+                .bind(company_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        let mut exchanges: Vec<&str> = records.iter().map(|record| record.exchange.as_str()).collect();
+        exchanges.sort_unstable();
+        exchanges.dedup();
+        for exchange in exchanges {
+            summary.delisted += sqlx::query(
+                r#"
+                UPDATE companies SET status = 'delisted', status_date = ?, updated_at = CURRENT_TIMESTAMP
+                WHERE exchange = ? AND master_list_date < ? AND status != 'delisted'
+                    AND COALESCE(status_date, ?) <= ?
+                "#
+            )
+            .bind(as_of)
+            .bind(exchange)
+            .bind(as_of)
+            .bind(as_of)
+            .bind(as_of)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+        Ok(summary)
+    }
+
+    /// Sets the sector and industry of every listing of each classified
+    /// security, matched by ISIN or else by its NSE symbol. Returns the
+    /// number of classifications that matched no listing.
+    pub async fn import_classifications(&self, classifications: &[Classification]) -> Result<usize> {
+        let mut unmatched = 0;
+        let mut tx = self.pool.begin().await?;
+
+        for classification in classifications {
+            let mut updated = 0;
+            let update = r#"
+                UPDATE companies SET
+                    sector = COALESCE(?, sector),
+                    industry = COALESCE(?, industry),
+                    name = COALESCE(name, ?),
+                    updated_at = CURRENT_TIMESTAMP
+            "#;
+            if let Some(isin) = &classification.isin {
+                updated = sqlx::query(&format!("{} WHERE isin = ?", update))
+                    .bind(&classification.sector)
+                    .bind(&classification.industry)
+                    .bind(&classification.name)
+                    .bind(isin)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+            }
+            if let (0, Some(symbol)) = (updated, &classification.symbol) {
+                updated = sqlx::query(&format!(
+                    "{} WHERE id IN (SELECT company_id FROM symbol_history WHERE exchange = 'NSE' AND symbol = ?)",
+                    update
+                ))
+                .bind(&classification.sector)
+                .bind(&classification.industry)
+                .bind(&classification.name)
+                .bind(symbol)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            }
+            if updated == 0 {
+                unmatched += 1;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(unmatched)
+    }
+
+    /// Flags listings as suspended or delisted (or active again), matched by
+    /// ISIN or by any symbol they have had. Returns the number of changes
+    /// that matched no listing.
+    pub async fn set_listing_status(&self, changes: &[StatusChange]) -> Result<usize> {
+        let mut unmatched = 0;
+        let mut tx = self.pool.begin().await?;
+
+        for change in changes {
+            let updated = sqlx::query(
+                r#"
+                UPDATE companies SET status = ?, status_date = ?, updated_at = CURRENT_TIMESTAMP
+                WHERE (isin = ? AND isin != '' AND exchange = ?)
+                    OR id IN (SELECT company_id FROM symbol_history WHERE exchange = ? AND symbol = ?)
+                "#
+            )
+            .bind(change.status.as_str())
+            .bind(change.date)
+            .bind(&change.isin)
+            .bind(&change.exchange)
+            .bind(&change.exchange)
+            .bind(&change.symbol)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if updated == 0 {
+                unmatched += 1;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(unmatched)
+    }
+
+    /// Listings matching `filter` with their master data, best match first.
+    pub async fn search_companies(&self, filter: &CompanyFilter) -> Result<Vec<CompanyProfile>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT
+                c.id, c.exchange, c.symbol, c.series, c.isin, c.name, c.listing_date,
+                c.paid_up_value, c.market_lot, c.face_value, c.sector, c.industry,
+                c.status, c.status_date,
+                (SELECT MIN(p.trade_date) FROM daily_prices p WHERE p.company_id = c.id) AS first_trade_date,
+                (SELECT MAX(p.trade_date) FROM daily_prices p WHERE p.company_id = c.id) AS last_trade_date
+            FROM companies c
+            WHERE 1 = 1
+            "#
+        );
+        let text = filter.text.as_deref().map(|text| text.trim().to_ascii_uppercase());
+        if let Some(text) = &text {
+            let pattern = format!("%{}%", text);
+            query
+                .push(" AND (c.name LIKE ")
+                .push_bind(pattern.clone())
+                .push(" OR c.isin = ")
+                .push_bind(text.clone())
+                .push(" OR c.id IN (SELECT company_id FROM symbol_history WHERE symbol LIKE ")
+                .push_bind(pattern)
+                .push("))");
+        }
+        if let Some(symbol) = &filter.symbol {
+            push_symbol_filter(&mut query, symbol);
+        }
+        if let Some(isin) = &filter.isin {
+            query.push(" AND c.isin = ").push_bind(isin.to_ascii_uppercase());
+        }
+        if let Some(exchange) = &filter.exchange {
+            query.push(" AND c.exchange = ").push_bind(exchange.to_ascii_uppercase());
+        }
+        if let Some(sector) = &filter.sector {
+            query.push(" AND c.sector LIKE ").push_bind(format!("%{}%", sector.trim()));
+        }
+        if let Some(industry) = &filter.industry {
+            query.push(" AND c.industry LIKE ").push_bind(format!("%{}%", industry.trim()));
+        }
+        if let Some(status) = filter.status {
+            query.push(" AND c.status = ").push_bind(status.as_str());
+        }
+        query.push(" ORDER BY ");
+        if let Some(text) = text {
+            query.push("c.symbol = ").push_bind(text).push(" DESC, ");
+        }
+        query.push("c.name IS NULL, c.name, c.exchange, c.symbol, c.series");
+        if let Some(limit) = filter.limit {
+            query.push(" LIMIT ").push_bind(limit);
+        }
+
+        let profiles = query.build_query_as::<CompanyProfile>().fetch_all(&self.pool).await?;
+        Ok(profiles)
+    }
+
+    /// Every symbol and ISIN the given listings have traded under, oldest
+    /// first.
+    pub async fn get_symbol_history(&self, company_ids: &[i64]) -> Result<Vec<SymbolHistoryEntry>> {
+        let mut entries = Vec::new();
+        for chunk in company_ids.chunks(COMPANY_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                r#"
+                SELECT company_id, exchange, symbol, series, isin, valid_from, valid_to, source
+                FROM symbol_history
+                WHERE company_id IN (
+                "#
+            );
+            let mut ids = query.separated(", ");
+            for company_id in chunk {
+                ids.push_bind(company_id);
+            }
+            query.push(") ORDER BY company_id, COALESCE(valid_from, ''), id");
+            entries.extend(
+                query
+                    .build_query_as::<SymbolHistoryEntry>()
+                    .fetch_all(&self.pool)
+                    .await?,
+            );
+        }
+        Ok(entries)
+    }
+}
+
+/// A listing as it appears in one trade date's records.
+struct ListingSighting<'a> {
+    exchange: &'a str,
+    symbol: &'a str,
+    series: &'a str,
+    isin: &'a str,
+    trade_date: NaiveDate,
+}
+
+impl ListingSighting<'_> {
+    fn key(&self) -> ListingKey {
+        (self.exchange.to_string(), self.symbol.to_string(), self.series.to_string())
+    }
+}
+
+impl<'a> From<&'a StockRecord> for ListingSighting<'a> {
+    fn from(record: &'a StockRecord) -> Self {
+        ListingSighting {
+            exchange: &record.exchange,
+            symbol: &record.symbol,
+            series: &record.series,
+            isin: &record.isin,
+            trade_date: record.timestamp,
         }
-        
-        info!("Stored {} stock records in database", stored_count);
-        Ok(stored_count)
     }
 }
 
+/// Restricts a query joining `companies c` to listings that have ever
+/// traded under `symbol`.
+fn push_symbol_filter(query: &mut QueryBuilder<'_, Sqlite>, symbol: &str) {
+    query
+        .push(" AND c.id IN (SELECT company_id FROM symbol_history WHERE symbol = ")
+        .push_bind(symbol.to_ascii_uppercase())
+        .push(")");
+}
+
+/// Limits `alias.trade_date` to `from`..=`to` and, unless the filter is
+/// empty, the listing `c` to the filter's series.
+fn push_date_and_series_filter(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    alias: &str,
+    series_filter: &SeriesFilter,
+    from: NaiveDate,
+    to: NaiveDate,
+) {
+    query
+        .push(format!(" AND {}.trade_date BETWEEN ", alias))
+        .push_bind(from)
+        .push(" AND ")
+        .push_bind(to);
+    if !series_filter.is_empty() {
+        query.push(" AND c.series IN (");
+        let mut series = query.separated(", ");
+        for value in series_filter.iter() {
+            series.push_bind(value.to_string());
+        }
+        series.push_unseparated(")");
+    }
+}
+
+fn listing_key(record: &StockRecord) -> ListingKey {
+    (record.exchange.clone(), record.symbol.clone(), record.series.clone())
+}
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Listing {
+    pub id: i64,
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub isin: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ExchangeStatistics {
+    pub exchange: String,
+    pub companies: i64,
+    pub price_records: i64,
+    pub latest_trade_date: Option<chrono::NaiveDate>,
+}
+
 #[derive(Debug)]
 pub struct IngestionLog {
     pub id: i64,
@@ -190,6 +2061,7 @@ pub struct IngestionLog {
     pub file_name: Option<String>,
     pub trade_date: Option<chrono::NaiveDate>,
     pub records_processed: Option<i64>,
+    pub records_rejected: Option<i64>,
     pub status: String,
     pub error_message: Option<String>,
     pub started_at: Option<chrono::NaiveDateTime>,
@@ -202,8 +2074,73 @@ pub struct IngestionLogInsert {
     pub file_name: Option<String>,
     pub trade_date: Option<chrono::NaiveDate>,
     pub records_processed: Option<i64>,
+    pub records_rejected: Option<i64>,
     pub status: String,
     pub error_message: Option<String>,
+    /// [`SeriesFilter::to_log_value`] of the run's series filter
+    pub series_filter: Option<String>,
     pub started_at: Option<chrono::NaiveDateTime>,
     pub completed_at: chrono::NaiveDateTime,
 }
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DataQualityIssue {
+    pub id: i64,
+    pub ingestion_id: i64,
+    pub exchange: String,
+    pub trade_date: NaiveDate,
+    pub symbol: String,
+    pub series: String,
+    pub rule: String,
+    pub severity: String,
+    pub message: String,
+}
+
+/// Which stored quality issues to list; `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct QualityIssueFilter {
+    pub exchange: Option<String>,
+    pub symbol: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub rule: Option<Rule>,
+    pub severity: Option<Severity>,
+    pub limit: Option<i64>,
+}
+
+/// Which corporate actions to list; `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct CorporateActionFilter {
+    pub symbol: Option<String>,
+    pub isin: Option<String>,
+    pub kind: Option<ActionKind>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// Which listings to search; `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct CompanyFilter {
+    /// Part of a name or of any symbol the listing has had, or an exact ISIN
+    pub text: Option<String>,
+    /// Exactly this symbol, current or former
+    pub symbol: Option<String>,
+    pub isin: Option<String>,
+    pub exchange: Option<String>,
+    /// Part of the sector name
+    pub sector: Option<String>,
+    /// Part of the industry name
+    pub industry: Option<String>,
+    pub status: Option<ListingStatus>,
+    pub limit: Option<i64>,
+}
+
+/// Which stored prices to read; `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct PriceFilter {
+    pub exchange: Option<String>,
+    pub symbol: Option<String>,
+    pub series: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
@@ -0,0 +1,136 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use std::io::Cursor;
+use tracing::{info, warn};
+
+use crate::source::{
+    FieldError, FieldErrorKind, RejectedRow, StaleFile, parse_decimal, parse_field, parse_integer,
+};
+
+/// Name delivery files are archived and logged under, next to the `nse`
+/// bhavcopies.
+pub const DELIVERY_SOURCE: &str = "nse-mto";
+
+/// One security's delivery position for a day, from NSE's
+/// `MTO_DDMMYYYY.DAT`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryRecord {
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub trade_date: NaiveDate,
+    /// Quantity traded, as in the bhavcopy
+    pub traded_qty: i64,
+    /// Quantity marked for delivery, gross across clients
+    pub deliverable_qty: i64,
+    /// `deliverable_qty` as a percentage of `traded_qty`
+    pub delivery_pct: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedDelivery {
+    pub records: Vec<DeliveryRecord>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// The name NSE publishes a date's delivery file under, e.g.
+/// `MTO_15012025.DAT`.
+pub fn mto_file_name(date: NaiveDate) -> String {
+    format!("MTO_{}.DAT", date.format("%d%m%Y"))
+}
+
+/// The trade date of a delivery file named like `MTO_15012025.DAT`.
+pub fn identify_mto_file_name(file_name: &str) -> Option<NaiveDate> {
+    let stem = file_name.to_ascii_uppercase();
+    let date = stem.strip_prefix("MTO_")?.strip_suffix(".DAT")?;
+    NaiveDate::parse_from_str(date, "%d%m%Y").ok()
+}
+
+/// Parses NSE's security-wise delivery position file. Rows of record type
+/// `20` are `Record Type, Sr No, Name of Security, Type, Quantity Traded,
+/// Deliverable Quantity, % of Deliverable Quantity to Traded Quantity`;
+/// the record type `10` row carries the trade date as `DDMMYYYY`.
+pub fn parse_mto(content: &str, date: NaiveDate) -> Result<ParsedDelivery> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(Cursor::new(content));
+    let mut parsed = ParsedDelivery::default();
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or(0);
+                let rejected = RejectedRow::new(line, FieldError {
+                    column: String::new(),
+                    raw_value: String::new(),
+                    kind: FieldErrorKind::MalformedRow(e.to_string()),
+                });
+                warn!("Rejected delivery row: {}", rejected);
+                parsed.rejected.push(rejected);
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        match record.get(0) {
+            Some("10") => {
+                let raw = record.get(2).unwrap_or_default();
+                let found = NaiveDate::parse_from_str(raw, "%d%m%Y")
+                    .map_err(|_| anyhow!("Invalid trade date '{}' in delivery file header", raw))?;
+                if found != date {
+                    return Err(StaleFile { requested: date, found }.into());
+                }
+            },
+            Some("20") => match delivery_record(&record, date) {
+                Ok(delivery) => parsed.records.push(delivery),
+                Err(e) => {
+                    let rejected = RejectedRow::new(line, e);
+                    warn!("Rejected delivery row: {}", rejected);
+                    parsed.rejected.push(rejected);
+                },
+            },
+            // Title, settlement and column header lines
+            _ => {},
+        }
+    }
+
+    info!(
+        "Parsed {} delivery records for {} ({} rejected)",
+        parsed.records.len(),
+        date,
+        parsed.rejected.len()
+    );
+    Ok(parsed)
+}
+
+fn delivery_record(record: &csv::StringRecord, date: NaiveDate) -> std::result::Result<DeliveryRecord, FieldError> {
+    const COLUMNS: [&str; 7] = [
+        "Record Type",
+        "Sr No",
+        "Name of Security",
+        "Type",
+        "Quantity Traded",
+        "Deliverable Quantity",
+        "% of Deliverable Quantity to Traded Quantity",
+    ];
+    let field = |index: usize| {
+        record.get(index).ok_or_else(|| FieldError {
+            column: COLUMNS[index].to_string(),
+            raw_value: String::new(),
+            kind: FieldErrorKind::MissingValue,
+        })
+    };
+
+    Ok(DeliveryRecord {
+        exchange: "NSE".to_string(),
+        symbol: parse_field(field(2)?, COLUMNS[2], |raw| Ok(raw.to_ascii_uppercase()))?,
+        series: field(3)?.to_ascii_uppercase(),
+        trade_date: date,
+        traded_qty: parse_field(field(4)?, COLUMNS[4], parse_integer)?,
+        deliverable_qty: parse_field(field(5)?, COLUMNS[5], parse_integer)?,
+        delivery_pct: parse_field(field(6)?, COLUMNS[6], parse_decimal)?,
+    })
+}
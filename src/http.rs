@@ -0,0 +1,267 @@
+use anyhow::{Result, anyhow};
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
+use std::fmt;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+/// Timeouts and retry policy for downloads.
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub connect_timeout: Duration,
+    /// Limit for a whole request, including reading the body
+    pub request_timeout: Duration,
+    /// Retries after the first attempt for transient failures
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further retry
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Upper bound on requests started per second across every concurrent
+    /// download, including retries and the landing page. `None` disables it
+    pub requests_per_second: Option<f64>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(60),
+            max_retries: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            requests_per_second: Some(2.0),
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Exponential backoff with full jitter for the given retry (0-based).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        let millis = exponential.as_millis() as u64;
+        if millis == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+    }
+}
+
+/// Spaces requests evenly so that no more than `requests_per_second` start
+/// in any second.
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the caller may start a request.
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// The file does not exist (HTTP 404): the exchange has not published it
+/// yet or did not trade that day. Not worth retrying.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotPublished {
+    pub url: String,
+}
+
+impl fmt::Display for NotPublished {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "File not published (HTTP 404): {}", self.url)
+    }
+}
+
+impl std::error::Error for NotPublished {}
+
+/// Whether `error` means the requested file does not exist.
+pub fn is_not_published(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<NotPublished>().is_some()
+}
+
+/// A successful download.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+enum Failure {
+    /// Worth another attempt, possibly after fetching a fresh session cookie
+    Transient { reason: String, refresh_session: bool },
+    Permanent(anyhow::Error),
+}
+
+/// HTTP client with retries and, optionally, a session cookie taken from the
+/// site's landing page before the first download.
+pub struct HttpFetcher {
+    client: Client,
+    config: HttpConfig,
+    landing_page: Option<String>,
+    session_primed: Mutex<bool>,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl HttpFetcher {
+    pub fn new(config: HttpConfig, default_headers: HeaderMap, landing_page: Option<String>) -> Self {
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .default_headers(default_headers)
+            .cookie_store(true)
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        let rate_limiter = config
+            .requests_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(RateLimiter::new);
+
+        HttpFetcher {
+            client,
+            config,
+            landing_page,
+            session_primed: Mutex::new(false),
+            rate_limiter,
+        }
+    }
+
+    pub fn config(&self) -> &HttpConfig {
+        &self.config
+    }
+
+    /// Downloads `url`, retrying timeouts, connection errors, 401/403, 429
+    /// and 5xx responses. A 404 fails at once with [`NotPublished`].
+    pub async fn get(&self, url: &str) -> Result<HttpResponse> {
+        let mut retry = 0;
+        loop {
+            let failure = match self.attempt(url).await {
+                Ok(response) => return Ok(response),
+                Err(failure) => failure,
+            };
+
+            match failure {
+                Failure::Permanent(error) => return Err(error),
+                Failure::Transient { reason, refresh_session } => {
+                    if retry >= self.config.max_retries {
+                        return Err(anyhow!(
+                            "Failed to download {} after {} attempts: {}",
+                            url,
+                            retry + 1,
+                            reason
+                        ));
+                    }
+                    if refresh_session {
+                        *self.session_primed.lock().await = false;
+                    }
+
+                    let delay = self.config.backoff(retry);
+                    warn!("Download of {} failed ({}), retrying in {:?}", url, reason, delay);
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                },
+            }
+        }
+    }
+
+    async fn attempt(&self, url: &str) -> std::result::Result<HttpResponse, Failure> {
+        self.prime_session().await;
+
+        self.throttle().await;
+        let response = self.client.get(url).send().await.map_err(transport_failure)?;
+        let status = response.status();
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(Failure::Permanent(NotPublished { url: url.to_string() }.into()));
+        }
+        if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+            return Err(Failure::Transient {
+                reason: format!("HTTP {}", status),
+                refresh_session: self.landing_page.is_some(),
+            });
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(Failure::Transient {
+                reason: format!("HTTP {}", status),
+                refresh_session: false,
+            });
+        }
+        if !status.is_success() {
+            return Err(Failure::Permanent(anyhow!("Failed to download {}: HTTP {}", url, status)));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let body = response.bytes().await.map_err(transport_failure)?;
+
+        Ok(HttpResponse {
+            content_type,
+            body: body.to_vec(),
+        })
+    }
+
+    /// Visits the landing page once so the cookie jar holds a session
+    /// cookie. Failures are only logged: the download itself will tell, and
+    /// a 401/403 from it triggers another visit.
+    async fn prime_session(&self) {
+        let Some(landing_page) = &self.landing_page else {
+            return;
+        };
+        let mut primed = self.session_primed.lock().await;
+        if *primed {
+            return;
+        }
+        *primed = true;
+
+        info!("Fetching session cookie from {}", landing_page);
+        self.throttle().await;
+        match self.client.get(landing_page).send().await {
+            Ok(response) if response.status().is_success() => {},
+            Ok(response) => warn!("Landing page returned HTTP {}", response.status()),
+            Err(e) => warn!("Failed to load landing page: {}", e),
+        }
+    }
+
+    async fn throttle(&self) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+    }
+}
+
+fn transport_failure(error: reqwest::Error) -> Failure {
+    if error.is_timeout() || error.is_connect() || error.is_request() || error.is_body() {
+        Failure::Transient {
+            reason: error.to_string(),
+            refresh_session: false,
+        }
+    } else {
+        Failure::Permanent(error.into())
+    }
+}
@@ -1,5 +1,18 @@
+pub mod adjustment;
+pub mod bse;
+pub mod cache;
+pub mod calendar;
+pub mod companies;
+pub mod corporate_actions;
 pub mod database;
+pub mod delivery;
+pub mod http;
+pub mod local;
 pub mod nse;
+pub mod reconcile;
+pub mod source;
+pub mod symbols;
+pub mod validation;
 
 #[cfg(test)]
 mod tests {
@@ -12,7 +25,7 @@ mod tests {
         let csv_data = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
 RELIANCE,EQ,2500.00,2550.00,2480.00,2520.00,2520.00,2500.00,1000000,2520000000.00,15-JAN-2025,50000,INE002A01018"#;
 
-        let records = nse::parse_csv_data(csv_data, date).unwrap();
+        let records = nse::parse_csv_data(csv_data, date).unwrap().records;
         assert_eq!(records.len(), 1);
         
         let record = &records[0];
@@ -57,13 +70,451 @@ RELIANCE,EQ,2500.00,2550.00,2480.00,2520.00,2520.00,2500.00,1000000,2520000000.0
         assert_eq!(date.year(), 2025);
     }
     
+    #[test]
+    fn test_decode_zipped_bhavcopy() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let zipped = include_bytes!("../tests/fixtures/cm15JAN2025bhav.csv.zip");
+
+        let csv_content = source::decode_bhavcopy_payload(zipped, Some("application/zip")).unwrap();
+        assert!(csv_content.starts_with("SYMBOL,SERIES"));
+
+        let records = nse::parse_csv_data(&csv_content, date).unwrap().records;
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].symbol, "TCS");
+        assert_eq!(records[1].close, 4120.75);
+        assert_eq!(records[2].isin, "INE009A01021");
+    }
+
+    #[test]
+    fn test_decode_zip_detected_by_magic_bytes() {
+        // NSE sometimes serves archives as application/octet-stream
+        let zipped = include_bytes!("../tests/fixtures/cm15JAN2025bhav.csv.zip");
+
+        let from_magic = source::decode_bhavcopy_payload(zipped, Some("application/octet-stream")).unwrap();
+        let without_header = source::decode_bhavcopy_payload(zipped, None).unwrap();
+        assert_eq!(from_magic, without_header);
+        assert_eq!(from_magic, include_str!("../tests/fixtures/cm15JAN2025bhav.csv"));
+    }
+
+    #[test]
+    fn test_decode_plain_csv_bhavcopy() {
+        let plain = include_bytes!("../tests/fixtures/cm15JAN2025bhav.csv");
+
+        let csv_content = source::decode_bhavcopy_payload(plain, Some("text/csv")).unwrap();
+        assert_eq!(csv_content, include_str!("../tests/fixtures/cm15JAN2025bhav.csv"));
+    }
+
+    #[test]
+    fn test_decode_zip_without_csv_member() {
+        let zipped = include_bytes!("../tests/fixtures/no_csv_member.zip");
+
+        let result = source::decode_bhavcopy_payload(zipped, None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_corrupt_zip() {
+        // Advertised as a zip but truncated after the magic bytes
+        let result = source::decode_bhavcopy_payload(b"PK\x03\x04garbage", Some("application/zip"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_udiff_bhavcopy_parsing() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let zipped = include_bytes!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip");
+
+        let csv_content = source::decode_bhavcopy_payload(zipped, Some("application/zip")).unwrap();
+        let records = nse::parse_csv_data(&csv_content, date).unwrap().records;
+        assert_eq!(records.len(), 4);
+
+        let record = &records[0];
+        assert_eq!(record.symbol, "RELIANCE");
+        assert_eq!(record.series, "EQ");
+        assert_eq!(record.open, 2500.00);
+        assert_eq!(record.high, 2550.00);
+        assert_eq!(record.low, 2480.00);
+        assert_eq!(record.close, 2520.00);
+        assert_eq!(record.last, 2520.00);
+        assert_eq!(record.prevclose, 2500.00);
+        assert_eq!(record.tottrdqty, 1000000);
+        assert_eq!(record.tottrdval, 2520000000.00);
+        assert_eq!(record.totaltrades, 50000);
+        assert_eq!(record.isin, "INE002A01018");
+
+        assert_eq!(records[3].symbol, "SAKSOFT");
+        assert_eq!(records[3].series, "BE");
+    }
+
+    #[test]
+    fn test_legacy_and_udiff_map_to_same_records() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let legacy = nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), date)
+            .unwrap()
+            .records;
+        let udiff = nse::parse_csv_data(
+            include_str!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv"),
+            date,
+        )
+        .unwrap()
+        .records;
+
+        for (old, new) in legacy.iter().zip(udiff.iter()) {
+            assert_eq!(old.symbol, new.symbol);
+            assert_eq!(old.series, new.series);
+            assert_eq!(old.close, new.close);
+            assert_eq!(old.prevclose, new.prevclose);
+            assert_eq!(old.tottrdqty, new.tottrdqty);
+            assert_eq!(old.totaltrades, new.totaltrades);
+            assert_eq!(old.isin, new.isin);
+        }
+    }
+
+    #[test]
+    fn test_bhavcopy_format_detection() {
+        let legacy = csv::StringRecord::from(vec!["SYMBOL", "SERIES", "OPEN"]);
+        let udiff = csv::StringRecord::from(vec!["TradDt", "ISIN", "TckrSymb", "SctySrs"]);
+        let unknown = csv::StringRecord::from(vec!["foo", "bar"]);
+
+        assert_eq!(nse::BhavcopyFormat::detect(&legacy), Some(nse::BhavcopyFormat::Legacy));
+        assert_eq!(nse::BhavcopyFormat::detect(&udiff), Some(nse::BhavcopyFormat::Udiff));
+        assert_eq!(nse::BhavcopyFormat::detect(&unknown), None);
+
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        assert!(nse::parse_csv_data("foo,bar\n1,2", date).is_err());
+    }
+
+    #[test]
+    fn test_bhavcopy_url_by_format() {
+        let nse_client = nse::NseClient::new();
+
+        let before_cutover = NaiveDate::from_ymd_opt(2024, 7, 5).unwrap();
+        assert_eq!(nse::BhavcopyFormat::for_date(before_cutover), nse::BhavcopyFormat::Legacy);
+        assert_eq!(
+            nse_client.construct_bhavcopy_url(before_cutover).unwrap(),
+            "https://www.nseindia.com/content/historical/EQUITIES/2024/JUL/cm05JUL2024bhav.csv.zip"
+        );
+
+        let after_cutover = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        assert_eq!(nse::BhavcopyFormat::for_date(after_cutover), nse::BhavcopyFormat::Udiff);
+        assert_eq!(
+            nse_client.construct_bhavcopy_url(after_cutover).unwrap(),
+            "https://nsearchives.nseindia.com/content/cm/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip"
+        );
+    }
+
+    #[test]
+    fn test_source_registry() {
+        assert!(source::available_sources().contains(&"nse"));
+        assert!(source::available_sources().contains(&"bse"));
+
+        let nse_source = source::create_source("nse").unwrap();
+        assert_eq!(nse_source.name(), "nse");
+        assert_eq!(source::create_source("NSE").unwrap().name(), "nse");
+
+        let err = source::create_source("nasdaq").err().unwrap();
+        assert!(err.to_string().contains("Available sources: nse, bse"));
+    }
+
+    #[test]
+    fn test_nse_source_parses_raw_file() {
+        let nse_source = source::create_source("nse").unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let raw = source::RawFile {
+            file_name: "BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip".to_string(),
+            content_type: Some("application/zip".to_string()),
+            body: include_bytes!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip").to_vec(),
+        };
+
+        let parsed = nse_source.parse(&raw, date).unwrap();
+        assert_eq!(parsed.records.len(), 4);
+        assert!(parsed.rejected.is_empty());
+
+        let from = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 14).unwrap();
+        assert_eq!(nse_source.trading_dates_in_range(from, to).len(), 3);
+    }
+
+    #[test]
+    fn test_bse_legacy_bhavcopy_parsing() {
+        let bse_source = source::create_source("bse").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 7, 5).unwrap();
+        let raw = source::RawFile {
+            file_name: "EQ050724_CSV.ZIP".to_string(),
+            content_type: Some("application/x-zip-compressed".to_string()),
+            body: include_bytes!("../tests/fixtures/EQ050724_CSV.ZIP").to_vec(),
+        };
+
+        let parsed = bse_source.parse(&raw, date).unwrap();
+        assert!(parsed.rejected.is_empty());
+        assert_eq!(parsed.records.len(), 3);
+
+        let record = &parsed.records[0];
+        assert_eq!(record.exchange, "BSE");
+        assert_eq!(record.symbol, "500325");
+        assert_eq!(record.series, "A");
+        assert_eq!(record.open, 3130.00);
+        assert_eq!(record.close, 3175.85);
+        assert_eq!(record.prevclose, 3129.70);
+        assert_eq!(record.tottrdqty, 412385);
+        assert_eq!(record.tottrdval, 1304765231.00);
+        assert_eq!(record.totaltrades, 18520);
+        assert_eq!(record.isin, "INE002A01018");
+        assert_eq!(parsed.records[2].symbol, "539594");
+    }
+
+    #[test]
+    fn test_bse_udiff_bhavcopy_parsing() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let csv_content = include_str!("../tests/fixtures/BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV");
+
+        let parsed = bse::parse_bse_csv_data(csv_content, date).unwrap();
+        assert!(parsed.rejected.is_empty());
+        assert_eq!(parsed.records.len(), 2);
+
+        let record = &parsed.records[1];
+        assert_eq!(record.exchange, "BSE");
+        assert_eq!(record.symbol, "539594");
+        assert_eq!(record.series, "X");
+        assert_eq!(record.close, 8.05);
+        assert_eq!(record.tottrdqty, 3125041);
+        assert_eq!(record.isin, "INE094S01041");
+    }
+
+    #[test]
+    fn test_bse_legacy_without_isin_column() {
+        let date = NaiveDate::from_ymd_opt(2016, 3, 1).unwrap();
+        let csv_content = r#"SC_CODE,SC_NAME,SC_GROUP,SC_TYPE,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,NO_TRADES,NO_OF_SHRS,NET_TURNOV,TDCLOIND
+500325,RELIANCE LTD.,A ,Q,980.00,1001.00,975.25,995.30,995.00,979.10,21004,640214,632145874.00,"#;
+
+        let parsed = bse::parse_bse_csv_data(csv_content, date).unwrap();
+        assert!(parsed.rejected.is_empty());
+        assert_eq!(parsed.records[0].symbol, "500325");
+        assert_eq!(parsed.records[0].isin, "");
+    }
+
+    #[test]
+    fn test_bse_bhavcopy_url_by_format() {
+        let bse_client = bse::BseClient::new();
+
+        let legacy = NaiveDate::from_ymd_opt(2024, 7, 5).unwrap();
+        assert_eq!(
+            bse_client.construct_bhavcopy_url(legacy),
+            "https://www.bseindia.com/download/BhavCopy/Equity/EQ050724_CSV.ZIP"
+        );
+
+        let udiff = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        assert_eq!(
+            bse_client.construct_bhavcopy_url(udiff),
+            "https://www.bseindia.com/download/BhavCopy/Equity/BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_records_from_both_exchanges() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let nse_records = nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), date)
+            .unwrap()
+            .records;
+        let bse_records = bse::parse_bse_csv_data(
+            include_str!("../tests/fixtures/BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV"),
+            date,
+        )
+        .unwrap()
+        .records;
+        db.store_stock_records(&nse_records).await.unwrap();
+        db.store_stock_records(&bse_records).await.unwrap();
+
+        let rows = sqlx::query_as::<_, (String, String, f64)>(
+            "SELECT c.exchange, c.symbol, p.close_price FROM daily_prices p
+             JOIN companies c ON c.id = p.company_id
+             WHERE c.isin = 'INE002A01018' AND p.exchange = c.exchange
+             ORDER BY c.exchange",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("BSE".to_string(), "500325".to_string(), 2519.55),
+                ("NSE".to_string(), "RELIANCE".to_string(), 2520.00),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_same_symbol_on_two_exchanges_does_not_collide() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let nse_id = db.upsert_company("NSE", "SBIN", "INE062A01020", "EQ", date).await.unwrap();
+        let bse_id = db.upsert_company("BSE", "SBIN", "INE062A01020", "EQ", date).await.unwrap();
+        assert_ne!(nse_id, bse_id);
+        assert_eq!(db.upsert_company("NSE", "SBIN", "INE062A01020", "EQ", date).await.unwrap(), nse_id);
+
+        // Listings are tied to a known exchange
+        assert!(db.upsert_company("LSE", "SBIN", "INE062A01020", "EQ", date).await.is_err());
+
+        let listings = db.get_listings_by_isin("INE062A01020").await.unwrap();
+        assert_eq!(listings.len(), 2);
+        assert_eq!(listings[0].exchange, "BSE");
+        assert_eq!(listings[1].exchange, "NSE");
+        assert_eq!(db.count_cross_listed_securities().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_statistics() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let nse_records = nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), date)
+            .unwrap()
+            .records;
+        let bse_records = bse::parse_bse_csv_data(
+            include_str!("../tests/fixtures/BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV"),
+            date,
+        )
+        .unwrap()
+        .records;
+        db.store_stock_records(&nse_records).await.unwrap();
+        db.store_stock_records(&bse_records).await.unwrap();
+
+        let stats = db.get_exchange_statistics().await.unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].exchange, "BSE");
+        assert_eq!(stats[0].companies, 2);
+        assert_eq!(stats[0].price_records, 2);
+        assert_eq!(stats[1].exchange, "NSE");
+        assert_eq!(stats[1].companies, 3);
+        assert_eq!(stats[1].price_records, 3);
+        assert_eq!(stats[1].latest_trade_date, Some(date));
+
+        // RELIANCE is the only fixture security on both exchanges
+        assert_eq!(db.count_cross_listed_securities().await.unwrap(), 1);
+    }
+
+    const MULTI_SERIES_CSV: &str = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+SBIN,EQ,760.00,768.00,755.10,765.30,765.00,758.40,9000000,6887700000.00,15-JAN-2025,210000,INE062A01020
+SBIN,BL,762.50,762.50,762.50,762.50,762.50,0.00,500000,381250000.00,15-JAN-2025,2,INE062A01020
+SBIN,N1,1012.00,1012.00,1010.00,1010.00,1010.00,1011.00,120,121200.00,15-JAN-2025,4,INE062A08049
+SAKSOFT,BE,210.10,214.00,205.35,209.80,209.50,211.05,48211,10109032.15,15-JAN-2025,1532,INE0J1Y01017"#;
+
+    #[tokio::test]
+    async fn test_each_series_keeps_its_own_prices() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        assert_eq!(db.store_stock_records(&records).await.unwrap(), 4);
+
+        let rows = sqlx::query_as::<_, (String, f64)>(
+            "SELECT c.series, p.close_price FROM daily_prices p
+             JOIN companies c ON c.id = p.company_id
+             WHERE c.symbol = 'SBIN' AND p.trade_date = ?
+             ORDER BY c.series",
+        )
+        .bind(date)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("BL".to_string(), 762.50),
+                ("EQ".to_string(), 765.30),
+                ("N1".to_string(), 1010.00),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_stock_records_is_all_or_nothing() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let mut records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        // An unknown exchange violates the companies -> exchanges foreign key
+        records[3].exchange = "XYZ".to_string();
+        assert!(db.store_stock_records(&records).await.is_err());
+
+        let companies: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM companies")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let prices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM daily_prices")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!((companies, prices), (0, 0));
+
+        // Nothing from the failed batch is cached, so a retry writes everything
+        let records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        assert_eq!(db.store_stock_records(&records).await.unwrap(), 4);
+        let prices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM daily_prices")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(prices, 4);
+    }
+
+    #[tokio::test]
+    async fn test_store_stock_records_reingest_and_isin_change() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let mut records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        db.store_stock_records(&records).await.unwrap();
+
+        // Re-ingesting the same date replaces prices rather than duplicating them
+        records[0].close = 766.00;
+        records[0].isin = "INE062A01999".to_string();
+        db.store_stock_records(&records).await.unwrap();
+
+        let rows = sqlx::query_as::<_, (String, f64)>(
+            "SELECT c.isin, p.close_price FROM daily_prices p
+             JOIN companies c ON c.id = p.company_id
+             WHERE c.symbol = 'SBIN' AND c.series = 'EQ'",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, vec![("INE062A01999".to_string(), 766.00)]);
+
+        let companies: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM companies")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(companies, 4);
+    }
+
+    #[test]
+    fn test_series_filter() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let mut records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        let filter = source::SeriesFilter::new(["eq", " BE "]);
+        assert_eq!(filter.retain(&mut records), 2);
+        let kept: Vec<_> = records.iter().map(|r| (r.symbol.as_str(), r.series.as_str())).collect();
+        assert_eq!(kept, vec![("SBIN", "EQ"), ("SAKSOFT", "BE")]);
+
+        let mut records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        let everything = source::SeriesFilter::new(Vec::<String>::new());
+        assert!(everything.is_empty());
+        assert_eq!(everything.retain(&mut records), 0);
+        assert_eq!(records.len(), 4);
+    }
+
     #[test]
     fn test_empty_csv_handling() {
         let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
         let empty_csv = "";
         
-        let records = nse::parse_csv_data(empty_csv, date).unwrap();
-        assert_eq!(records.len(), 0);
+        let parsed = nse::parse_csv_data(empty_csv, date).unwrap();
+        assert_eq!(parsed.records.len(), 0);
+        assert_eq!(parsed.rejected.len(), 0);
     }
     
     #[test]
@@ -73,8 +524,2037 @@ RELIANCE,EQ,2500.00,2550.00,2480.00,2520.00,2520.00,2500.00,1000000,2520000000.0
         let bad_csv = r#"SYMBOL,SERIES
 RELIANCE,EQ"#;
         
-        let records = nse::parse_csv_data(bad_csv, date).unwrap();
+        let parsed = nse::parse_csv_data(bad_csv, date).unwrap();
         // Should handle gracefully and return empty results
-        assert_eq!(records.len(), 0);
+        assert_eq!(parsed.records.len(), 0);
+        assert_eq!(parsed.rejected.len(), 1);
+        assert_eq!(parsed.rejected[0].column, "OPEN");
+        assert_eq!(parsed.rejected[0].reason, source::FieldErrorKind::MissingColumn);
+    }
+
+    #[test]
+    fn test_malformed_numbers_are_rejected_not_zeroed() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let csv_data = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+RELIANCE,EQ,2500.00,2550.00,2480.00,2520.00,2520.00,2500.00,1000000,2520000000.00,15-JAN-2025,50000,INE002A01018
+TCS,EQ,4100.00,4150.50,4080.25,N/A,4121.00,4095.30,250000,1030187500.00,15-JAN-2025,42000,INE467B01029
+INFY,EQ,1900.00,1925.00,1890.10,1915.40,1915.00,1898.60,,1149240000.00,15-JAN-2025,61000,INE009A01021
+WIPRO,EQ,300.00,305.00,298.00,301.00,301.00,299.00,12.5,3762500.00,15-JAN-2025,900,INE075A01022"#;
+
+        let parsed = nse::parse_csv_data(csv_data, date).unwrap();
+        assert_eq!(parsed.records.len(), 1);
+        assert_eq!(parsed.records[0].symbol, "RELIANCE");
+        assert_eq!(parsed.rejected.len(), 3);
+
+        let bad_close = &parsed.rejected[0];
+        assert_eq!(bad_close.line, 3);
+        assert_eq!(bad_close.column, "CLOSE");
+        assert_eq!(bad_close.raw_value, "N/A");
+        assert!(matches!(bad_close.reason, source::FieldErrorKind::InvalidNumber(_)));
+
+        let blank_qty = &parsed.rejected[1];
+        assert_eq!(blank_qty.line, 4);
+        assert_eq!(blank_qty.column, "TOTTRDQTY");
+        assert_eq!(blank_qty.reason, source::FieldErrorKind::MissingValue);
+
+        let fractional_qty = &parsed.rejected[2];
+        assert_eq!(fractional_qty.line, 5);
+        assert_eq!(fractional_qty.raw_value, "12.5");
+    }
+
+    #[test]
+    fn test_whole_number_quantities_with_decimals_are_accepted() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let csv_data = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+RELIANCE,EQ,2500.00,2550.00,2480.00,2520.00,2520.00,2500.00,1000000.00,2520000000.00,15-JAN-2025,50000,INE002A01018"#;
+
+        let parsed = nse::parse_csv_data(csv_data, date).unwrap();
+        assert!(parsed.rejected.is_empty());
+        assert_eq!(parsed.records[0].tottrdqty, 1000000);
+    }
+
+    async fn memory_database() -> database::Database {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = database::Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_from_migrations() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = database::Database::from_pool(pool);
+        assert_eq!(db.schema_version().await.unwrap(), None);
+
+        db.initialize().await.unwrap();
+        let latest = database::Database::latest_schema_version();
+        assert!(latest >= 1);
+        assert_eq!(db.schema_version().await.unwrap(), Some(latest));
+
+        // Re-running is a no-op
+        db.initialize().await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), Some(latest));
+
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        for table in ["_sqlx_migrations", "companies", "daily_prices", "exchanges", "ingestion_log"] {
+            assert!(tables.iter().any(|t| t == table), "missing table {}", table);
+        }
+
+        let exchanges: Vec<String> = sqlx::query_scalar("SELECT code FROM exchanges ORDER BY code")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(exchanges, vec!["BSE", "NSE"]);
+    }
+
+    #[tokio::test]
+    async fn test_open_requires_existing_file_and_schema() {
+        let path = std::env::temp_dir().join(format!("market-data-open-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let location = path.to_str().unwrap().to_string();
+
+        // Read-only commands do not create a database at a mistyped path
+        for missing in [location.clone(), format!("sqlite://{}", location)] {
+            let err = database::Database::open(&missing).await.err().unwrap();
+            assert!(err.to_string().contains("init-db"));
+            assert!(!path.exists());
+        }
+
+        std::fs::File::create(&path).unwrap();
+        let db = database::Database::open(&location).await.unwrap();
+        let err = db.ensure_initialized().await.unwrap_err();
+        assert!(err.to_string().contains("has not been initialised"));
+        db.pool.close().await;
+
+        // A sqlite: URL pointing at the same file sees the migrated schema
+        let db = database::Database::new(&format!("sqlite://{}", location)).await.unwrap();
+        db.ensure_initialized().await.unwrap();
+        db.pool.close().await;
+
+        let db = database::Database::open(&location).await.unwrap();
+        db.ensure_initialized().await.unwrap();
+        db.pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_log_records_rejected_count() {
+        let db = memory_database().await;
+        let now = chrono::Local::now().naive_local();
+
+        db.log_ingestion(&database::IngestionLogInsert {
+            source: "nse".to_string(),
+            file_name: None,
+            trade_date: NaiveDate::from_ymd_opt(2025, 1, 15),
+            records_processed: Some(1890),
+            records_rejected: Some(2),
+            status: "partial".to_string(),
+            error_message: None,
+            series_filter: None,
+            started_at: Some(now),
+            completed_at: now,
+        })
+        .await
+        .unwrap();
+
+        let logs = db.get_ingestion_logs(Some(10)).await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].status, "partial");
+        assert_eq!(logs[0].records_processed, Some(1890));
+        assert_eq!(logs[0].records_rejected, Some(2));
+    }
+
+    #[test]
+    fn test_bundled_calendar_skips_holidays() {
+        let nse_client = nse::NseClient::new();
+
+        // Republic Day 2024 fell on a Friday
+        let from = NaiveDate::from_ymd_opt(2024, 1, 24).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 1, 29).unwrap();
+        let dates = nse_client.get_trading_dates_in_range(from, to);
+
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 25).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 29).unwrap(),
+            ]
+        );
+
+        let calendar = calendar::TradingCalendar::bundled("NSE");
+        let republic_day = NaiveDate::from_ymd_opt(2024, 1, 26).unwrap();
+        assert_eq!(calendar.holiday(republic_day), Some("Republic Day"));
+        assert_eq!(
+            calendar.previous_trading_day(NaiveDate::from_ymd_opt(2024, 1, 29).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 1, 25).unwrap()
+        );
+        assert_eq!(calendar.next_trading_day(republic_day), NaiveDate::from_ymd_opt(2024, 1, 29).unwrap());
+    }
+
+    #[test]
+    fn test_special_session_on_weekend_is_a_trading_day() {
+        let calendar = calendar::TradingCalendar::bundled("NSE");
+
+        // Muhurat trading on Sunday 12 November 2023
+        let muhurat = NaiveDate::from_ymd_opt(2023, 11, 12).unwrap();
+        assert!(calendar.is_trading_day(muhurat));
+        assert!(calendar.special_session(muhurat).is_some());
+
+        let dates = calendar.trading_dates_in_range(
+            NaiveDate::from_ymd_opt(2023, 11, 10).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 11, 14).unwrap(),
+        );
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd_opt(2023, 11, 10).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 11, 12).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 11, 13).unwrap(),
+            ]
+        );
+        assert_eq!(calendar.trading_day_on_or_before(NaiveDate::from_ymd_opt(2023, 11, 14).unwrap()),
+            NaiveDate::from_ymd_opt(2023, 11, 13).unwrap());
+
+        // Without the bundled list only weekends are closed
+        let weekends_only = calendar::TradingCalendar::weekends_only();
+        assert!(!weekends_only.is_trading_day(muhurat));
+        assert!(weekends_only.is_trading_day(NaiveDate::from_ymd_opt(2023, 11, 14).unwrap()));
+        assert_eq!(weekends_only.uncovered_years(muhurat, muhurat), vec![2023]);
+
+        // The bundled list runs from 2015 to 2026; imported days cover their year
+        let mut calendar = calendar;
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert!(calendar.is_trading_day(day(2015, 2, 28)));
+        assert_eq!(calendar.holiday(day(2026, 1, 26)), Some("Republic Day"));
+        assert_eq!(calendar.uncovered_years(day(2014, 6, 1), day(2027, 6, 1)), vec![2014, 2027]);
+        calendar.extend(&calendar::parse_calendar_csv("date,kind,description\n2027-01-26,holiday,Republic Day", "NSE").unwrap());
+        assert_eq!(calendar.uncovered_years(day(2015, 1, 1), day(2027, 12, 31)), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_parse_calendar_csv_and_json() {
+        let csv = "date,kind,description\n2026-01-26,,Republic Day\n08-Nov-2026,special_session,Muhurat Trading\n";
+        let days = calendar::parse_calendar_csv(csv, "nse").unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].exchange, "NSE");
+        assert_eq!(days[0].kind, calendar::SessionKind::Holiday);
+        assert_eq!(days[1].date, NaiveDate::from_ymd_opt(2026, 11, 8).unwrap());
+        assert_eq!(days[1].kind, calendar::SessionKind::SpecialSession);
+
+        let json = r#"[
+            {"date": "2026-03-03", "description": "Holi"},
+            {"date": "2026-03-03", "description": "Holi", "exchange": "BSE"}
+        ]"#;
+        let days = calendar::parse_calendar_json(json, "NSE").unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].exchange, "NSE");
+        assert_eq!(days[1].exchange, "BSE");
+        assert_eq!(days[1].kind, calendar::SessionKind::Holiday);
+
+        let err = calendar::parse_calendar_csv("date,kind\n2026-13-01,holiday\n", "NSE").unwrap_err();
+        assert!(err.to_string().contains("row 2"));
+        assert!(calendar::parse_calendar_csv("date,kind\n2026-01-01,closing\n", "NSE").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_imported_holidays_override_bundled_calendar() {
+        let db = memory_database().await;
+
+        let unscheduled = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let republic_day_2024 = NaiveDate::from_ymd_opt(2024, 1, 26).unwrap();
+        let imported = db
+            .upsert_market_days(&[
+                calendar::MarketDay {
+                    exchange: "NSE".to_string(),
+                    date: unscheduled,
+                    kind: calendar::SessionKind::Holiday,
+                    description: "Election".to_string(),
+                },
+                calendar::MarketDay {
+                    exchange: "NSE".to_string(),
+                    date: republic_day_2024,
+                    kind: calendar::SessionKind::SpecialSession,
+                    description: "Test session".to_string(),
+                },
+            ])
+            .await
+            .unwrap();
+        assert_eq!(imported, 2);
+
+        // Re-importing the same date updates it rather than duplicating it
+        db.upsert_market_days(&[calendar::MarketDay {
+            exchange: "NSE".to_string(),
+            date: unscheduled,
+            kind: calendar::SessionKind::Holiday,
+            description: "General Election".to_string(),
+        }])
+        .await
+        .unwrap();
+        assert_eq!(db.get_market_days("NSE").await.unwrap().len(), 2);
+        assert!(db.get_market_days("BSE").await.unwrap().is_empty());
+
+        let nse = db.trading_calendar("NSE").await.unwrap();
+        assert_eq!(nse.holiday(unscheduled), Some("General Election"));
+        assert!(!nse.is_trading_day(unscheduled));
+        assert!(nse.is_trading_day(republic_day_2024));
+
+        let bse = db.trading_calendar("BSE").await.unwrap();
+        assert!(bse.is_trading_day(unscheduled));
+        assert!(!bse.is_trading_day(republic_day_2024));
+
+        let mut source = source::create_source("nse").unwrap();
+        source.set_calendar(nse);
+        assert!(source
+            .trading_dates_in_range(unscheduled, unscheduled)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_latest_published_date_respects_cutoff() {
+        let calendar = calendar::TradingCalendar::bundled("NSE");
+        let cutoff = calendar::default_publication_cutoff();
+        let at = |date: (i32, u32, u32), time: (u32, u32)| {
+            NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+                .unwrap()
+                .and_hms_opt(time.0, time.1, 0)
+                .unwrap()
+        };
+
+        // Wednesday morning: the day's file is not out, use Tuesday
+        assert_eq!(
+            calendar.latest_published_date(at((2025, 1, 15), (9, 0)), cutoff),
+            NaiveDate::from_ymd_opt(2025, 1, 14).unwrap()
+        );
+        // After the cutoff the current day counts
+        assert_eq!(
+            calendar.latest_published_date(at((2025, 1, 15), (18, 30)), cutoff),
+            NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()
+        );
+        // Monday morning falls back over the weekend to Friday
+        assert_eq!(
+            calendar.latest_published_date(at((2025, 1, 13), (10, 0)), cutoff),
+            NaiveDate::from_ymd_opt(2025, 1, 10).unwrap()
+        );
+        // On a holiday or weekend the previous trading day is already out
+        assert_eq!(
+            calendar.latest_published_date(at((2025, 1, 26), (8, 0)), cutoff),
+            NaiveDate::from_ymd_opt(2025, 1, 24).unwrap()
+        );
+        // Monday after Republic Day 2024 (Friday) falls back to Thursday
+        assert_eq!(
+            calendar.latest_published_date(at((2024, 1, 29), (12, 0)), cutoff),
+            NaiveDate::from_ymd_opt(2024, 1, 25).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_exchange_now_is_ist() {
+        use chrono::{Offset, Utc};
+
+        assert_eq!(calendar::exchange_timezone().fix().local_minus_utc(), 19800);
+        let drift = calendar::exchange_now() - Utc::now().naive_utc();
+        assert!((drift.num_minutes() - 330).abs() <= 1);
+    }
+
+    fn fast_http_config() -> http::HttpConfig {
+        http::HttpConfig {
+            connect_timeout: std::time::Duration::from_millis(500),
+            request_timeout: std::time::Duration::from_millis(500),
+            max_retries: 3,
+            initial_backoff: std::time::Duration::from_millis(1),
+            max_backoff: std::time::Duration::from_millis(5),
+            requests_per_second: None,
+        }
+    }
+
+    const UDIFF_15JAN2025_PATH: &str = "/content/cm/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip";
+
+    async fn mock_nse_server() -> wiremock::MockServer {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(200).insert_header("set-cookie", "nsit=session1; Path=/"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn requests_to(server: &wiremock::MockServer, request_path: &str) -> usize {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path() == request_path)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_server_errors_then_succeeds() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .and(header("cookie", "nsit=session1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "application/zip")
+                    .set_body_bytes(include_bytes!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip").to_vec()),
+            )
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let content = client.download_bhavcopy(date).await.unwrap();
+
+        assert!(content.starts_with("TradDt,"));
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 3);
+        // The session cookie is fetched once and reused across retries
+        assert_eq!(requests_to(&server, "/").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_404_is_not_published_and_not_retried() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let err = client
+            .fetch_bhavcopy(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+            .await
+            .unwrap_err();
+
+        assert!(http::is_not_published(&err));
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refreshes_session_cookie_after_403() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(403))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"SYMBOL,SERIES\n".to_vec()))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        client
+            .fetch_bhavcopy(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 2);
+        assert_eq!(requests_to(&server, "/").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_landing_page_is_only_retried_after_401() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        let path_for = |d| format!("/content/cm/BhavCopy_NSE_CM_0_0_0_202501{:02}_F_0000.csv.zip", d);
+        Mock::given(method("GET"))
+            .and(path(path_for(16)))
+            .respond_with(ResponseTemplate::new(401))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        for d in [14, 15, 16, 17] {
+            Mock::given(method("GET"))
+                .and(path(path_for(d)))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(b"SYMBOL,SERIES\n".to_vec()))
+                .mount(&server)
+                .await;
+        }
+
+        // A landing page that fails is not visited again before every download
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        client.fetch_bhavcopy(day(14)).await.unwrap();
+        client.fetch_bhavcopy(day(15)).await.unwrap();
+        assert_eq!(requests_to(&server, "/").await, 1);
+
+        // Only a 401/403 sends the client back to it
+        client.fetch_bhavcopy(day(16)).await.unwrap();
+        assert_eq!(requests_to(&server, &path_for(16)).await, 2);
+        assert_eq!(requests_to(&server, "/").await, 2);
+        client.fetch_bhavcopy(day(17)).await.unwrap();
+        assert_eq!(requests_to(&server, "/").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gives_up_after_max_retries() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let err = client
+            .fetch_bhavcopy(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+            .await
+            .unwrap_err();
+
+        assert!(!http::is_not_published(&err));
+        assert!(err.to_string().contains("after 4 attempts"), "{}", err);
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_timeouts() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(2)))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"SYMBOL,SERIES\n".to_vec()))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        client
+            .fetch_bhavcopy(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_does_not_retry_other_client_errors() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let err = client
+            .fetch_bhavcopy(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("HTTP 400"));
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 1);
+    }
+
+    #[test]
+    fn test_backoff_grows_exponentially_with_jitter() {
+        let config = http::HttpConfig {
+            initial_backoff: std::time::Duration::from_millis(100),
+            max_backoff: std::time::Duration::from_millis(1000),
+            ..http::HttpConfig::default()
+        };
+
+        for _ in 0..20 {
+            let first = config.backoff(0).as_millis();
+            assert!((50..=100).contains(&first), "{}", first);
+            let third = config.backoff(2).as_millis();
+            assert!((200..=400).contains(&third), "{}", third);
+            let capped = config.backoff(10).as_millis();
+            assert!((500..=1000).contains(&capped), "{}", capped);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_concurrent_requests() {
+        let limiter = http::RateLimiter::new(50.0);
+        let started = std::time::Instant::now();
+
+        futures::future::join_all((0..6).map(|_| limiter.acquire())).await;
+
+        // The first request goes at once, the other five 20ms apart
+        let elapsed = started.elapsed();
+        assert!(elapsed >= std::time::Duration::from_millis(95), "{:?}", elapsed);
+        assert!(elapsed < std::time::Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_fetches_share_the_rate_limit() {
+        use wiremock::matchers::{method, path_regex};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path_regex("^/content/cm/"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"SYMBOL,SERIES\n".to_vec()))
+            .mount(&server)
+            .await;
+
+        let config = http::HttpConfig {
+            requests_per_second: Some(20.0),
+            ..fast_http_config()
+        };
+        let client = nse::NseClient::with_base_url(config, &server.uri());
+        let dates = client.get_trading_dates_in_range(
+            NaiveDate::from_ymd_opt(2025, 1, 13).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 16).unwrap(),
+        );
+
+        let started = std::time::Instant::now();
+        let results = futures::future::join_all(dates.iter().map(|date| client.fetch_bhavcopy(*date))).await;
+
+        assert!(results.iter().all(|result| result.is_ok()));
+        // Landing page plus four downloads, 50ms apart
+        assert!(started.elapsed() >= std::time::Duration::from_millis(190), "{:?}", started.elapsed());
+        assert_eq!(server.received_requests().await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_ingested_dates_from_prices_and_successful_runs() {
+        let db = memory_database().await;
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+
+        // Prices stored for the 15th
+        let records = nse::parse_csv_data(MULTI_SERIES_CSV, day(15)).unwrap().records;
+        db.store_stock_records(&records).await.unwrap();
+
+        // A partial run for the 14th, a failed one for the 16th and a BSE run for the 17th
+        let now = chrono::Local::now().naive_local();
+        for (source, date, status) in [("nse", day(14), "partial"), ("nse", day(16), "failed"), ("bse", day(17), "success")] {
+            db.log_ingestion(&database::IngestionLogInsert {
+                source: source.to_string(),
+                file_name: None,
+                trade_date: Some(date),
+                records_processed: Some(0),
+                records_rejected: Some(0),
+                status: status.to_string(),
+                error_message: None,
+                series_filter: None,
+                started_at: Some(now),
+                completed_at: now,
+            })
+            .await
+            .unwrap();
+        }
+
+        let all_series = source::SeriesFilter::default();
+        let ingested = db.get_ingested_dates("NSE", "nse", &all_series, day(1), day(31)).await.unwrap();
+        let mut ingested: Vec<_> = ingested.into_iter().collect();
+        ingested.sort();
+        assert_eq!(ingested, vec![day(14), day(15)]);
+
+        let ingested = db.get_ingested_dates("NSE", "nse", &all_series, day(15), day(31)).await.unwrap();
+        assert_eq!(ingested.len(), 1);
+
+        let ingested = db.get_ingested_dates("BSE", "bse", &all_series, day(1), day(31)).await.unwrap();
+        assert!(ingested.contains(&day(17)));
+        assert!(!ingested.contains(&day(15)));
+
+        // A `--series EQ` run on the 20th only covers EQ; unlogged prices
+        // count for the series they are in
+        db.store_stock_records(&nse_records(day(20), &[("SBIN", "INE062A01020", 766.0)])).await.unwrap();
+        let eq = source::SeriesFilter::new(["eq"]);
+        assert_eq!(eq.to_log_value().as_deref(), Some("EQ"));
+        db.log_ingestion(&database::IngestionLogInsert {
+            source: "nse".to_string(),
+            file_name: None,
+            trade_date: Some(day(20)),
+            records_processed: Some(1),
+            records_rejected: Some(0),
+            status: "success".to_string(),
+            error_message: None,
+            series_filter: eq.to_log_value(),
+            started_at: Some(now),
+            completed_at: now,
+        })
+        .await
+        .unwrap();
+        let ingested = |filter: source::SeriesFilter| {
+            let db = &db;
+            async move {
+                let mut dates: Vec<_> = db.get_ingested_dates("NSE", "nse", &filter, day(15), day(31)).await.unwrap().into_iter().collect();
+                dates.sort();
+                dates
+            }
+        };
+        assert_eq!(ingested(all_series.clone()).await, vec![day(15)]);
+        assert_eq!(ingested(eq).await, vec![day(15), day(20)]);
+        assert_eq!(ingested(source::SeriesFilter::new(["EQ", "BE"])).await, vec![day(15)]);
+        assert_eq!(ingested(source::SeriesFilter::new(["SM"])).await, Vec::<NaiveDate>::new());
+    }
+
+    fn temp_cache(name: &str) -> cache::RawFileCache {
+        let root = std::env::temp_dir().join(format!("market-data-cache-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&root);
+        cache::RawFileCache::new(root)
+    }
+
+    #[test]
+    fn test_raw_file_cache_round_trip_and_checksum() {
+        let cache = temp_cache("round-trip");
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let raw = source::RawFile {
+            file_name: "https://example.test/cm15JAN2025bhav.csv.zip".to_string(),
+            content_type: Some("application/zip".to_string()),
+            body: include_bytes!("../tests/fixtures/cm15JAN2025bhav.csv.zip").to_vec(),
+        };
+
+        assert!(cache.load("nse", date).unwrap().is_none());
+        let entry = cache.store("nse", date, &raw).unwrap();
+        assert_eq!(entry.sha256, cache::sha256_hex(&raw.body));
+        assert_eq!(entry.sha256.len(), 64);
+        assert_eq!(
+            entry.log_file_name(),
+            format!("https://example.test/cm15JAN2025bhav.csv.zip sha256:{}", entry.sha256)
+        );
+
+        let (loaded, loaded_entry) = cache.load("nse", date).unwrap().unwrap();
+        assert_eq!(loaded.body, raw.body);
+        assert_eq!(loaded.file_name, raw.file_name);
+        assert_eq!(loaded.content_type, raw.content_type);
+        assert_eq!(loaded_entry, entry);
+
+        // The same body for another date is stored once
+        let next_day = NaiveDate::from_ymd_opt(2025, 1, 16).unwrap();
+        cache.store("nse", next_day, &raw).unwrap();
+        let objects = std::fs::read_dir(cache.root().join("objects").join(&entry.sha256[..2])).unwrap().count();
+        assert_eq!(objects, 1);
+        assert_eq!(
+            cache.cached_dates("nse", NaiveDate::MIN, NaiveDate::MAX).unwrap(),
+            vec![date, next_day]
+        );
+        assert_eq!(cache.cached_dates("nse", next_day, next_day).unwrap(), vec![next_day]);
+        assert!(cache.cached_dates("bse", NaiveDate::MIN, NaiveDate::MAX).unwrap().is_empty());
+
+        // A corrupted object is reported, not parsed
+        let object = cache.root().join("objects").join(&entry.sha256[..2]).join(&entry.sha256);
+        std::fs::write(&object, b"tampered").unwrap();
+        let err = cache.load("nse", date).unwrap_err();
+        assert!(err.to_string().contains("checksum"));
+
+        let _ = std::fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_cache_modes() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(UDIFF_15JAN2025_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(
+                include_bytes!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip").to_vec(),
+            ))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let cache = temp_cache("modes");
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let err = cache::fetch_with_cache(&client, &cache, date, cache::CacheMode::Offline)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("offline mode"));
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 0);
+
+        let (raw, entry) = cache::fetch_with_cache(&client, &cache, date, cache::CacheMode::Prefer)
+            .await
+            .unwrap();
+        assert!(entry.url.ends_with(UDIFF_15JAN2025_PATH));
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 1);
+
+        // Cached now: neither mode goes back to the network
+        for mode in [cache::CacheMode::Prefer, cache::CacheMode::Offline] {
+            let (cached, _) = cache::fetch_with_cache(&client, &cache, date, mode).await.unwrap();
+            assert_eq!(cached.body, raw.body);
+        }
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 1);
+
+        cache::fetch_with_cache(&client, &cache, date, cache::CacheMode::Refresh)
+            .await
+            .unwrap();
+        assert_eq!(requests_to(&server, UDIFF_15JAN2025_PATH).await, 2);
+
+        let _ = std::fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_stale_files_are_evicted_from_the_cache() {
+        use source::MarketDataSource;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        // The exchange serves 15 Jan's files when asked for 16 Jan's
+        let bhavcopy_path = "/content/cm/BhavCopy_NSE_CM_0_0_0_20250116_F_0000.csv.zip";
+        let mto_path = "/archives/equities/mto/MTO_16012025.DAT";
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(bhavcopy_path))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(
+                include_bytes!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip").to_vec(),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(mto_path))
+            .respond_with(ResponseTemplate::new(200).set_body_string(include_str!("../tests/fixtures/MTO_15012025.DAT")))
+            .mount(&server)
+            .await;
+
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let cache = temp_cache("stale");
+        let date = NaiveDate::from_ymd_opt(2025, 1, 16).unwrap();
+
+        for attempt in 1..=2 {
+            let (raw, _) = cache::fetch_with_cache(&client, &cache, date, cache::CacheMode::Prefer)
+                .await
+                .unwrap();
+            let parsed = client.parse(&raw, date);
+            assert!(parsed.as_ref().unwrap_err().downcast_ref::<source::StaleFile>().is_some());
+            cache.evict_if_stale(client.name(), date, &parsed).unwrap();
+            assert!(cache.load(client.name(), date).unwrap().is_none());
+            assert_eq!(requests_to(&server, bhavcopy_path).await, attempt);
+
+            let (raw, _) = cache::fetch_delivery_with_cache(&client, &cache, date, cache::CacheMode::Prefer)
+                .await
+                .unwrap();
+            let parsed = delivery::parse_mto(std::str::from_utf8(&raw.body).unwrap(), date);
+            assert!(parsed.as_ref().unwrap_err().downcast_ref::<source::StaleFile>().is_some());
+            cache.evict_if_stale(delivery::DELIVERY_SOURCE, date, &parsed).unwrap();
+            assert!(cache.load(delivery::DELIVERY_SOURCE, date).unwrap().is_none());
+            assert_eq!(requests_to(&server, mto_path).await, attempt);
+        }
+
+        // Files that parse, or fail for other reasons, stay cached
+        let (raw, _) = cache::fetch_with_cache(&client, &cache, date, cache::CacheMode::Prefer)
+            .await
+            .unwrap();
+        cache.evict_if_stale(client.name(), date, &Err::<(), _>(anyhow::anyhow!("bad row"))).unwrap();
+        cache.evict_if_stale(client.name(), date, &client.parse(&raw, date.pred_opt().unwrap())).unwrap();
+        assert!(cache.load(client.name(), date).unwrap().is_some());
+
+        let _ = std::fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_replace_stock_records_drops_stale_prices() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        db.store_stock_records(&records).await.unwrap();
+        let bse = bse::parse_bse_csv_data(
+            include_str!("../tests/fixtures/BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV"),
+            date,
+        )
+        .unwrap()
+        .records;
+        db.store_stock_records(&bse).await.unwrap();
+
+        let eq_only: Vec<_> = records.iter().filter(|r| r.series == "EQ").cloned().collect();
+        let stored = db.replace_stock_records("NSE", date, &source::SeriesFilter::default(), &eq_only).await.unwrap();
+        assert_eq!(stored, eq_only.len());
+
+        let nse_prices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM daily_prices WHERE exchange = 'NSE'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(nse_prices, eq_only.len() as i64);
+
+        // Other exchanges' prices for the date are untouched
+        let bse_prices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM daily_prices WHERE exchange = 'BSE'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(bse_prices, bse.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_reparse_with_a_series_filter_keeps_other_series() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let mut records = nse::parse_csv_data(MULTI_SERIES_CSV, date).unwrap().records;
+        db.store_stock_records(&records).await.unwrap();
+
+        // What `reparse --series EQ,N1` stores after filtering the reparsed
+        // file, where the corrected parser no longer produces the N1 row
+        let series_filter = source::SeriesFilter::new(["EQ", "N1"]);
+        records.retain(|record| record.series == "EQ");
+        records[0].close = 766.0;
+        db.replace_stock_records("NSE", date, &series_filter, &records).await.unwrap();
+
+        let stored: Vec<(String, f64)> = sqlx::query_as(
+            "SELECT c.series, p.close_price FROM daily_prices p JOIN companies c ON c.id = p.company_id ORDER BY c.series"
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            stored,
+            vec![("BE".to_string(), 209.8), ("BL".to_string(), 762.5), ("EQ".to_string(), 766.0)]
+        );
+    }
+
+    #[test]
+    fn test_identify_bhavcopy_from_file_name() {
+        let identify = |name| local::identify_from_file_name(name).map(|f| (f.source, f.trade_date));
+        let jan15 = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        assert_eq!(identify("cm15JAN2025bhav.csv.zip"), Some(("nse", jan15)));
+        assert_eq!(identify("cm15jan2025bhav.csv"), Some(("nse", jan15)));
+        assert_eq!(identify("BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip"), Some(("nse", jan15)));
+        assert_eq!(identify("BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV"), Some(("bse", jan15)));
+        assert_eq!(identify("EQ150125_CSV.ZIP"), Some(("bse", jan15)));
+        assert_eq!(identify("EQ150125.CSV"), Some(("bse", jan15)));
+        assert_eq!(identify("export.csv"), None);
+        assert_eq!(identify("cm32JAN2025bhav.csv"), None);
+    }
+
+    #[test]
+    fn test_identify_bhavcopy_from_contents() {
+        let identify = |path: &str, body: &[u8]| {
+            let found = local::identify_bhavcopy(std::path::Path::new(path), body).unwrap();
+            (found.source, found.trade_date)
+        };
+
+        assert_eq!(
+            identify("a.csv", include_bytes!("../tests/fixtures/cm15JAN2025bhav.csv")),
+            ("nse", NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+        );
+        assert_eq!(
+            identify("b.zip", include_bytes!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv.zip")),
+            ("nse", NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+        );
+        assert_eq!(
+            identify("c.csv", include_bytes!("../tests/fixtures/BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV")),
+            ("bse", NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+        );
+        assert_eq!(
+            identify("d.zip", include_bytes!("../tests/fixtures/EQ050724_CSV.ZIP")),
+            ("bse", NaiveDate::from_ymd_opt(2024, 7, 5).unwrap())
+        );
+
+        let err = local::identify_bhavcopy(std::path::Path::new("e.csv"), b"SYMBOL,SERIES\nSBIN,EQ\n").unwrap_err();
+        assert!(format!("{:#}", err).contains("No TradDt, TIMESTAMP or TRADING_DATE column"));
+    }
+
+    #[test]
+    fn test_find_bhavcopy_files_with_globs_and_recursion() {
+        let root = std::env::temp_dir().join(format!("market-data-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("2025/01")).unwrap();
+        for file in ["cm14JAN2025bhav.csv.zip", "2025/cm15JAN2025bhav.csv", "2025/01/EQ150125_CSV.ZIP", "2025/notes.txt"] {
+            std::fs::write(root.join(file), b"").unwrap();
+        }
+
+        let dirs = vec![root.clone()];
+        let top_level = local::find_bhavcopy_files(&[], &dirs, false).unwrap();
+        assert_eq!(top_level, vec![root.join("cm14JAN2025bhav.csv.zip")]);
+
+        let recursive = local::find_bhavcopy_files(&[], &dirs, true).unwrap();
+        assert_eq!(
+            recursive,
+            vec![
+                root.join("2025/01/EQ150125_CSV.ZIP"),
+                root.join("2025/cm15JAN2025bhav.csv"),
+                root.join("cm14JAN2025bhav.csv.zip"),
+            ]
+        );
+
+        let pattern = format!("{}/**/cm*bhav.csv*", root.display());
+        let globbed = local::find_bhavcopy_files(&[pattern], &dirs, false).unwrap();
+        assert_eq!(
+            globbed,
+            vec![root.join("2025/cm15JAN2025bhav.csv"), root.join("cm14JAN2025bhav.csv.zip")]
+        );
+
+        assert!(local::find_bhavcopy_files(&[format!("{}/*.json", root.display())], &[], false).is_err());
+        assert!(local::find_bhavcopy_files(&[root.join("missing.csv").display().to_string()], &[], false).is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_trade_date_is_read_from_the_file() {
+        let jan15 = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let legacy = nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), jan15).unwrap();
+        assert!(legacy.records.iter().all(|r| r.timestamp == jan15));
+
+        let udiff = nse::parse_csv_data(
+            include_str!("../tests/fixtures/BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv"),
+            jan15,
+        )
+        .unwrap();
+        assert!(udiff.records.iter().all(|r| r.timestamp == jan15));
+
+        let bse_date = NaiveDate::from_ymd_opt(2024, 7, 5).unwrap();
+        let bse_legacy = source::create_source("bse")
+            .unwrap()
+            .parse(
+                &source::RawFile {
+                    file_name: "EQ050724_CSV.ZIP".to_string(),
+                    content_type: None,
+                    body: include_bytes!("../tests/fixtures/EQ050724_CSV.ZIP").to_vec(),
+                },
+                bse_date,
+            )
+            .unwrap();
+        assert!(bse_legacy.records.iter().all(|r| r.timestamp == bse_date));
+
+        // Files without a date column keep the requested date
+        let undated = nse::parse_csv_data(
+            "SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TOTALTRADES\nSBIN,EQ,1,1,1,1,1,1,1,1,1",
+            jan15,
+        )
+        .unwrap();
+        assert_eq!(undated.records[0].timestamp, jan15);
+    }
+
+    #[test]
+    fn test_file_for_another_day_is_stale() {
+        // NSE served the 15th's file when the 16th was requested
+        let requested = NaiveDate::from_ymd_opt(2025, 1, 16).unwrap();
+        let err = nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), requested).unwrap_err();
+
+        let stale = err.downcast_ref::<source::StaleFile>().expect("stale file error");
+        assert_eq!(stale.requested, requested);
+        assert_eq!(stale.found, NaiveDate::from_ymd_opt(2025, 1, 15).unwrap());
+        assert_eq!(
+            err.to_string(),
+            "Stale file: requested 2025-01-16 but the file is for 2025-01-15"
+        );
+    }
+
+    #[test]
+    fn test_unparseable_timestamp_is_rejected() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let csv_data = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+SBIN,EQ,760.00,768.00,755.10,765.30,765.00,758.40,9000000,6887700000.00,15-JAN-2025,210000,INE062A01020
+TCS,EQ,4100.00,4150.00,4080.00,4120.00,4121.00,4095.00,1200000,4944000000.00,2025/01/15,80000,INE467B01029
+INFY,EQ,1900.00,1920.00,1890.00,1910.00,1911.00,1895.00,2500000,4775000000.00,,90000,INE009A01021"#;
+
+        let parsed = nse::parse_csv_data(csv_data, date).unwrap();
+        assert_eq!(parsed.records.len(), 1);
+        assert_eq!(parsed.rejected.len(), 2);
+        assert_eq!(parsed.rejected[0].column, "TIMESTAMP");
+        assert_eq!(parsed.rejected[0].raw_value, "2025/01/15");
+        assert!(matches!(parsed.rejected[0].reason, source::FieldErrorKind::InvalidDate(_)));
+        assert_eq!(parsed.rejected[1].reason, source::FieldErrorKind::MissingValue);
+    }
+
+    const SUSPECT_CSV: &str = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+RELIANCE,EQ,2500.00,2550.00,2480.00,2520.00,2520.00,2500.00,1000000,2520000000.00,15-JAN-2025,50000,INE002A01018
+TCS,EQ,0.00,4150.50,4080.25,4120.75,4121.00,4095.30,250000,1030187500.00,15-JAN-2025,42000,INE467B01029
+INFY,EQ,1900.00,1925.00,1890.10,1960.00,1915.00,1898.60,600000,1149240000.00,15-JAN-2025,61000,INE009A01021
+SBIN,EQ,760.00,768.00,755.10,765.30,765.00,758.40,-10,6887700000.00,15-JAN-2025,210000,INE062A01020
+IDEA,EQ,12.00,12.40,11.90,12.30,12.30,9.80,90000000,1100000000.00,15-JAN-2025,300000,INE669E01016"#;
+
+    #[test]
+    fn test_validation_rejects_impossible_records_and_warns_on_big_moves() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let records = nse::parse_csv_data(SUSPECT_CSV, date).unwrap().records;
+
+        let validated = validation::validate_records(records, &validation::ValidationConfig::default());
+        let symbols: Vec<&str> = validated.records.iter().map(|r| r.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["RELIANCE", "IDEA"]);
+        assert_eq!(validated.rejected, 3);
+        assert_eq!(validated.warnings(), 1);
+
+        let rules = |symbol: &str| -> Vec<(validation::Rule, validation::Severity)> {
+            validated
+                .issues
+                .iter()
+                .filter(|issue| issue.symbol == symbol)
+                .map(|issue| (issue.rule, issue.severity))
+                .collect()
+        };
+        use validation::{Rule, Severity};
+        assert_eq!(
+            rules("TCS"),
+            vec![(Rule::PositivePrices, Severity::Rejection), (Rule::PriceRange, Severity::Rejection)]
+        );
+        assert_eq!(rules("INFY"), vec![(Rule::PriceRange, Severity::Rejection)]);
+        assert_eq!(rules("SBIN"), vec![(Rule::NonNegativeVolume, Severity::Rejection)]);
+        assert_eq!(rules("IDEA"), vec![(Rule::CloseChange, Severity::Warning)]);
+        assert!(validated.issues.iter().all(|issue| issue.trade_date == date && issue.exchange == "NSE"));
+    }
+
+    #[test]
+    fn test_validation_rules_are_configurable() {
+        use validation::{Action, Rule};
+        assert_eq!(validation::parse_rule_setting("close-change=reject").unwrap(), (Rule::CloseChange, Action::Reject));
+        assert_eq!(validation::parse_rule_setting("price_range=off").unwrap(), (Rule::PriceRange, Action::Off));
+        assert!(validation::parse_rule_setting("price_range").is_err());
+        assert!(validation::parse_rule_setting("volume_spike=warn").is_err());
+
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let records = nse::parse_csv_data(SUSPECT_CSV, date).unwrap().records;
+        let mut config = validation::ValidationConfig::default();
+        config.set_action(Rule::CloseChange, Action::Reject);
+        config.set_action(Rule::PriceRange, Action::Warn);
+        config.set_action(Rule::NonNegativeVolume, Action::Off);
+        config.max_close_change_pct = 2.0;
+
+        let validated = validation::validate_records(records, &config);
+        let symbols: Vec<&str> = validated.records.iter().map(|r| r.symbol.as_str()).collect();
+        // TCS still has a zero open; INFY moved 3.2% and IDEA 25.5%
+        assert_eq!(symbols, vec!["RELIANCE", "SBIN"]);
+        assert_eq!(validated.rejected, 3);
+        assert!(!validated.issues.iter().any(|issue| issue.rule == Rule::NonNegativeVolume));
+    }
+
+    #[tokio::test]
+    async fn test_quality_issues_are_stored_against_the_ingestion_run() {
+        let db = memory_database().await;
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let records = nse::parse_csv_data(SUSPECT_CSV, date).unwrap().records;
+        let validated = validation::validate_records(records, &validation::ValidationConfig::default());
+
+        let now = chrono::Local::now().naive_local();
+        let log = database::IngestionLogInsert {
+            source: "nse".to_string(),
+            file_name: None,
+            trade_date: Some(date),
+            records_processed: Some(2),
+            records_rejected: Some(3),
+            status: "partial".to_string(),
+            error_message: None,
+            series_filter: None,
+            started_at: Some(now),
+            completed_at: now,
+        };
+        let first_run = db.log_ingestion(&log).await.unwrap();
+        let ingestion_id = db.log_ingestion(&log).await.unwrap();
+        assert_ne!(first_run, ingestion_id);
+        assert_eq!(db.store_quality_issues(ingestion_id, &validated.issues).await.unwrap(), 5);
+
+        let all = db.get_quality_issues(&database::QualityIssueFilter::default()).await.unwrap();
+        assert_eq!(all.len(), 5);
+        assert!(all.iter().all(|issue| issue.ingestion_id == ingestion_id));
+
+        let tcs = db
+            .get_quality_issues(&database::QualityIssueFilter {
+                symbol: Some("tcs".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(tcs.len(), 2);
+        assert_eq!(tcs[0].rule, "positive_prices");
+        assert_eq!(tcs[0].severity, "rejection");
+        assert!(tcs[0].message.contains("open 0"));
+
+        let warnings = db
+            .get_quality_issues(&database::QualityIssueFilter {
+                severity: Some(validation::Severity::Warning),
+                from: Some(date),
+                to: Some(date),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].symbol, "IDEA");
+
+        let other_day = db
+            .get_quality_issues(&database::QualityIssueFilter {
+                from: date.succ_opt(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(other_day.is_empty());
+    }
+
+    fn closing_price(company_id: i64, symbol: &str, date: NaiveDate, close: f64, prev_close: f64) -> reconcile::ClosingPrice {
+        reconcile::ClosingPrice {
+            company_id,
+            exchange: "NSE".to_string(),
+            symbol: symbol.to_string(),
+            series: "EQ".to_string(),
+            isin: None,
+            trade_date: date,
+            close_price: close,
+            prev_close,
+        }
+    }
+
+    #[test]
+    fn test_reconciliation_classifies_prevclose_breaks() {
+        use reconcile::BreakCause;
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let prices = vec![
+            // Continuous, then a 1:5 face value split
+            closing_price(1, "SPLIT", day(22), 1000.0, 990.0),
+            closing_price(1, "SPLIT", day(23), 1010.0, 1000.0),
+            closing_price(1, "SPLIT", day(24), 205.0, 202.0),
+            // 1:2 bonus (one new share per two held)
+            closing_price(2, "BONUS", day(23), 300.0, 298.0),
+            closing_price(2, "BONUS", day(24), 202.0, 200.0),
+            // The 24th was never stored
+            closing_price(3, "GAP", day(23), 500.0, 495.0),
+            closing_price(3, "GAP", day(25), 520.0, 510.0),
+            // Republic Day (Friday the 26th) is not a missing day
+            closing_price(4, "HOLIDAY", day(25), 100.0, 99.0),
+            closing_price(4, "HOLIDAY", day(29), 101.0, 97.0),
+            // 10:1 consolidation
+            closing_price(5, "MERGED", day(23), 4.0, 4.1),
+            closing_price(5, "MERGED", day(24), 41.0, 40.0),
+        ];
+        let calendars = std::collections::HashMap::from([("NSE".to_string(), calendar::TradingCalendar::bundled("NSE"))]);
+
+        let breaks = reconcile::find_breaks(&prices, &calendars, 0.01);
+        let found: Vec<(&str, NaiveDate, BreakCause)> = breaks
+            .iter()
+            .map(|b| (b.symbol.as_str(), b.trade_date, b.cause))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("BONUS", day(24), BreakCause::Bonus),
+                ("MERGED", day(24), BreakCause::Split),
+                ("SPLIT", day(24), BreakCause::Split),
+                ("GAP", day(25), BreakCause::MissingDay),
+                ("HOLIDAY", day(29), BreakCause::DataError),
+            ]
+        );
+
+        let split = &breaks[2];
+        assert_eq!(split.previous_date, day(23));
+        assert_eq!(split.stored_close, 1010.0);
+        assert_eq!(split.reported_prev_close, 202.0);
+        assert_eq!(split.ratio, Some(5.0));
+        assert_eq!(split.detail, "Consistent with a 1:5 split, or a 4:1 bonus");
+        assert_eq!(breaks[0].detail, "Consistent with a 1:2 bonus");
+        assert_eq!(breaks[1].detail, "Consistent with a 10:1 consolidation");
+        assert!(breaks[3].detail.contains("2024-01-24"));
+    }
+
+    #[tokio::test]
+    async fn test_reconciliation_reads_stored_prices_and_exports() {
+        let db = memory_database().await;
+        let jan15 = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let jan16 = NaiveDate::from_ymd_opt(2025, 1, 16).unwrap();
+        db.store_stock_records(&nse::parse_csv_data(MULTI_SERIES_CSV, jan15).unwrap().records)
+            .await
+            .unwrap();
+        let next_day = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+SBIN,EQ,766.00,770.00,760.00,768.00,768.00,765.30,9000000,6887700000.00,16-JAN-2025,210000,INE062A01020
+SBIN,N1,1011.00,1012.00,1009.00,1010.50,1010.50,700.00,80,80800.00,16-JAN-2025,3,INE062A08049"#;
+        db.store_stock_records(&nse::parse_csv_data(next_day, jan16).unwrap().records)
+            .await
+            .unwrap();
+
+        let prices = db.get_closing_prices(Some("nse"), Some("sbin"), None, None).await.unwrap();
+        assert!(prices.windows(2).all(|pair| (pair[0].company_id, pair[0].trade_date) < (pair[1].company_id, pair[1].trade_date)));
+        let calendars = std::collections::HashMap::from([("NSE".to_string(), db.trading_calendar("NSE").await.unwrap())]);
+        let breaks = reconcile::find_breaks(&prices, &calendars, 0.01);
+        assert_eq!(breaks.len(), 1);
+        assert_eq!(breaks[0].series, "N1");
+        assert_eq!(breaks[0].cause, reconcile::BreakCause::DataError);
+
+        let only_16th = db.get_closing_prices(None, None, Some(jan16), None).await.unwrap();
+        assert!(only_16th.iter().all(|price| price.trade_date == jan16));
+
+        let mut csv_out = Vec::new();
+        reconcile::write_csv(&breaks, &mut csv_out).unwrap();
+        let csv_out = String::from_utf8(csv_out).unwrap();
+        let mut lines = csv_out.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "exchange,symbol,series,isin,previous_date,stored_close,trade_date,reported_prev_close,ratio,cause,detail"
+        );
+        assert!(lines.next().unwrap().starts_with("NSE,SBIN,N1,INE062A08049,2025-01-15,"));
+
+        let mut empty = Vec::new();
+        reconcile::write_csv(&[], &mut empty).unwrap();
+        assert_eq!(String::from_utf8(empty).unwrap().lines().count(), 1);
+
+        let mut json_out = Vec::new();
+        reconcile::write_json(&breaks, &mut json_out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json_out).unwrap();
+        assert_eq!(json[0]["cause"], "data_error");
+        assert_eq!(json[0]["trade_date"], "2025-01-16");
+    }
+
+    #[test]
+    fn test_purpose_strings_parse_into_terms() {
+        use corporate_actions::{ActionKind, parse_purpose};
+        let terms = |purpose: &str| {
+            let parsed = parse_purpose(purpose, Some(10.0));
+            assert_eq!(parsed.len(), 1, "{}", purpose);
+            parsed.into_iter().next().unwrap().1
+        };
+
+        let split = terms("Face Value Split From Rs 10 To Rs 2");
+        assert_eq!(split.kind, ActionKind::Split);
+        assert_eq!((split.old_face_value, split.new_face_value), (Some(10.0), Some(2.0)));
+        assert_eq!(split.price_factor(), Some(0.2));
+        let split = terms("Face Value Split (Sub-Division) - From Rs.10/- Per Share To Re.1/- Per Share");
+        assert_eq!((split.old_face_value, split.new_face_value), (Some(10.0), Some(1.0)));
+        let consolidation = terms("Consolidation Of Shares From Rs 1/- Per Share To Rs 10/- Per Share");
+        assert_eq!(consolidation.kind, ActionKind::Split);
+        assert_eq!(consolidation.price_factor(), Some(10.0));
+
+        let bonus = terms("Bonus 1:1");
+        assert_eq!(bonus.kind, ActionKind::Bonus);
+        assert_eq!((bonus.ratio_new, bonus.ratio_held), (Some(1), Some(1)));
+        assert_eq!(bonus.price_factor(), Some(0.5));
+        let bonus = terms("Bonus 3 : 2");
+        assert_eq!((bonus.ratio_new, bonus.ratio_held), (Some(3), Some(2)));
+        assert_eq!(bonus.price_factor(), Some(0.4));
+
+        let dividend = terms("Interim Dividend - Rs 6.50 Per Share");
+        assert_eq!(dividend.kind, ActionKind::Dividend);
+        assert_eq!(dividend.amount, Some(6.5));
+        assert_eq!(dividend.price_factor(), None);
+        assert_eq!(terms("Dividend - Re 0.50 Per Share").amount, Some(0.5));
+        // Percent of the Rs 10 face value
+        assert_eq!(terms("Dividend-35%").amount, Some(3.5));
+
+        let rights = terms("Rights 3:25 @ Premium Rs 2248/-");
+        assert_eq!(rights.kind, ActionKind::Rights);
+        assert_eq!((rights.ratio_new, rights.ratio_held), (Some(3), Some(25)));
+        assert_eq!(rights.amount, Some(2248.0));
+
+        assert_eq!(terms("Buy Back").kind, ActionKind::Other);
+
+        let several = parse_purpose("Annual General Meeting/Dividend - Rs 20 Per Share/Special Dividend - Rs 8 Per Share", None);
+        let parts: Vec<(&str, Option<f64>)> = several.iter().map(|(purpose, terms)| (purpose.as_str(), terms.amount)).collect();
+        assert_eq!(
+            parts,
+            vec![("Dividend - Rs 20 Per Share", Some(20.0)), ("Special Dividend - Rs 8 Per Share", Some(8.0))]
+        );
+    }
+
+    #[test]
+    fn test_corporate_action_files_parse() {
+        let nse = corporate_actions::parse_corporate_actions_csv(
+            include_str!("../tests/fixtures/CF-CA-equities-01-05-2024-to-31-01-2025.csv"),
+            "NSE",
+        )
+        .unwrap();
+        assert_eq!(nse.len(), 7);
+        assert!(nse.iter().all(|action| action.source == "nse" && action.exchange == "NSE" && action.series == "EQ"));
+        let reliance = &nse[0];
+        assert_eq!(reliance.symbol, "RELIANCE");
+        assert_eq!(reliance.company_name.as_deref(), Some("Reliance Industries Limited"));
+        assert_eq!(reliance.ex_date, NaiveDate::from_ymd_opt(2024, 10, 28).unwrap());
+        assert_eq!(reliance.record_date, NaiveDate::from_ymd_opt(2024, 10, 28));
+        assert_eq!(reliance.isin, None);
+        let wipro = nse.iter().find(|action| action.symbol == "WIPRO").unwrap();
+        assert_eq!(wipro.record_date, None);
+
+        let manual = corporate_actions::parse_corporate_actions_csv(
+            "symbol,isin,ex_date,purpose,exchange\nirfc,ine053f01010,2025-01-14,Split From Rs 10 To Rs 2,\n500325,,28-Oct-2024,Bonus 1:1,BSE",
+            "NSE",
+        )
+        .unwrap();
+        assert_eq!(manual.len(), 2);
+        assert_eq!(manual[0].source, "manual");
+        assert_eq!((manual[0].symbol.as_str(), manual[0].exchange.as_str()), ("IRFC", "NSE"));
+        assert_eq!(manual[0].isin.as_deref(), Some("INE053F01010"));
+        assert_eq!(manual[1].exchange, "BSE");
+        assert_eq!(manual[1].isin, None);
+
+        let json = corporate_actions::parse_corporate_actions_json(
+            r#"[{"symbol": "TCS", "ex_date": "2025-01-17", "purpose": "Interim Dividend - Rs 10 Per Share"}]"#,
+            "NSE",
+        )
+        .unwrap();
+        assert_eq!(json[0].terms.amount, Some(10.0));
+
+        let err = corporate_actions::parse_corporate_actions_csv("symbol,ex_date,purpose\nTCS,17/01/2025,Bonus 1:1", "NSE").unwrap_err();
+        assert!(err.to_string().contains("row 2"));
+    }
+
+    #[tokio::test]
+    async fn test_corporate_actions_link_to_listings_by_isin() {
+        let db = memory_database().await;
+        let csv = "symbol,ex_date,purpose,exchange\nSBIN,2025-01-09,Rights 3:25 @ Premium Rs 2248/-,\nSBIN,2024-06-01,Dividend - Rs 13.70 Per Share,\nTCS,2025-01-17,Bonus 1:1,";
+        let actions = corporate_actions::parse_corporate_actions_csv(csv, "NSE").unwrap();
+
+        // SBIN is already listed, TCS is not
+        let jan15 = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        db.store_stock_records(&nse::parse_csv_data(MULTI_SERIES_CSV, jan15).unwrap().records)
+            .await
+            .unwrap();
+        assert_eq!(db.upsert_corporate_actions(&actions).await.unwrap(), 3);
+        assert_eq!(db.link_corporate_actions().await.unwrap(), 1);
+
+        // Importing again refreshes rather than duplicates
+        db.upsert_corporate_actions(&actions).await.unwrap();
+        let all = db.get_corporate_actions(&database::CorporateActionFilter::default()).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].ex_date, NaiveDate::from_ymd_opt(2024, 6, 1).unwrap());
+        assert_eq!(all[0].isin.as_deref(), Some("INE062A01020"));
+
+        // TCS is linked once it is ingested
+        db.store_stock_records(&nse::parse_csv_data(
+            include_str!("../tests/fixtures/cm15JAN2025bhav.csv"),
+            jan15,
+        ).unwrap().records)
+        .await
+        .unwrap();
+        assert_eq!(db.link_corporate_actions().await.unwrap(), 0);
+
+        // The BSE listing of SBIN finds the NSE actions through its ISIN
+        db.store_stock_records(&[source::StockRecord {
+            exchange: "BSE".to_string(),
+            symbol: "500112".to_string(),
+            series: "A".to_string(),
+            open: 760.0,
+            high: 768.0,
+            low: 755.0,
+            close: 765.0,
+            last: 765.0,
+            prevclose: 758.0,
+            tottrdqty: 100,
+            tottrdval: 76500.0,
+            timestamp: jan15,
+            totaltrades: 10,
+            isin: "INE062A01020".to_string(),
+        }])
+        .await
+        .unwrap();
+        let sbin = db
+            .get_corporate_actions(&database::CorporateActionFilter {
+                symbol: Some("500112".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(sbin.len(), 2);
+        assert!(sbin.iter().all(|action| action.symbol == "SBIN"));
+
+        let rights = db
+            .get_corporate_actions(&database::CorporateActionFilter {
+                isin: Some("ine062a01020".to_string()),
+                kind: Some(corporate_actions::ActionKind::Rights),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(rights.len(), 1);
+        assert_eq!((rights[0].terms.ratio_new, rights[0].terms.ratio_held, rights[0].terms.amount), (Some(3), Some(25), Some(2248.0)));
+
+        let bonus = db
+            .get_corporate_actions(&database::CorporateActionFilter {
+                from: NaiveDate::from_ymd_opt(2025, 1, 17),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(bonus[0].isin.as_deref(), Some("INE467B01029"));
+        assert_eq!(bonus[0].terms.price_factor(), Some(0.5));
+    }
+
+    fn manual_actions(csv: &str) -> Vec<corporate_actions::CorporateAction> {
+        corporate_actions::parse_corporate_actions_csv(csv, "NSE").unwrap()
+    }
+
+    fn price_bar(date: NaiveDate, close: f64, volume: f64) -> adjustment::PriceBar {
+        adjustment::PriceBar {
+            company_id: 1,
+            exchange: "NSE".to_string(),
+            symbol: "ACME".to_string(),
+            series: "EQ".to_string(),
+            trade_date: date,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            last: close,
+            prev_close: close,
+            volume,
+            traded_value: close * volume,
+            trades: 1,
+            deliverable_qty: None,
+            delivery_pct: None,
+        }
+    }
+
+    #[test]
+    fn test_adjusted_prices_apply_later_factors() {
+        use adjustment::Adjustment;
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let actions = manual_actions(
+            "symbol,ex_date,purpose\nACME,2024-03-05,Face Value Split From Rs 10 To Rs 5\nACME,2024-03-07,Dividend - Rs 5 Per Share\nACME,2024-03-07,Annual General Meeting",
+        );
+        let factors = adjustment::compute_factors(1, &actions, |date| {
+            assert_eq!(date, day(7));
+            Some(100.0)
+        });
+        assert_eq!(factors.len(), 2);
+        assert_eq!((factors[0].ex_date, factors[0].split_factor, factors[0].dividend_factor), (day(5), 0.5, 1.0));
+        assert_eq!((factors[1].ex_date, factors[1].split_factor, factors[1].dividend_factor), (day(7), 1.0, 0.95));
+        assert_eq!((factors[1].dividend, factors[1].reference_close), (Some(5.0), Some(100.0)));
+
+        let bars = vec![price_bar(day(4), 200.0, 1000.0), price_bar(day(6), 100.0, 2000.0), price_bar(day(7), 96.0, 2000.0)];
+
+        let mut raw = bars.clone();
+        adjustment::adjust_prices(&mut raw, &factors, Adjustment::Raw);
+        assert_eq!(raw, bars);
+
+        let mut split = bars.clone();
+        adjustment::adjust_prices(&mut split, &factors, Adjustment::Split);
+        let closes: Vec<(f64, f64)> = split.iter().map(|bar| (bar.close, bar.volume)).collect();
+        assert_eq!(closes, vec![(100.0, 2000.0), (100.0, 2000.0), (96.0, 2000.0)]);
+        assert_eq!(split[0].traded_value, 200_000.0);
+
+        let mut total_return = bars.clone();
+        adjustment::adjust_prices(&mut total_return, &factors, Adjustment::TotalReturn);
+        let closes: Vec<f64> = total_return.iter().map(|bar| bar.close).collect();
+        assert_eq!(closes, vec![95.0, 95.0, 96.0]);
+        // Volumes are only adjusted for share count changes
+        assert_eq!(total_return[0].volume, 2000.0);
+
+        assert_eq!("total_return".parse::<Adjustment>().unwrap(), Adjustment::TotalReturn);
+        assert!("dividend".parse::<Adjustment>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_action_recorded_on_both_exchanges_is_applied_once() {
+        use adjustment::Adjustment;
+        let db = memory_database().await;
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        db.store_stock_records(&nse_records(day(4), &[("RELIANCE", "INE002A01018", 2600.0)])).await.unwrap();
+        db.store_stock_records(&nse_records(day(5), &[("RELIANCE", "INE002A01018", 1310.0)])).await.unwrap();
+        db.upsert_company("BSE", "500325", "INE002A01018", "A", day(4)).await.unwrap();
+
+        let actions = manual_actions(
+            "symbol,ex_date,purpose,isin,exchange\nRELIANCE,2024-03-05,Bonus 1:1,INE002A01018,NSE\n500325,2024-03-05,Bonus 1:1,INE002A01018,BSE\nRELIANCE,2024-03-05,Dividend - Rs 10 Per Share,INE002A01018,NSE\n500325,2024-03-05,Dividend - Rs 10 Per Share,INE002A01018,BSE",
+        );
+        db.upsert_corporate_actions(&actions).await.unwrap();
+        db.refresh_adjustment_factors().await.unwrap();
+
+        let filter = database::PriceFilter { symbol: Some("RELIANCE".to_string()), ..Default::default() };
+        let split = db.get_adjusted_prices(&filter, Adjustment::Split).await.unwrap();
+        assert_eq!(split[0].close, 1300.0);
+        let listing = &db.find_listings_by_symbol(Some("NSE"), "RELIANCE", None).await.unwrap()[0];
+        let factors = db.get_adjustment_factors(&[listing.id]).await.unwrap();
+        assert_eq!((factors[0].split_factor, factors[0].dividend), (0.5, Some(10.0)));
+    }
+
+    #[tokio::test]
+    async fn test_adjustment_factors_are_cached_and_refreshed_incrementally() {
+        use adjustment::Adjustment;
+        let db = memory_database().await;
+        let jan15 = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let jan16 = NaiveDate::from_ymd_opt(2025, 1, 16).unwrap();
+        db.store_stock_records(&nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), jan15).unwrap().records)
+            .await
+            .unwrap();
+        let next_day = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+RELIANCE,EQ,1255.00,1270.00,1250.00,1262.00,1262.00,1260.00,2000000,2524000000.00,16-JAN-2025,60000,INE002A01018
+TCS,EQ,4100.00,4150.00,4080.00,4130.00,4130.00,4120.75,250000,1030187500.00,16-JAN-2025,42000,INE467B01029"#;
+        db.store_stock_records(&nse::parse_csv_data(next_day, jan16).unwrap().records)
+            .await
+            .unwrap();
+
+        // Nothing to do without corporate actions
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 0);
+
+        let actions = manual_actions("symbol,ex_date,purpose\nRELIANCE,2025-01-16,Bonus 1:1\nTCS,2025-01-16,Dividend - Rs 41.2075 Per Share");
+        db.upsert_corporate_actions(&actions).await.unwrap();
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 2);
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 0);
+
+        // Importing the same actions again leaves the cache alone
+        db.upsert_corporate_actions(&actions).await.unwrap();
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 0);
+
+        let reliance = database::PriceFilter {
+            symbol: Some("RELIANCE".to_string()),
+            ..Default::default()
+        };
+        let raw = db.get_adjusted_prices(&reliance, Adjustment::Raw).await.unwrap();
+        assert_eq!(raw[0].close, 2520.0);
+        let adjusted = db.get_adjusted_prices(&reliance, Adjustment::Split).await.unwrap();
+        assert_eq!((adjusted[0].close, adjusted[0].volume), (1260.0, 2_000_000.0));
+        assert_eq!((adjusted[1].close, adjusted[1].volume), (1262.0, 2_000_000.0));
+
+        let tcs = database::PriceFilter {
+            symbol: Some("TCS".to_string()),
+            ..Default::default()
+        };
+        let split_only = db.get_adjusted_prices(&tcs, Adjustment::Split).await.unwrap();
+        assert_eq!(split_only[0].close, 4120.75);
+        let total_return = db.get_adjusted_prices(&tcs, Adjustment::TotalReturn).await.unwrap();
+        assert_eq!(total_return[0].close, 4079.5425);
+        assert_eq!(total_return[1].close, 4130.0);
+
+        // A corrected dividend only recomputes TCS
+        let corrected = manual_actions("symbol,ex_date,purpose\nTCS,2025-01-16,Dividend - Rs 20.60375 Per Share");
+        db.upsert_corporate_actions(&corrected).await.unwrap();
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 1);
+
+        // A dividend before the first stored price is recomputed once its
+        // reference close arrives
+        let early = manual_actions("symbol,ex_date,purpose\nINFY,2025-01-15,Dividend - Rs 18.986 Per Share");
+        db.upsert_corporate_actions(&early).await.unwrap();
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 1);
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 0);
+        let jan14 = NaiveDate::from_ymd_opt(2025, 1, 14).unwrap();
+        let day_before = r#"SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
+INFY,EQ,1890.00,1905.00,1880.00,1898.60,1898.00,1885.00,500000,949300000.00,14-JAN-2025,50000,INE009A01021"#;
+        db.store_stock_records(&nse::parse_csv_data(day_before, jan14).unwrap().records)
+            .await
+            .unwrap();
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 1);
+        let infy = db
+            .get_adjusted_prices(
+                &database::PriceFilter {
+                    symbol: Some("INFY".to_string()),
+                    ..Default::default()
+                },
+                Adjustment::TotalReturn,
+            )
+            .await
+            .unwrap();
+        assert_eq!(infy[0].close, 1879.614);
+    }
+
+    /// One NSE EQ record per (symbol, isin, close) on `date`.
+    fn nse_records(date: NaiveDate, rows: &[(&str, &str, f64)]) -> Vec<source::StockRecord> {
+        let mut csv = String::from("SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN\n");
+        for (symbol, isin, close) in rows {
+            let timestamp = date.format("%d-%b-%Y").to_string().to_uppercase();
+            csv.push_str(&format!("{symbol},EQ,{close},{close},{close},{close},{close},{close},100,{},{timestamp},10,{isin}\n", close * 100.0));
+        }
+        nse::parse_csv_data(&csv, date).unwrap().records
+    }
+
+    async fn company_ids(db: &database::Database) -> Vec<(String, String, i64)> {
+        sqlx::query_as("SELECT c.symbol, COALESCE(c.isin, ''), COUNT(p.id) FROM companies c LEFT JOIN daily_prices p ON p.company_id = c.id GROUP BY c.id ORDER BY c.symbol")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_symbol_changes() {
+        let nse = "SM_NAME,SM_KEY_SYMBOL,SM_NEW_SYMBOL,SM_APPLICABLE_FROM\nZomato Limited,zomato,ETERNAL,\"09-APR-2025\"\nAditya Birla Nuvo Limited,INDRAYON,ABIRLANUVO,08-Feb-2000";
+        let changes = symbols::parse_symbol_changes_csv(nse, "NSE").unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].old_symbol, "INDRAYON");
+        assert_eq!(changes[1].old_symbol, "ZOMATO");
+        assert_eq!(changes[1].new_symbol, "ETERNAL");
+        assert_eq!(changes[1].effective_date, NaiveDate::from_ymd_opt(2025, 4, 9).unwrap());
+        assert_eq!(changes[1].company_name.as_deref(), Some("Zomato Limited"));
+        assert_eq!(changes[1].exchange, "NSE");
+
+        let headerless = "Zomato Limited,ZOMATO,ETERNAL,09-APR-2025\n";
+        assert_eq!(symbols::parse_symbol_changes_csv(headerless, "NSE").unwrap(), changes[1..]);
+
+        let err = symbols::parse_symbol_changes_csv("old_symbol,new_symbol,effective_date\nA,B,2025-01-01\nC,,2025-01-02", "NSE").unwrap_err();
+        assert!(err.to_string().contains("row 3"));
+    }
+
+    #[tokio::test]
+    async fn test_renamed_symbol_keeps_its_listing() {
+        let db = memory_database().await;
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        db.store_stock_records(&nse_records(day(14), &[("INFY", "INE009A01021", 1890.0)])).await.unwrap();
+        db.store_stock_records(&nse_records(day(15), &[("INFY", "INE009A01021", 1895.0)])).await.unwrap();
+        db.store_stock_records(&nse_records(day(16), &[("INFOSYS", "INE009A01021", 1900.0)])).await.unwrap();
+
+        // Same ISIN on the same exchange and series: one listing, renamed
+        assert_eq!(company_ids(&db).await, vec![("INFOSYS".to_string(), "INE009A01021".to_string(), 3)]);
+
+        // A backfilled day under the old symbol joins the listing without renaming it back
+        db.store_stock_records(&nse_records(day(13), &[("INFY", "INE009A01021", 1885.0)])).await.unwrap();
+        assert_eq!(company_ids(&db).await, vec![("INFOSYS".to_string(), "INE009A01021".to_string(), 4)]);
+
+        let bars = db
+            .get_price_bars(&database::PriceFilter {
+                symbol: Some("infy".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(bars.len(), 4);
+        assert!(bars.iter().all(|bar| bar.symbol == "INFOSYS"));
+
+        let listing = &db.find_listings_by_symbol(Some("NSE"), "INFY", None).await.unwrap()[0];
+        let history = db.get_symbol_history(&[listing.id]).await.unwrap();
+        let ranges: Vec<_> = history.iter().map(|entry| (entry.symbol.as_str(), entry.valid_from, entry.valid_to)).collect();
+        assert_eq!(ranges, vec![("INFY", Some(day(13)), Some(day(15))), ("INFOSYS", Some(day(16)), None)]);
+        assert!(history[1].is_current());
+        assert_eq!(db.find_listings_by_symbol(None, "INFY", Some(day(15))).await.unwrap().len(), 1);
+        assert!(db.find_listings_by_symbol(None, "INFY", Some(day(16))).await.unwrap().is_empty());
+
+        // An ISIN change is recorded rather than overwritten, and older data
+        // under the old ISIN does not revert it
+        db.store_stock_records(&nse_records(day(17), &[("INFOSYS", "INE009A01039", 950.0)])).await.unwrap();
+        db.store_stock_records(&nse_records(day(10), &[("INFY", "INE009A01021", 1880.0)])).await.unwrap();
+        assert_eq!(company_ids(&db).await, vec![("INFOSYS".to_string(), "INE009A01039".to_string(), 6)]);
+        let history = db.get_symbol_history(&[listing.id]).await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!((history[1].valid_to, history[2].valid_from), (Some(day(16)), Some(day(17))));
+        // A historical sighting is dated by its trade date, not the wall clock
+        assert_eq!(db.upsert_company("NSE", "INFY", "INE009A01021", "EQ", day(12)).await.unwrap(), listing.id);
+        assert_eq!(db.get_symbol_history(&[listing.id]).await.unwrap(), history);
+    }
+
+    #[tokio::test]
+    async fn test_symbol_change_file_merges_listings() {
+        let db = memory_database().await;
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        // The rename came with a new ISIN, so ingestion cannot link the two
+        db.store_stock_records(&nse_records(day(15), &[("OLDCO", "INE000A01011", 100.0), ("TCS", "INE467B01029", 4120.0)]))
+            .await
+            .unwrap();
+        db.store_stock_records(&nse_records(day(16), &[("NEWCO", "INE000A01029", 101.0), ("TCS", "INE467B01029", 4130.0)]))
+            .await
+            .unwrap();
+        assert_eq!(company_ids(&db).await.len(), 3);
+        let actions = manual_actions("symbol,ex_date,purpose\nOLDCO,2025-01-10,Dividend - Rs 2 Per Share");
+        db.upsert_corporate_actions(&actions).await.unwrap();
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 1);
+        // The next equity list has the new symbol instead, which delists the old one
+        let header = "SYMBOL,NAME OF COMPANY, SERIES, DATE OF LISTING, PAID UP VALUE, MARKET LOT, ISIN NUMBER, FACE VALUE\n";
+        let tcs = "TCS,Tata Consultancy Services Limited,EQ,25-AUG-2004,1,1,INE467B01029,1\n";
+        let list = format!("{header}{tcs}OLDCO,Old Co Limited,EQ,01-JAN-2000,10,1,INE000A01011,10\n");
+        db.import_equity_list(&companies::parse_equity_list_csv(&list).unwrap(), day(15)).await.unwrap();
+        let list = format!("{header}{tcs}NEWCO,New Co Limited,EQ,16-JAN-2025,10,1,INE000A01029,10\n");
+        assert_eq!(db.import_equity_list(&companies::parse_equity_list_csv(&list).unwrap(), day(16)).await.unwrap().delisted, 1);
+        let classifications = companies::parse_classification_csv("symbol,industry\nNEWCO,Textiles").unwrap();
+        assert_eq!(db.import_classifications(&classifications).await.unwrap(), 0);
+
+        let changes = symbols::parse_symbol_changes_csv(
+            "SM_NAME,SM_KEY_SYMBOL,SM_NEW_SYMBOL,SM_APPLICABLE_FROM\nNew Co,OLDCO,NEWCO,16-JAN-2025\nTata Consultancy,TCSLTD,TCS,01-JAN-2004\nNobody,GONE,ELSEWHERE,01-JAN-2025",
+            "NSE",
+        )
+        .unwrap();
+        let summary = db.apply_symbol_changes(&changes).await.unwrap();
+        assert_eq!(summary, symbols::SymbolChangeSummary { applied: 2, merged: 1, unmatched: 1 });
+        assert_eq!(
+            company_ids(&db).await,
+            vec![("NEWCO".to_string(), "INE000A01029".to_string(), 2), ("TCS".to_string(), "INE467B01029".to_string(), 2)]
+        );
+
+        let listing = &db.find_listings_by_symbol(None, "OLDCO", None).await.unwrap()[0];
+        assert_eq!(listing.symbol, "NEWCO");
+        let history = db.get_symbol_history(&[listing.id]).await.unwrap();
+        let ranges: Vec<_> = history.iter().map(|entry| (entry.symbol.as_str(), entry.valid_to)).collect();
+        assert_eq!(ranges, vec![("OLDCO", Some(day(15))), ("NEWCO", None)]);
+        // Master data merges too: the old listing's fields win, but it is
+        // active again since the new symbol is in the later list
+        let profile = &db
+            .search_companies(&database::CompanyFilter { symbol: Some("NEWCO".to_string()), ..Default::default() })
+            .await
+            .unwrap()[0];
+        assert_eq!((profile.name.as_deref(), profile.listing_date), (Some("Old Co Limited"), Some(NaiveDate::from_ymd_opt(2000, 1, 1).unwrap())));
+        assert_eq!((profile.industry.as_deref(), profile.face_value), (Some("Textiles"), Some(10.0)));
+        assert_eq!((profile.status.as_str(), profile.status_date), ("active", None));
+
+        // The merged listing keeps the old ISIN's actions; both of its ISINs
+        // were queued for recomputing
+        assert_eq!(db.refresh_adjustment_factors().await.unwrap(), 2);
+        let factors = db.get_adjustment_factors(&[listing.id]).await.unwrap();
+        assert_eq!(factors.len(), 1);
+        let actions = db
+            .get_corporate_actions(&database::CorporateActionFilter {
+                symbol: Some("NEWCO".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(actions.len(), 1);
+
+        // A former symbol that was never ingested still finds the listing
+        let tcs = db.find_listings_by_symbol(Some("NSE"), "TCSLTD", None).await.unwrap();
+        assert_eq!(tcs[0].symbol, "TCS");
+
+        // Applying the file again changes nothing
+        let again = db.apply_symbol_changes(&changes).await.unwrap();
+        assert_eq!(again.merged, 0);
+        assert_eq!(company_ids(&db).await.len(), 2);
+    }
+
+    const EQUITY_LIST: &str = "SYMBOL,NAME OF COMPANY, SERIES, DATE OF LISTING, PAID UP VALUE, MARKET LOT, ISIN NUMBER, FACE VALUE
+RELIANCE,Reliance Industries Limited,EQ,29-NOV-1995,10,1,INE002A01018,10
+TCS,Tata Consultancy Services Limited,EQ,25-AUG-2004,1,1,INE467B01029,1
+INFY,Infosys Limited,EQ,08-FEB-1995,5,1,INE009A01021,5
+";
+
+    #[test]
+    fn test_parse_company_master_files() {
+        assert!(companies::is_equity_list(EQUITY_LIST));
+        let records = companies::parse_equity_list_csv(EQUITY_LIST).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].symbol, "TCS");
+        assert_eq!(records[1].name, "Tata Consultancy Services Limited");
+        assert_eq!(records[1].listing_date, NaiveDate::from_ymd_opt(2004, 8, 25));
+        assert_eq!((records[1].face_value, records[1].paid_up_value, records[1].market_lot), (Some(1.0), Some(1.0), Some(1)));
+        assert_eq!((records[1].exchange.as_str(), records[1].series.as_str()), ("NSE", "EQ"));
+
+        let index = "Company Name,Industry,Symbol,Series,ISIN Code\nInfosys Ltd.,Information Technology,INFY,EQ,INE009A01021\nSomething Ltd.,Capital Goods,SMTH,EQ,";
+        assert!(!companies::is_equity_list(index));
+        let classifications = companies::parse_classification_csv(index).unwrap();
+        assert_eq!(classifications[0].industry.as_deref(), Some("Information Technology"));
+        assert_eq!(classifications[0].isin.as_deref(), Some("INE009A01021"));
+        assert_eq!((classifications[1].symbol.as_deref(), classifications[1].isin.as_deref()), (Some("SMTH"), None));
+        let err = companies::parse_classification_csv("symbol,sector\nINFY,").unwrap_err();
+        assert!(err.to_string().contains("row 2"));
+
+        let suspended = companies::parse_status_csv("Symbol,Date of Suspension\nabc,05-Mar-2024\nXYZ,", companies::ListingStatus::Suspended, "NSE").unwrap();
+        assert_eq!(suspended[0].symbol, "ABC");
+        assert_eq!(suspended[0].date, NaiveDate::from_ymd_opt(2024, 3, 5));
+        assert_eq!(suspended[1].date, None);
+        assert_eq!("Delisted".parse::<companies::ListingStatus>().unwrap(), companies::ListingStatus::Delisted);
+    }
+
+    #[tokio::test]
+    async fn test_equity_list_fills_master_data_and_flags_delistings() {
+        let db = memory_database().await;
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        db.store_stock_records(&nse_records(day(1, 2), &[("RELIANCE", "INE002A01018", 2600.0), ("INFY", "INE009A01021", 1500.0)]))
+            .await
+            .unwrap();
+        db.upsert_company("BSE", "500325", "INE002A01018", "A", day(1, 2)).await.unwrap();
+
+        let records = companies::parse_equity_list_csv(EQUITY_LIST).unwrap();
+        let summary = db.import_equity_list(&records, day(1, 5)).await.unwrap();
+        assert_eq!(summary, companies::MasterImportSummary { imported: 3, new_listings: 1, delisted: 0, relisted: 0 });
+        // Existing listings are reused, and TCS is created without prices
+        assert_eq!(company_ids(&db).await.len(), 4);
+
+        let profile = |symbol: &str| {
+            let db = &db;
+            let symbol = symbol.to_string();
+            async move {
+                db.search_companies(&database::CompanyFilter { symbol: Some(symbol), ..Default::default() })
+                    .await
+                    .unwrap()
+            }
+        };
+        let reliance = profile("RELIANCE").await;
+        assert_eq!(reliance[0].name.as_deref(), Some("Reliance Industries Limited"));
+        assert_eq!(reliance[0].face_value, Some(10.0));
+        assert_eq!(reliance[0].listing_date, Some(NaiveDate::from_ymd_opt(1995, 11, 29).unwrap()));
+        assert_eq!(reliance[0].first_trade_date, Some(day(1, 2)));
+        // The BSE listing of the same ISIN takes its name and face value
+        let bse = profile("500325").await;
+        assert_eq!((bse[0].name.as_deref(), bse[0].face_value), (Some("Reliance Industries Limited"), Some(10.0)));
+
+        // A later list without INFY flags it delisted, without removing it
+        let later: Vec<_> = records.iter().filter(|record| record.symbol != "INFY").cloned().collect();
+        let summary = db.import_equity_list(&later, day(2, 1)).await.unwrap();
+        assert_eq!((summary.new_listings, summary.delisted), (0, 1));
+        let infy = profile("INFY").await;
+        assert_eq!((infy[0].status.as_str(), infy[0].status_date), ("delisted", Some(day(2, 1))));
+        assert_eq!(infy[0].last_trade_date, Some(day(1, 2)));
+        // Re-importing an older list does not flag the listings seen since
+        assert_eq!(db.import_equity_list(&records[..1], day(1, 5)).await.unwrap().delisted, 0);
+        // Nor does importing lists out of order relist INFY or bring back
+        // an old name
+        let mut stale = records.clone();
+        stale[0].name = "Reliance Petrochemicals Limited".to_string();
+        assert_eq!(db.import_equity_list(&stale, day(1, 20)).await.unwrap(), companies::MasterImportSummary { imported: 3, ..Default::default() });
+        let infy = profile("INFY").await;
+        assert_eq!((infy[0].status.as_str(), infy[0].status_date), ("delisted", Some(day(2, 1))));
+        assert_eq!(profile("RELIANCE").await[0].name.as_deref(), Some("Reliance Industries Limited"));
+        assert_eq!(db.import_equity_list(&records, day(3, 1)).await.unwrap().relisted, 1);
+        assert_eq!(profile("INFY").await[0].status, "active");
+
+        let classifications = companies::parse_classification_csv(
+            "Company Name,Industry,Symbol,Series,ISIN Code\nReliance Industries Ltd.,Oil Gas & Consumable Fuels,RELIANCE,EQ,INE002A01018\nInfosys Ltd.,Information Technology,INFY,EQ,\nNobody Ltd.,Textiles,NOBODY,EQ,INE000000000",
+        )
+        .unwrap();
+        assert_eq!(db.import_classifications(&classifications).await.unwrap(), 1);
+        assert_eq!(profile("500325").await[0].industry.as_deref(), Some("Oil Gas & Consumable Fuels"));
+
+        let suspended = companies::parse_status_csv("symbol,date\nTCS,2024-03-04\nNOBODY,", companies::ListingStatus::Suspended, "NSE").unwrap();
+        assert_eq!(db.set_listing_status(&suspended).await.unwrap(), 1);
+
+        let search = |filter: database::CompanyFilter| {
+            let db = &db;
+            async move {
+                db.search_companies(&filter)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|profile| format!("{} {}", profile.exchange, profile.symbol))
+                    .collect::<Vec<_>>()
+            }
+        };
+        let text = |text: &str| database::CompanyFilter { text: Some(text.to_string()), ..Default::default() };
+        assert_eq!(search(text("reliance")).await, vec!["NSE RELIANCE", "BSE 500325"]);
+        assert_eq!(search(text("INE009A01021")).await, vec!["NSE INFY"]);
+        assert_eq!(
+            search(database::CompanyFilter { industry: Some("information".to_string()), ..Default::default() }).await,
+            vec!["NSE INFY"]
+        );
+        assert_eq!(
+            search(database::CompanyFilter { status: Some(companies::ListingStatus::Suspended), ..Default::default() }).await,
+            vec!["NSE TCS"]
+        );
+        assert_eq!(search(database::CompanyFilter { limit: Some(2), ..Default::default() }).await.len(), 2);
+
+        // Flagging a cross-listed ISIN on NSE leaves its BSE listing alone
+        let delisted = vec![companies::StatusChange {
+            exchange: "NSE".to_string(),
+            symbol: "RELIANCE".to_string(),
+            isin: Some("INE002A01018".to_string()),
+            status: companies::ListingStatus::Delisted,
+            date: Some(day(4, 1)),
+        }];
+        assert_eq!(db.set_listing_status(&delisted).await.unwrap(), 0);
+        assert_eq!(profile("RELIANCE").await[0].status, "delisted");
+        assert_eq!((profile("500325").await[0].status.as_str(), profile("500325").await[0].status_date), ("active", None));
+    }
+
+    #[test]
+    fn test_parse_delivery_file() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let content = include_str!("../tests/fixtures/MTO_15012025.DAT");
+        let parsed = delivery::parse_mto(content, date).unwrap();
+        assert_eq!(parsed.records.len(), 5);
+        assert!(parsed.rejected.is_empty());
+        let reliance = parsed.records.iter().find(|record| record.symbol == "RELIANCE").unwrap();
+        assert_eq!((reliance.traded_qty, reliance.deliverable_qty, reliance.delivery_pct), (1000000, 512345, 51.23));
+        assert_eq!((reliance.exchange.as_str(), reliance.series.as_str(), reliance.trade_date), ("NSE", "EQ", date));
+
+        let err = delivery::parse_mto(content, date.succ_opt().unwrap()).unwrap_err();
+        assert!(err.downcast_ref::<source::StaleFile>().is_some());
+
+        let damaged = content.replace("20,5,TCS,EQ,250000", "20,5,TCS,EQ,-");
+        let parsed = delivery::parse_mto(&damaged, date).unwrap();
+        assert_eq!((parsed.records.len(), parsed.rejected.len()), (4, 1));
+        assert_eq!(parsed.rejected[0].column, "Quantity Traded");
+
+        assert_eq!(delivery::mto_file_name(date), "MTO_15012025.DAT");
+        assert_eq!(delivery::identify_mto_file_name("mto_15012025.dat"), Some(date));
+        assert_eq!(delivery::identify_mto_file_name("MTO_15012025.csv"), None);
+        assert_eq!(local::identify_delivery_file(std::path::Path::new("archive/MTO_15012025.DAT")), Some(date));
+        assert_eq!(local::identify_from_file_name("MTO_15012025.DAT"), None);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_positions_join_price_bars() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mto_path = "/archives/equities/mto/MTO_15012025.DAT";
+        let server = mock_nse_server().await;
+        Mock::given(method("GET"))
+            .and(path(mto_path))
+            .respond_with(ResponseTemplate::new(200).set_body_string(include_str!("../tests/fixtures/MTO_15012025.DAT")))
+            .mount(&server)
+            .await;
+        let client = nse::NseClient::with_base_url(fast_http_config(), &server.uri());
+        let cache = temp_cache("delivery");
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let (raw, entry) = cache::fetch_delivery_with_cache(&client, &cache, date, cache::CacheMode::Prefer)
+            .await
+            .unwrap();
+        assert!(entry.url.ends_with(mto_path));
+        assert!(cache.load(delivery::DELIVERY_SOURCE, date).unwrap().is_some());
+        assert!(cache.load("nse", date).unwrap().is_none());
+        let bse = bse::BseClient::with_config(fast_http_config());
+        assert!(cache::fetch_delivery_with_cache(&bse, &cache, date, cache::CacheMode::Prefer).await.is_err());
+        let _ = std::fs::remove_dir_all(cache.root());
+
+        let db = memory_database().await;
+        let prices = nse::parse_csv_data(include_str!("../tests/fixtures/cm15JAN2025bhav.csv"), date).unwrap();
+        db.store_stock_records(&prices.records).await.unwrap();
+        let records = delivery::parse_mto(std::str::from_utf8(&raw.body).unwrap(), date).unwrap().records;
+        // GOLDBEES and SBIN's BL series have no stored prices
+        assert_eq!(db.store_delivery_records(&records).await.unwrap(), 3);
+        assert_eq!(db.store_delivery_records(&records).await.unwrap(), 3);
+
+        let bars = db.get_price_bars(&database::PriceFilter::default()).await.unwrap();
+        let tcs = bars.iter().find(|bar| bar.symbol == "TCS").unwrap();
+        assert_eq!((tcs.deliverable_qty, tcs.delivery_pct), (Some(162500.0), Some(65.0)));
+        assert!(db.get_delivery_dates("NSE", delivery::DELIVERY_SOURCE, &source::SeriesFilter::default(), date, date).await.unwrap().contains(&date));
+
+        // A day without a delivery file joins as empty, and a backfilled file
+        // under a former symbol finds the renamed listing
+        let next = date.succ_opt().unwrap();
+        db.store_stock_records(&nse_records(next, &[("INFOSYS", "INE009A01021", 1920.0)])).await.unwrap();
+        let infy = |bars: &[adjustment::PriceBar]| -> Vec<(NaiveDate, Option<f64>)> {
+            bars.iter().filter(|bar| bar.symbol == "INFOSYS").map(|bar| (bar.trade_date, bar.delivery_pct)).collect()
+        };
+        let bars = db.get_price_bars(&database::PriceFilter::default()).await.unwrap();
+        assert_eq!(infy(&bars), vec![(date, Some(52.84)), (next, None)]);
+
+        // Splits scale the deliverable quantity like the volume
+        db.upsert_corporate_actions(&manual_actions("symbol,ex_date,purpose\nTCS,2025-01-16,Face Value Split From Rs 10/- To Rs 5/-"))
+            .await
+            .unwrap();
+        db.refresh_adjustment_factors().await.unwrap();
+        let filter = database::PriceFilter { symbol: Some("TCS".to_string()), ..Default::default() };
+        let adjusted = db.get_adjusted_prices(&filter, adjustment::Adjustment::Split).await.unwrap();
+        assert_eq!((adjusted[0].deliverable_qty, adjusted[0].delivery_pct), (Some(325000.0), Some(65.0)));
+
+        // A rename with a new ISIN is stored as a new listing until the
+        // symbol change file merges it, delivery positions included
+        db.store_stock_records(&nse_records(next, &[("TCSNEW", "INE467B01037", 2070.0)])).await.unwrap();
+        let next_delivery = delivery::DeliveryRecord { symbol: "TCSNEW".to_string(), trade_date: next, ..records[4].clone() };
+        assert_eq!(db.store_delivery_records(&[next_delivery]).await.unwrap(), 1);
+        let changes = symbols::parse_symbol_changes_csv("Tata Consultancy,TCS,TCSNEW,16-JAN-2025", "NSE").unwrap();
+        assert_eq!(db.apply_symbol_changes(&changes).await.unwrap().merged, 1);
+        let bars = db.get_price_bars(&database::PriceFilter { symbol: Some("TCS".to_string()), ..Default::default() }).await.unwrap();
+        let delivery: Vec<_> = bars.iter().map(|bar| (bar.symbol.as_str(), bar.deliverable_qty)).collect();
+        assert_eq!(delivery, vec![("TCSNEW", Some(162500.0)), ("TCSNEW", Some(162500.0))]);
     }
 }
@@ -0,0 +1,173 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::delivery;
+use crate::source::{decode_bhavcopy_payload, parse_trade_date};
+
+/// A bhavcopy that was delivered as a file rather than downloaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalBhavcopy {
+    /// Registered source name, e.g. `nse`
+    pub source: &'static str,
+    pub trade_date: NaiveDate,
+}
+
+/// Expands `--file` arguments (paths or glob patterns) and `--dir`
+/// directories into the bhavcopy and delivery files they contain, sorted and
+/// deduplicated.
+pub fn find_bhavcopy_files(files: &[String], dirs: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+
+    for pattern in files {
+        if pattern.contains(['*', '?', '[']) {
+            let matches = glob::glob(pattern).with_context(|| format!("Invalid glob pattern '{}'", pattern))?;
+            let before = found.len();
+            for path in matches {
+                let path = path?;
+                if path.is_file() {
+                    found.push(path);
+                }
+            }
+            if found.len() == before {
+                return Err(anyhow!("No files match '{}'", pattern));
+            }
+        } else {
+            let path = PathBuf::from(pattern);
+            if !path.is_file() {
+                return Err(anyhow!("File not found: {}", path.display()));
+            }
+            found.push(path);
+        }
+    }
+
+    for dir in dirs {
+        if !dir.is_dir() {
+            return Err(anyhow!("Directory not found: {}", dir.display()));
+        }
+        collect_dir(dir, recursive, &mut found)?;
+    }
+
+    found.sort();
+    found.dedup();
+    Ok(found)
+}
+
+fn collect_dir(dir: &Path, recursive: bool, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_dir(&path, recursive, found)?;
+            }
+        } else if is_bhavcopy_extension(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_bhavcopy_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv") || ext.eq_ignore_ascii_case("zip"))
+        || identify_delivery_file(path).is_some()
+}
+
+/// The trade date of an NSE delivery file (`MTO_15012025.DAT`), or `None`
+/// if `path` is not one.
+pub fn identify_delivery_file(path: &Path) -> Option<NaiveDate> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(delivery::identify_mto_file_name)
+}
+
+/// Works out which exchange a file is from and its trade date, first from
+/// the file name and otherwise from the file's own date column.
+pub fn identify_bhavcopy(path: &Path, body: &[u8]) -> Result<LocalBhavcopy> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    if let Some(identified) = identify_from_file_name(file_name) {
+        return Ok(identified);
+    }
+
+    let content = decode_bhavcopy_payload(body, None)?;
+    identify_from_contents(&content)
+        .with_context(|| format!("Cannot tell the exchange and trade date of {}", path.display()))
+}
+
+/// Recognises the names the exchanges publish files under:
+/// `cm15JAN2025bhav.csv`, `BhavCopy_NSE_CM_0_0_0_20250115_F_0000.csv`,
+/// `EQ150125_CSV.ZIP` and `BhavCopy_BSE_CM_0_0_0_20250115_F_0000.CSV`,
+/// with or without a `.zip` wrapper.
+pub fn identify_from_file_name(file_name: &str) -> Option<LocalBhavcopy> {
+    let mut stem = file_name.to_ascii_lowercase();
+    while let Some(stripped) = stem.strip_suffix(".zip").or_else(|| stem.strip_suffix(".csv")) {
+        stem = stripped.to_string();
+    }
+
+    if let Some(date) = stem.strip_prefix("cm").and_then(|rest| rest.strip_suffix("bhav")) {
+        let trade_date = NaiveDate::parse_from_str(date, "%d%b%Y").ok()?;
+        return Some(LocalBhavcopy { source: "nse", trade_date });
+    }
+
+    for (prefix, source) in [("bhavcopy_nse_cm_", "nse"), ("bhavcopy_bse_cm_", "bse")] {
+        if let Some(rest) = stem.strip_prefix(prefix) {
+            let trade_date = rest
+                .split('_')
+                .filter(|part| part.len() == 8)
+                .find_map(|part| NaiveDate::parse_from_str(part, "%Y%m%d").ok())?;
+            return Some(LocalBhavcopy { source, trade_date });
+        }
+    }
+
+    let date = stem.strip_prefix("eq")?;
+    let date = date.strip_suffix("_csv").unwrap_or(date);
+    let trade_date = NaiveDate::parse_from_str(date, "%d%m%y").ok()?;
+    Some(LocalBhavcopy { source: "bse", trade_date })
+}
+
+/// Reads the exchange and trade date from the first data row: `TradDt` and
+/// `Src` in UDiFF files, `TIMESTAMP` in legacy NSE files and `TRADING_DATE`
+/// in legacy BSE files.
+pub fn identify_from_contents(content: &str) -> Result<LocalBhavcopy> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(Cursor::new(content));
+    let headers = reader.headers()?.clone();
+    let row = reader
+        .records()
+        .next()
+        .ok_or_else(|| anyhow!("File has no data rows"))??;
+    let value = |column: &str| {
+        headers
+            .iter()
+            .position(|header| header == column)
+            .and_then(|index| row.get(index))
+            .filter(|value| !value.is_empty())
+    };
+
+    let (source, raw_date) = if let Some(date) = value("TradDt") {
+        let source = match value("Src") {
+            Some(src) if src.eq_ignore_ascii_case("BSE") => "bse",
+            Some(src) if src.eq_ignore_ascii_case("NSE") => "nse",
+            other => return Err(anyhow!("Unknown UDiFF source {:?}", other)),
+        };
+        (source, date)
+    } else if let Some(date) = value("TIMESTAMP") {
+        ("nse", date)
+    } else if let Some(date) = value("TRADING_DATE") {
+        ("bse", date)
+    } else {
+        return Err(anyhow!("No TradDt, TIMESTAMP or TRADING_DATE column"));
+    };
+
+    let trade_date = parse_trade_date(raw_date)
+        .ok_or_else(|| anyhow!("Invalid trade date '{}'", raw_date))?;
+    Ok(LocalBhavcopy { source, trade_date })
+}
@@ -1,13 +1,29 @@
-use clap::{Parser, Subcommand};
-use chrono::NaiveDate;
-use anyhow::Result;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use anyhow::{Context, Result, anyhow};
 use sqlx::Row;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-mod database;
-mod nse;
-
-use database::{Database, IngestionLogInsert};
-use nse::{NseClient, parse_csv_data};
+use market_data::adjustment::{self, Adjustment};
+use market_data::cache::{self, CacheMode, RawFileCache};
+use market_data::calendar::{self, TradingCalendar};
+use market_data::corporate_actions::{self, ActionKind};
+use market_data::companies::{self, ListingStatus};
+use market_data::delivery::{self, ParsedDelivery};
+use market_data::database::{CompanyFilter, CorporateActionFilter, Database, IngestionLogInsert, PriceFilter, QualityIssueFilter};
+use market_data::http::{self, HttpConfig};
+use market_data::local;
+use market_data::reconcile::{self, BreakCause};
+use market_data::source::{self, MarketDataSource, ParsedBhavcopy, RawFile, SeriesFilter};
+use market_data::symbols;
+use market_data::validation::{self, Action, QualityIssue, Rule, Severity, ValidationConfig};
 use tracing::{info, warn, error};
 
 #[derive(Parser)]
@@ -15,38 +31,502 @@ use tracing::{info, warn, error};
 #[command(about = "A CLI tool for ingesting stock market data")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Database file path or sqlite:// URL. Falls back to DATABASE_URL, then ./market_data.db
+    #[arg(long, global = true, env = "MARKET_DATA_DB")]
+    db_path: Option<String>,
+    
+    /// Directory holding the archive of downloaded files
+    #[arg(long, global = true, env = "MARKET_DATA_CACHE_DIR", default_value = "./bhavcopy_cache")]
+    cache_dir: PathBuf,
+    
     #[command(subcommand)]
     command: Commands,
 }
 
+impl Cli {
+    fn database_location(&self) -> String {
+        self.db_path
+            .clone()
+            .or_else(|| std::env::var("DATABASE_URL").ok())
+            .unwrap_or_else(|| "./market_data.db".to_string())
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Download and ingest stock market data
-    Ingest {
-        /// Data source to use
-        #[arg(long, default_value = "nse")]
-        source: String,
+    Ingest(IngestArgs),
+    /// Rebuild stored prices from the archive of downloaded files
+    Reparse(ReparseArgs),
+    /// Show ingestion status and logs
+    Status,
+    /// Initialize the database
+    InitDb,
+    /// Manage the database schema
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Manage exchange holidays and special sessions
+    Holidays {
+        #[command(subcommand)]
+        command: HolidayCommands,
+    },
+    /// List data quality issues found during ingestion
+    Quality(QualityArgs),
+    /// Check each day's PREVCLOSE against the stored close of the day before
+    Reconcile(ReconcileArgs),
+    /// Query or export daily prices, raw or adjusted for corporate actions
+    Prices(PricesArgs),
+    /// Manage splits, bonuses, dividends and rights issues
+    CorporateActions {
+        #[command(subcommand)]
+        command: CorporateActionCommands,
+    },
+    /// Track symbol changes and look listings up by any symbol they have had
+    Symbols {
+        #[command(subcommand)]
+        command: SymbolCommands,
+    },
+    /// Import and search company names, classification and listing status
+    Companies {
+        #[command(subcommand)]
+        command: CompanyCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Apply any pending schema migrations
+    Migrate,
+    /// Show the current and latest schema versions
+    Version,
+}
+
+#[derive(Subcommand)]
+enum HolidayCommands {
+    /// Import holidays from a CSV or JSON file (date, kind, description, exchange)
+    Import {
+        /// Path to a .csv or .json file
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Exchange for entries that do not name one
+        #[arg(long, default_value = "NSE")]
+        exchange: String,
+    },
+    /// List the holidays and special sessions in a year
+    List {
+        #[arg(long, default_value = "NSE")]
+        exchange: String,
+
+        /// Calendar year. Defaults to the current year in IST
+        #[arg(long)]
+        year: Option<i32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CorporateActionCommands {
+    /// Import NSE's corporate actions CSV, or a CSV or JSON file in the manual format
+    Import {
+        /// Path to a .csv or .json file
+        #[arg(long)]
+        file: PathBuf,
         
-        /// Specific date to download (YYYY-MM-DD format)
+        /// Exchange for entries that do not name one
+        #[arg(long, default_value = "NSE")]
+        exchange: String,
+    },
+    /// List stored corporate actions
+    List {
+        /// Actions on this symbol, or on any listing sharing its ISIN
         #[arg(long)]
-        date: Option<String>,
+        symbol: Option<String>,
+        
+        #[arg(long)]
+        isin: Option<String>,
         
-        /// Start date for range download (YYYY-MM-DD format)
+        /// split, bonus, dividend, rights or other
+        #[arg(long)]
+        kind: Option<ActionKind>,
+        
+        /// First ex-date to list (YYYY-MM-DD format)
         #[arg(long)]
         from: Option<String>,
         
-        /// End date for range download (YYYY-MM-DD format)
+        /// Last ex-date to list (YYYY-MM-DD format)
         #[arg(long)]
         to: Option<String>,
     },
-    /// Show ingestion status and logs
-    Status,
-    /// Initialize the database
-    InitDb {
-        /// Database file path
-        #[arg(long, default_value = "./market_data.db")]
-        db_path: String,
+}
+
+#[derive(Subcommand)]
+enum SymbolCommands {
+    /// Import NSE's symbol change file (symbolchange.csv)
+    Import {
+        /// Path to the .csv file
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Exchange for entries that do not name one
+        #[arg(long, default_value = "NSE")]
+        exchange: String,
+    },
+    /// Show every symbol and ISIN of the listings that have traded under a symbol
+    History {
+        /// Current or former symbol
+        #[arg(long)]
+        symbol: String,
+
+        #[arg(long)]
+        exchange: Option<String>,
+
+        /// Only listings that had the symbol on this date (YYYY-MM-DD format)
+        #[arg(long)]
+        date: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CompanyCommands {
+    /// Import NSE's equity list (EQUITY_L.csv) or a sector and industry file
+    Import {
+        /// Path to the .csv file
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Date the equity list was downloaded (YYYY-MM-DD format); defaults to today in IST
+        #[arg(long)]
+        date: Option<String>,
+    },
+    /// Flag the listings in a CSV of symbols as suspended or delisted
+    Flag {
+        /// Path to a .csv file with a symbol column and optional isin and date columns
+        #[arg(long)]
+        file: PathBuf,
+
+        /// suspended, delisted or active
+        #[arg(long)]
+        status: ListingStatus,
+
+        /// Exchange for entries that do not name one
+        #[arg(long, default_value = "NSE")]
+        exchange: String,
+    },
+    /// Search listings by name, current or former symbol, or ISIN
+    Search {
+        /// Part of a name or symbol, or an ISIN
+        #[arg(long)]
+        query: Option<String>,
+
+        /// Part of the sector name
+        #[arg(long)]
+        sector: Option<String>,
+
+        /// Part of the industry name
+        #[arg(long)]
+        industry: Option<String>,
+
+        /// active, suspended or delisted
+        #[arg(long)]
+        status: Option<ListingStatus>,
+
+        #[arg(long)]
+        exchange: Option<String>,
+
+        /// Maximum number of listings to show
+        #[arg(long, default_value = "50")]
+        limit: i64,
     },
+    /// Show the master data of the listings with a symbol or ISIN
+    Show {
+        /// Current or former symbol
+        #[arg(long, required_unless_present = "isin")]
+        symbol: Option<String>,
+
+        #[arg(long)]
+        isin: Option<String>,
+
+        #[arg(long)]
+        exchange: Option<String>,
+    },
+}
+
+/// Options that only apply to downloads, rejected with --file and --dir
+/// rather than silently ignored.
+const LOCAL_FILE_CONFLICTS: [&str; 7] = ["date", "from", "to", "backfill", "offline", "refetch", "delivery"];
+
+/// Download options that can also be set from the environment. clap would
+/// count an environment value as given, so these are only rejected with
+/// --file and --dir when passed on the command line.
+const LOCAL_FILE_COMMAND_LINE_CONFLICTS: [&str; 3] = ["concurrency", "rate_limit", "publish_cutoff"];
+
+#[derive(Args)]
+struct IngestArgs {
+    /// Data source to use (nse, bse)
+    #[arg(long, default_value = "nse")]
+    source: String,
+    
+    /// Specific date to download (YYYY-MM-DD format)
+    #[arg(long)]
+    date: Option<String>,
+    
+    /// Start date for range download (YYYY-MM-DD format)
+    #[arg(long)]
+    from: Option<String>,
+    
+    /// End date for range download (YYYY-MM-DD format)
+    #[arg(long)]
+    to: Option<String>,
+    
+    /// Ingest bhavcopy files from disk instead of downloading (paths or glob patterns)
+    #[arg(long, num_args = 1.., conflicts_with_all = LOCAL_FILE_CONFLICTS)]
+    file: Vec<String>,
+    
+    /// Ingest every .csv and .zip bhavcopy, and MTO_DDMMYYYY.DAT delivery file, in these directories
+    #[arg(long, num_args = 1.., conflicts_with_all = LOCAL_FILE_CONFLICTS)]
+    dir: Vec<PathBuf>,
+    
+    /// With --dir, also search subdirectories
+    #[arg(long, requires = "dir")]
+    recursive: bool,
+    
+    /// Fetch every trading day from --since (to --to, or the latest published day) not stored yet for the --series selected
+    #[arg(long, requires = "since", conflicts_with_all = ["date", "from"])]
+    backfill: bool,
+    
+    /// First date to backfill (YYYY-MM-DD format)
+    #[arg(long, requires = "backfill")]
+    since: Option<String>,
+    
+    /// With --backfill, also re-ingest dates that are already stored
+    #[arg(long, requires = "backfill")]
+    force: bool,
+    
+    /// Only keep these series, comma separated (e.g. EQ,BE). Defaults to all series
+    #[arg(long, value_delimiter = ',')]
+    series: Vec<String>,
+    
+    /// Also ingest NSE's delivery position file (MTO_DDMMYYYY.DAT) for each date
+    #[arg(long)]
+    delivery: bool,
+    
+    /// Time of day (HH:MM, IST) after which today's bhavcopy is treated as published
+    #[arg(long, env = "MARKET_DATA_PUBLISH_CUTOFF", default_value = "18:30", value_parser = parse_cutoff)]
+    publish_cutoff: NaiveTime,
+    
+    /// Only use files already in the cache directory; never download
+    #[arg(long, conflicts_with = "refetch")]
+    offline: bool,
+    
+    /// Download files again even if they are already cached
+    #[arg(long)]
+    refetch: bool,
+    
+    /// Seconds allowed for each download, including reading the body
+    #[arg(long, env = "MARKET_DATA_TIMEOUT", default_value_t = 60)]
+    timeout: u64,
+    
+    /// Seconds allowed to establish a connection
+    #[arg(long, env = "MARKET_DATA_CONNECT_TIMEOUT", default_value_t = 10)]
+    connect_timeout: u64,
+    
+    /// Retries for timeouts, connection resets, 401/403, 429 and 5xx responses
+    #[arg(long, env = "MARKET_DATA_RETRIES", default_value_t = 4)]
+    retries: u32,
+    
+    /// Number of dates downloaded in parallel during a backfill
+    #[arg(long, env = "MARKET_DATA_CONCURRENCY", default_value_t = 4)]
+    concurrency: usize,
+    
+    /// Maximum HTTP requests per second across all downloads (0 for no limit)
+    #[arg(long, env = "MARKET_DATA_RATE_LIMIT", default_value_t = 2.0)]
+    rate_limit: f64,
+    
+    #[command(flatten)]
+    validation: ValidationArgs,
+}
+
+#[derive(Args)]
+struct ReparseArgs {
+    /// Data source whose archived files are parsed (nse, bse)
+    #[arg(long, default_value = "nse")]
+    source: String,
+    
+    /// First date to reparse (YYYY-MM-DD format). Defaults to the oldest archived file
+    #[arg(long)]
+    from: Option<String>,
+    
+    /// Last date to reparse (YYYY-MM-DD format). Defaults to the newest archived file
+    #[arg(long)]
+    to: Option<String>,
+    
+    /// Only keep these series, comma separated (e.g. EQ,BE). Defaults to all series
+    #[arg(long, value_delimiter = ',')]
+    series: Vec<String>,
+    
+    #[command(flatten)]
+    validation: ValidationArgs,
+}
+
+#[derive(Args)]
+struct ValidationArgs {
+    /// Override a validation rule, e.g. close_change=reject or price_range=warn.
+    /// Rules: positive_prices, price_range, non_negative_volume, close_change
+    #[arg(long = "rule", env = "MARKET_DATA_RULES", value_delimiter = ',', value_parser = validation::parse_rule_setting)]
+    rules: Vec<(Rule, Action)>,
+    
+    /// Largest move from the previous close, in percent, before close_change fires
+    #[arg(long, env = "MARKET_DATA_MAX_CLOSE_CHANGE", default_value_t = 20.0)]
+    max_close_change: f64,
+}
+
+impl ValidationArgs {
+    fn config(&self) -> ValidationConfig {
+        let mut config = ValidationConfig::default();
+        config.max_close_change_pct = self.max_close_change;
+        for (rule, action) in &self.rules {
+            config.set_action(*rule, *action);
+        }
+        config
+    }
+}
+
+#[derive(Args)]
+struct QualityArgs {
+    /// Only issues on this trade date (YYYY-MM-DD format)
+    #[arg(long, conflicts_with_all = ["from", "to"])]
+    date: Option<String>,
+    
+    /// First trade date to list (YYYY-MM-DD format)
+    #[arg(long)]
+    from: Option<String>,
+    
+    /// Last trade date to list (YYYY-MM-DD format)
+    #[arg(long)]
+    to: Option<String>,
+    
+    /// Only issues for this symbol
+    #[arg(long)]
+    symbol: Option<String>,
+    
+    /// Only issues on this exchange (NSE, BSE)
+    #[arg(long)]
+    exchange: Option<String>,
+    
+    /// Only issues raised by this rule
+    #[arg(long)]
+    rule: Option<Rule>,
+    
+    /// Only warnings or only rejections
+    #[arg(long)]
+    severity: Option<Severity>,
+    
+    /// Maximum number of issues to show
+    #[arg(long, default_value_t = 50)]
+    limit: i64,
+}
+
+#[derive(Args)]
+struct ReconcileArgs {
+    /// Only this exchange (NSE, BSE). Defaults to every exchange
+    #[arg(long)]
+    exchange: Option<String>,
+    
+    /// Only this symbol
+    #[arg(long)]
+    symbol: Option<String>,
+    
+    /// First trade date to check (YYYY-MM-DD format); it is only used as the baseline
+    #[arg(long)]
+    from: Option<String>,
+    
+    /// Last trade date to check (YYYY-MM-DD format)
+    #[arg(long)]
+    to: Option<String>,
+    
+    /// Largest difference, in rupees, still treated as a match
+    #[arg(long, default_value_t = 0.01)]
+    tolerance: f64,
+    
+    /// Report format
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    format: ReportFormat,
+    
+    /// Write the report to this file instead of stdout (csv and json only)
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PricesArgs {
+    /// Only this symbol
+    #[arg(long)]
+    symbol: Option<String>,
+    
+    /// Only this exchange (NSE, BSE)
+    #[arg(long)]
+    exchange: Option<String>,
+    
+    /// Only this series (e.g. EQ)
+    #[arg(long)]
+    series: Option<String>,
+    
+    /// First trade date (YYYY-MM-DD format)
+    #[arg(long)]
+    from: Option<String>,
+    
+    /// Last trade date (YYYY-MM-DD format)
+    #[arg(long)]
+    to: Option<String>,
+    
+    /// raw, split (split and bonus adjusted) or total-return (also dividend adjusted)
+    #[arg(long, default_value = "raw")]
+    adjust: Adjustment,
+    
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    format: ReportFormat,
+    
+    /// Write the prices to this file instead of stdout (csv and json only)
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl IngestArgs {
+    fn cache_mode(&self) -> CacheMode {
+        if self.offline {
+            CacheMode::Offline
+        } else if self.refetch {
+            CacheMode::Refresh
+        } else {
+            CacheMode::Prefer
+        }
+    }
+    
+    fn http_config(&self) -> HttpConfig {
+        HttpConfig {
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            request_timeout: Duration::from_secs(self.timeout),
+            max_retries: self.retries,
+            requests_per_second: (self.rate_limit > 0.0).then_some(self.rate_limit),
+            ..HttpConfig::default()
+        }
+    }
+}
+
+fn parse_cutoff(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|_| anyhow!("Invalid cutoff '{}', expected HH:MM", value))
 }
 
 #[tokio::main]
@@ -54,100 +534,608 @@ async fn main() -> Result<()> {
     // Initialize tracing/logging
     tracing_subscriber::fmt::init();
     
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    if let Some(("ingest", ingest)) = matches.subcommand() {
+        reject_download_options_for_files(ingest);
+    }
+    if let Some(("reconcile" | "prices", report)) = matches.subcommand() {
+        reject_output_for_table(report);
+    }
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let db_location = cli.database_location();
+    let cache = RawFileCache::new(&cli.cache_dir);
     
     match cli.command {
-        Commands::Ingest { source, date, from, to } => {
-            handle_ingest(source, date, from, to).await?
+        Commands::Ingest(args) => {
+            handle_ingest(&db_location, &cache, args).await?
+        },
+        Commands::Reparse(args) => {
+            handle_reparse(&db_location, &cache, args).await?
         },
         Commands::Status => {
-            handle_status().await?
+            handle_status(&db_location).await?
+        },
+        Commands::InitDb => {
+            handle_init_db(&db_location).await?
+        },
+        Commands::Db { command: DbCommands::Migrate } => {
+            handle_db_migrate(&db_location).await?
+        },
+        Commands::Db { command: DbCommands::Version } => {
+            handle_db_version(&db_location).await?
+        },
+        Commands::Holidays { command: HolidayCommands::Import { file, exchange } } => {
+            handle_holidays_import(&db_location, &file, &exchange).await?
+        },
+        Commands::Holidays { command: HolidayCommands::List { exchange, year } } => {
+            handle_holidays_list(&db_location, &exchange, year).await?
+        },
+        Commands::Quality(args) => {
+            handle_quality(&db_location, args).await?
+        },
+        Commands::Reconcile(args) => {
+            handle_reconcile(&db_location, args).await?
+        },
+        Commands::Prices(args) => {
+            handle_prices(&db_location, args).await?
+        },
+        Commands::CorporateActions { command: CorporateActionCommands::Import { file, exchange } } => {
+            handle_corporate_actions_import(&db_location, &file, &exchange).await?
+        },
+        Commands::CorporateActions { command: CorporateActionCommands::List { symbol, isin, kind, from, to } } => {
+            let parse_date = |value: Option<String>| {
+                value.map(|value| NaiveDate::parse_from_str(&value, "%Y-%m-%d")).transpose()
+            };
+            let filter = CorporateActionFilter {
+                symbol,
+                isin,
+                kind,
+                from: parse_date(from)?,
+                to: parse_date(to)?,
+            };
+            handle_corporate_actions_list(&db_location, &filter).await?
+        },
+        Commands::Symbols { command: SymbolCommands::Import { file, exchange } } => {
+            handle_symbols_import(&db_location, &file, &exchange).await?
         },
-        Commands::InitDb { db_path } => {
-            handle_init_db(db_path).await?
+        Commands::Symbols { command: SymbolCommands::History { symbol, exchange, date } } => {
+            let date = date
+                .map(|value| NaiveDate::parse_from_str(&value, "%Y-%m-%d"))
+                .transpose()?;
+            handle_symbols_history(&db_location, &symbol, exchange.as_deref(), date).await?
+        },
+        Commands::Companies { command: CompanyCommands::Import { file, date } } => {
+            let date = date
+                .map(|value| NaiveDate::parse_from_str(&value, "%Y-%m-%d"))
+                .transpose()?;
+            handle_companies_import(&db_location, &file, date).await?
+        },
+        Commands::Companies { command: CompanyCommands::Flag { file, status, exchange } } => {
+            handle_companies_flag(&db_location, &file, status, &exchange).await?
+        },
+        Commands::Companies { command: CompanyCommands::Search { query, sector, industry, status, exchange, limit } } => {
+            let filter = CompanyFilter {
+                text: query,
+                sector,
+                industry,
+                status,
+                exchange,
+                limit: Some(limit),
+                ..Default::default()
+            };
+            handle_companies_search(&db_location, &filter).await?
+        },
+        Commands::Companies { command: CompanyCommands::Show { symbol, isin, exchange } } => {
+            let filter = CompanyFilter { symbol, isin, exchange, ..Default::default() };
+            handle_companies_show(&db_location, &filter).await?
         },
     }
     
     Ok(())
 }
 
-async fn handle_ingest(source: String, date: Option<String>, from: Option<String>, to: Option<String>) -> Result<()> {
+/// Exits with a usage error if a download-only option from the command line
+/// is combined with --file or --dir.
+fn reject_download_options_for_files(matches: &ArgMatches) {
+    let Some(local) = ["file", "dir"].into_iter().find(|id| matches.contains_id(id)) else {
+        return;
+    };
+    for id in LOCAL_FILE_COMMAND_LINE_CONFLICTS {
+        if matches.value_source(id) == Some(ValueSource::CommandLine) {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the argument '--{}' cannot be used with '--{}'", local, id.replace('_', "-")),
+                )
+                .exit();
+        }
+    }
+}
+
+/// Exits with a usage error if --output is given while the format is still
+/// the table, which only prints to the terminal.
+fn reject_output_for_table(matches: &ArgMatches) {
+    if matches.contains_id("output") && matches!(matches.get_one("format"), Some(ReportFormat::Table)) {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the argument '--output' requires '--format csv' or '--format json'",
+            )
+            .exit();
+    }
+}
+
+async fn handle_ingest(db_location: &str, cache: &RawFileCache, args: IngestArgs) -> Result<()> {
+    if !args.file.is_empty() || !args.dir.is_empty() {
+        return handle_ingest_files(db_location, cache, args).await;
+    }
+    
+    let http_config = args.http_config();
+    let cache_mode = args.cache_mode();
+    let validation = args.validation.config();
+    let IngestArgs { source, date, from, to, backfill, since, force, series, delivery, publish_cutoff, concurrency, .. } = args;
     info!("Starting data ingestion from source: {}", source);
     
-    if source != "nse" {
-        return Err(anyhow::anyhow!("Currently only 'nse' source is supported"));
+    let mut source = source::create_source_with_config(&source, http_config)?;
+    if delivery && source.delivery_source().is_none() {
+        return Err(anyhow!("{} does not publish delivery data; --delivery is only available for nse", source.name()));
     }
+    let series_filter = SeriesFilter::new(&series);
     
     // Connect to database
-    let db = Database::new("sqlite://market_data.db").await?;
-    let nse_client = NseClient::new();
+    let db = Database::new(db_location).await?;
+    source.set_calendar(db.trading_calendar(source.exchange()).await?);
     
     // Determine which dates to process
-    let dates_to_process = if let Some(date_str) = date {
+    let backfill_range = if backfill {
+        let since = NaiveDate::parse_from_str(since.as_deref().unwrap_or_default(), "%Y-%m-%d")?;
+        let until = match &to {
+            Some(to_str) => NaiveDate::parse_from_str(to_str, "%Y-%m-%d")?,
+            None => latest_published_date(source.as_ref(), publish_cutoff),
+        };
+        Some((since, until))
+    } else {
+        None
+    };
+    let dates_to_process = if let Some((since, until)) = backfill_range {
+        backfill_dates(&db, source.as_ref(), since, until, &series_filter, force, false).await?
+    } else if let Some(date_str) = date {
         if date_str == "today" {
-            let today = nse_client.get_latest_trading_date();
-            vec![today]
+            vec![latest_published_date(source.as_ref(), publish_cutoff)]
         } else {
             let parsed_date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")?;
+            if !source.calendar().is_trading_day(parsed_date) {
+                let reason = source.calendar().holiday(parsed_date).unwrap_or("weekend");
+                println!("⚠️  {} is closed on {} ({}), nothing to ingest", source.exchange(), parsed_date, reason);
+                return Ok(());
+            }
             vec![parsed_date]
         }
     } else if let (Some(from_str), Some(to_str)) = (from, to) {
         let from_date = NaiveDate::parse_from_str(&from_str, "%Y-%m-%d")?;
         let to_date = NaiveDate::parse_from_str(&to_str, "%Y-%m-%d")?;
-        nse_client.get_trading_dates_in_range(from_date, to_date)
+        source.trading_dates_in_range(from_date, to_date)
     } else {
-        let today = nse_client.get_latest_trading_date();
-        vec![today]
+        vec![latest_published_date(source.as_ref(), publish_cutoff)]
     };
     
+    if let (Some(first), Some(last)) = (dates_to_process.first(), dates_to_process.last()) {
+        warn_uncovered_years(source.calendar(), source.exchange(), *first, *last);
+    }
     info!("Processing {} dates", dates_to_process.len());
     
-    for date in dates_to_process {
-        match ingest_single_date(&db, &nse_client, date).await {
+    // Delivery files are stored after the prices, so that every record
+    // finds the listing its bhavcopy created
+    let delivery_dates = match (delivery, backfill_range) {
+        (false, _) => Vec::new(),
+        (true, Some((since, until))) => backfill_dates(&db, source.as_ref(), since, until, &series_filter, force, true).await?,
+        (true, None) => dates_to_process.clone(),
+    };
+    
+    let progress = (dates_to_process.len() > 1).then(|| backfill_progress_bar(dates_to_process.len()));
+    let source = source.as_ref();
+    
+    // Downloads run `concurrency` at a time and come back in date order;
+    // SQLite has a single writer, so they are stored one date at a time
+    let mut downloads = stream::iter(dates_to_process)
+        .map(|date| fetch_date(source, cache, cache_mode, date, progress.as_ref()))
+        .buffered(concurrency.max(1));
+    
+    let mut records_stored = 0;
+    while let Some(fetched) = downloads.next().await {
+        let date = fetched.date;
+        match store_date(&db, source, &series_filter, &validation, fetched, false, progress.as_ref()).await {
             Ok(count) => {
-                println!("✅ Successfully ingested {} records for {}", count, date);
+                records_stored += count;
+                report(progress.as_ref(), format!("✅ Successfully ingested {} records for {}", count, date));
+            },
+            Err(e) if http::is_not_published(&e) => {
+                warn!("No bhavcopy for {}: {}", date, e);
+                report(progress.as_ref(), format!("⚠️  No bhavcopy published for {} (holiday, or not out yet)", date));
             },
             Err(e) => {
                 error!("Failed to ingest data for {}: {}", date, e);
-                println!("❌ Failed to ingest data for {}: {}", date, e);
+                report(progress.as_ref(), format!("❌ Failed to ingest data for {}: {}", date, e));
             }
         }
+        
+        if let Some(progress) = &progress {
+            progress.inc(1);
+            progress.set_message(format!("{} records stored", records_stored));
+        }
+    }
+    
+    if let Some(progress) = &progress {
+        progress.finish_with_message(format!("{} records stored", records_stored));
+    }
+    
+    if !delivery_dates.is_empty() {
+        ingest_delivery(&db, source, cache, cache_mode, &series_filter, delivery_dates, concurrency).await?;
     }
     
     Ok(())
 }
 
-async fn ingest_single_date(db: &Database, nse_client: &NseClient, date: NaiveDate) -> Result<usize> {
+/// Trading days between `since` and `until` that still need ingesting for
+/// the series `series_filter` selects, or with `delivery`, that still need
+/// their delivery file. Every date is
+/// committed and logged on its own, so an interrupted backfill resumes
+/// where it stopped.
+async fn backfill_dates(
+    db: &Database,
+    source: &dyn MarketDataSource,
+    since: NaiveDate,
+    until: NaiveDate,
+    series_filter: &SeriesFilter,
+    force: bool,
+    delivery: bool,
+) -> Result<Vec<NaiveDate>> {
+    let trading_dates = source.trading_dates_in_range(since, until);
+    if force {
+        let what = if delivery { "delivery files for " } else { "" };
+        println!("🔁 Re-ingesting {}all {} trading days from {} to {}", what, trading_dates.len(), since, until);
+        return Ok(trading_dates);
+    }
+    
+    let ingested = match source.delivery_source() {
+        Some(delivery_source) if delivery => db.get_delivery_dates(source.exchange(), delivery_source, series_filter, since, until).await?,
+        _ => db.get_ingested_dates(source.exchange(), source.name(), series_filter, since, until).await?,
+    };
+    let missing: Vec<NaiveDate> = trading_dates
+        .iter()
+        .copied()
+        .filter(|date| !ingested.contains(date))
+        .collect();
+    
+    println!(
+        "🔍 {} of {} trading days from {} to {} are missing{}",
+        missing.len(),
+        trading_dates.len(),
+        since,
+        until,
+        if delivery { " delivery files" } else { "" }
+    );
+    Ok(missing)
+}
+
+/// Downloads and stores the delivery file of each date, `concurrency` at a
+/// time like the bhavcopies.
+async fn ingest_delivery(
+    db: &Database,
+    source: &dyn MarketDataSource,
+    cache: &RawFileCache,
+    cache_mode: CacheMode,
+    series_filter: &SeriesFilter,
+    dates: Vec<NaiveDate>,
+    concurrency: usize,
+) -> Result<()> {
+    let delivery_source = source
+        .delivery_source()
+        .ok_or_else(|| anyhow!("{} does not publish delivery data", source.name()))?;
+    let progress = (dates.len() > 1).then(|| backfill_progress_bar(dates.len()));
+    
+    let mut downloads = stream::iter(dates)
+        .map(|date| fetch_delivery_date(source, cache, cache_mode, date, progress.as_ref()))
+        .buffered(concurrency.max(1));
+    
+    let mut records_stored = 0;
+    while let Some(fetched) = downloads.next().await {
+        let date = fetched.date;
+        match store_delivery_date(db, delivery_source, series_filter, fetched, progress.as_ref()).await {
+            Ok(count) => {
+                records_stored += count;
+                report(progress.as_ref(), format!("✅ Stored {} delivery positions for {}", count, date));
+            },
+            Err(e) if http::is_not_published(&e) => {
+                warn!("No delivery file for {}: {}", date, e);
+                report(progress.as_ref(), format!("⚠️  No delivery file published for {} (holiday, or not out yet)", date));
+            },
+            Err(e) => {
+                error!("Failed to ingest delivery data for {}: {}", date, e);
+                report(progress.as_ref(), format!("❌ Failed to ingest delivery data for {}: {}", date, e));
+            }
+        }
+        
+        if let Some(progress) = &progress {
+            progress.inc(1);
+            progress.set_message(format!("{} delivery records stored", records_stored));
+        }
+    }
+    
+    if let Some(progress) = &progress {
+        progress.finish_with_message(format!("{} delivery records stored", records_stored));
+    }
+    
+    Ok(())
+}
+
+/// Warns that years without a holiday list are treated as weekends-only, so
+/// their holidays show up as failed downloads or missing dates.
+fn warn_uncovered_years(calendar: &TradingCalendar, exchange: &str, from: NaiveDate, to: NaiveDate) {
+    let years = calendar.uncovered_years(from, to);
+    if !years.is_empty() {
+        let years: Vec<String> = years.iter().map(|year| year.to_string()).collect();
+        println!(
+            "⚠️  No {} holiday list for {}; only weekends are treated as closed. Add one with 'market-data holidays import'",
+            exchange,
+            years.join(", ")
+        );
+    }
+}
+
+/// The latest date whose bhavcopy should be out, falling back to the
+/// previous trading day before the publication cutoff.
+fn latest_published_date(source: &dyn MarketDataSource, cutoff: NaiveTime) -> NaiveDate {
+    let now = calendar::exchange_now();
+    let date = source.latest_published_date(now, cutoff);
+    if date < now.date() && source.calendar().is_trading_day(now.date()) {
+        println!(
+            "ℹ️  {}'s bhavcopy is not published until {} IST; using {}",
+            now.date(),
+            cutoff.format("%H:%M"),
+            date
+        );
+    }
+    date
+}
+
+fn backfill_progress_bar(dates: usize) -> ProgressBar {
+    let progress = ProgressBar::new(dates as u64);
+    progress.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {pos}/{len} dates | {msg} | ETA {eta}")
+            .expect("valid progress template"),
+    );
+    progress.set_message("0 records stored");
+    progress
+}
+
+/// Prints a result line above the progress bar, if one is being drawn.
+fn report(progress: Option<&ProgressBar>, message: String) {
+    match progress {
+        // Not a terminal: the bar is hidden and would swallow the line
+        Some(progress) if !progress.is_hidden() => progress.println(message),
+        _ => println!("{}", message),
+    }
+}
+
+/// Prints a per-date step for a single date; during a backfill the progress
+/// bar stands in for them.
+fn report_step(progress: Option<&ProgressBar>, message: &str) {
+    match progress {
+        Some(_) => info!("{}", message),
+        None => println!("{}", message),
+    }
+}
+
+/// A date's download, before it is stored.
+struct FetchedDate<P = ParsedBhavcopy> {
+    date: NaiveDate,
+    started_at: chrono::NaiveDateTime,
+    file_name: Option<String>,
+    parsed: Result<P>,
+}
+
+async fn fetch_date(
+    source: &dyn MarketDataSource,
+    cache: &RawFileCache,
+    cache_mode: CacheMode,
+    date: NaiveDate,
+    progress: Option<&ProgressBar>,
+) -> FetchedDate {
     let started_at = chrono::Local::now().naive_local();
     info!("Starting ingestion for date: {}", date);
     
+    let mut file_name = None;
+    let parsed = async {
+        // Download bhavcopy
+        report_step(progress, &format!("📥 Downloading data for {}...", date));
+        let (raw, entry) = cache::fetch_with_cache(source, cache, date, cache_mode).await?;
+        file_name = Some(entry.log_file_name());
+        
+        // Parse CSV data
+        report_step(progress, "🔍 Parsing CSV data...");
+        let parsed = source.parse(&raw, date);
+        evict_if_stale(cache, source.name(), date, &parsed);
+        parsed
+    }.await;
+    
+    FetchedDate { date, started_at, file_name, parsed }
+}
+
+async fn fetch_delivery_date(
+    source: &dyn MarketDataSource,
+    cache: &RawFileCache,
+    cache_mode: CacheMode,
+    date: NaiveDate,
+    progress: Option<&ProgressBar>,
+) -> FetchedDate<ParsedDelivery> {
+    let started_at = chrono::Local::now().naive_local();
+    
+    let mut file_name = None;
+    let parsed = async {
+        report_step(progress, &format!("📥 Downloading delivery data for {}...", date));
+        let (raw, entry) = cache::fetch_delivery_with_cache(source, cache, date, cache_mode).await?;
+        file_name = Some(entry.log_file_name());
+        
+        let content = source::decode_bhavcopy_payload(&raw.body, raw.content_type.as_deref())?;
+        let parsed = delivery::parse_mto(&content, date);
+        if let Some(name) = source.delivery_source() {
+            evict_if_stale(cache, name, date, &parsed);
+        }
+        parsed
+    }.await;
+    
+    FetchedDate { date, started_at, file_name, parsed }
+}
+
+/// Drops a cached file that turned out to be for another trade date.
+fn evict_if_stale<T>(cache: &RawFileCache, source: &str, date: NaiveDate, parsed: &Result<T>) {
+    if let Err(e) = cache.evict_if_stale(source, date, parsed) {
+        warn!("Failed to evict stale {} file for {} from the cache: {}", source, date, e);
+    }
+}
+
+/// Stores and logs a fetched delivery file under `delivery_source`.
+/// Records whose listing has no stored prices count as rejected.
+async fn store_delivery_date(
+    db: &Database,
+    delivery_source: &str,
+    series_filter: &SeriesFilter,
+    fetched: FetchedDate<ParsedDelivery>,
+    progress: Option<&ProgressBar>,
+) -> Result<usize> {
+    let FetchedDate { date, started_at, file_name, parsed } = fetched;
+    
     let mut log = IngestionLogInsert {
-        source: "nse".to_string(),
-        file_name: None,
+        source: delivery_source.to_string(),
+        file_name,
         trade_date: Some(date),
         records_processed: Some(0),
+        records_rejected: Some(0),
         status: "failed".to_string(),
         error_message: None,
+        series_filter: series_filter.to_log_value(),
         started_at: Some(started_at),
         completed_at: chrono::Local::now().naive_local(),
     };
     
     let result = async {
-        // Download bhavcopy
-        println!("📥 Downloading data for {}...", date);
-        let csv_content = nse_client.download_bhavcopy(date).await?;
+        let mut parsed = parsed?;
+        parsed.records.retain(|record| series_filter.matches(&record.series));
         
-        // Parse CSV data
-        println!("🔍 Parsing CSV data...");
-        let records = parse_csv_data(&csv_content, date)?;
+        if !parsed.rejected.is_empty() {
+            let mut message = format!("⚠️  Rejected {} malformed delivery rows for {}:", parsed.rejected.len(), date);
+            for rejected in parsed.rejected.iter().take(10) {
+                message.push_str(&format!("\n   {}", rejected));
+            }
+            report(progress, message);
+        }
+        if parsed.records.is_empty() {
+            return Err(anyhow!("No delivery records found"));
+        }
         
-        if records.is_empty() {
+        report_step(progress, &format!("💾 Storing {} delivery records in database...", parsed.records.len()));
+        let stored = db.store_delivery_records(&parsed.records).await?;
+        let unmatched = parsed.records.len() - stored;
+        if unmatched > 0 {
+            report(
+                progress,
+                format!("⚠️  {} delivery records for {} have no stored prices; ingest the bhavcopy first", unmatched, date),
+            );
+        }
+        log.records_rejected = Some((parsed.rejected.len() + unmatched) as i64);
+        
+        Ok(stored)
+    }.await;
+    
+    match result {
+        Ok(count) => {
+            log.records_processed = Some(count as i64);
+            log.status = if log.records_rejected.unwrap_or(0) > 0 {
+                "partial".to_string()
+            } else {
+                "success".to_string()
+            };
+        },
+        Err(ref e) => log.error_message = Some(e.to_string()),
+    }
+    log.completed_at = chrono::Local::now().naive_local();
+    
+    if let Err(log_err) = db.log_ingestion(&log).await {
+        warn!("Failed to log ingestion: {}", log_err);
+    }
+    
+    result
+}
+
+/// Stores and logs a fetched date. With `replace_existing`, the date's
+/// stored prices in the filtered series are replaced rather than upserted.
+async fn store_date(
+    db: &Database,
+    source: &dyn MarketDataSource,
+    series_filter: &SeriesFilter,
+    validation: &ValidationConfig,
+    fetched: FetchedDate,
+    replace_existing: bool,
+    progress: Option<&ProgressBar>,
+) -> Result<usize> {
+    let FetchedDate { date, started_at, file_name, parsed } = fetched;
+    
+    let mut log = IngestionLogInsert {
+        source: source.name().to_string(),
+        file_name,
+        trade_date: Some(date),
+        records_processed: Some(0),
+        records_rejected: Some(0),
+        status: "failed".to_string(),
+        error_message: None,
+        series_filter: series_filter.to_log_value(),
+        started_at: Some(started_at),
+        completed_at: chrono::Local::now().naive_local(),
+    };
+    
+    let mut issues = Vec::new();
+    let result = async {
+        let mut parsed = parsed?;
+        
+        let filtered_out = series_filter.retain(&mut parsed.records);
+        if filtered_out > 0 {
+            info!("Skipped {} records outside the series filter", filtered_out);
+        }
+
+        if !parsed.rejected.is_empty() {
+            let mut message = format!("⚠️  Rejected {} malformed rows for {}:", parsed.rejected.len(), date);
+            for rejected in parsed.rejected.iter().take(10) {
+                message.push_str(&format!("\n   {}", rejected));
+            }
+            report(progress, message);
+        }
+        
+        let validated = validation::validate_records(parsed.records, validation);
+        if !validated.issues.is_empty() {
+            report(progress, quality_summary(date, &validated.issues, validated.rejected));
+        }
+        log.records_rejected = Some((parsed.rejected.len() + validated.rejected) as i64);
+        parsed.records = validated.records;
+        issues = validated.issues;
+        
+        if parsed.records.is_empty() {
+            if validated.rejected > 0 {
+                return Err(anyhow::anyhow!("All {} records failed validation", validated.rejected));
+            }
+            if filtered_out > 0 {
+                return Err(anyhow::anyhow!("No records matched the series filter"));
+            }
             return Err(anyhow::anyhow!("No valid records found in CSV data"));
         }
         
         // Store in database
-        println!("💾 Storing {} records in database...", records.len());
-        let stored_count = db.store_stock_records(&records).await?;
+        report_step(progress, &format!("💾 Storing {} records in database...", parsed.records.len()));
+        let stored_count = if replace_existing {
+            db.replace_stock_records(source.exchange(), date, series_filter, &parsed.records).await?
+        } else {
+            db.store_stock_records(&parsed.records).await?
+        };
         
         Ok(stored_count)
     }.await;
@@ -155,7 +1143,11 @@ async fn ingest_single_date(db: &Database, nse_client: &NseClient, date: NaiveDa
     match result {
         Ok(count) => {
             log.records_processed = Some(count as i64);
-            log.status = "success".to_string();
+            log.status = if log.records_rejected.unwrap_or(0) > 0 {
+                "partial".to_string()
+            } else {
+                "success".to_string()
+            };
             log.completed_at = chrono::Local::now().naive_local();
         },
         Err(ref e) => {
@@ -164,32 +1156,269 @@ async fn ingest_single_date(db: &Database, nse_client: &NseClient, date: NaiveDa
         }
     }
     
-    // Log the ingestion attempt
-    if let Err(log_err) = db.log_ingestion(&log).await {
-        warn!("Failed to log ingestion: {}", log_err);
+    // Log the ingestion attempt, then the issues found by it
+    match db.log_ingestion(&log).await {
+        Ok(ingestion_id) if !issues.is_empty() => {
+            if let Err(e) = db.store_quality_issues(ingestion_id, &issues).await {
+                warn!("Failed to store data quality issues: {}", e);
+            }
+        },
+        Ok(_) => {},
+        Err(log_err) => warn!("Failed to log ingestion: {}", log_err),
     }
     
     result
 }
 
-async fn handle_status() -> Result<()> {
+/// Summarises a date's validation issues, listing the first few.
+fn quality_summary(date: NaiveDate, issues: &[QualityIssue], rejected: usize) -> String {
+    let warnings = issues.iter().filter(|issue| issue.severity == Severity::Warning).count();
+    let mut message = format!(
+        "⚠️  Validation rejected {} records and raised {} warnings for {}:",
+        rejected, warnings, date
+    );
+    for issue in issues.iter().take(10) {
+        message.push_str(&format!("\n   {}: {}", issue.severity, issue));
+    }
+    if issues.len() > 10 {
+        message.push_str(&format!("\n   ... run `market-data quality --date {}` for the rest", date));
+    }
+    message
+}
+
+/// Ingests bhavcopies delivered as files, working out each file's exchange
+/// and trade date from its name or contents.
+async fn handle_ingest_files(db_location: &str, cache: &RawFileCache, args: IngestArgs) -> Result<()> {
+    let paths = local::find_bhavcopy_files(&args.file, &args.dir, args.recursive)?;
+    if paths.is_empty() {
+        println!("No bhavcopy files found");
+        return Ok(());
+    }
+    
+    let series_filter = SeriesFilter::new(&args.series);
+    let validation = args.validation.config();
+    let db = Database::new(db_location).await?;
+    println!("📂 Ingesting {} local files", paths.len());
+    
+    let progress = (paths.len() > 1).then(|| backfill_progress_bar(paths.len()));
+    // Delivery files go last, once the bhavcopies have created their listings
+    let (delivery_paths, paths): (Vec<PathBuf>, Vec<PathBuf>) =
+        paths.into_iter().partition(|path| local::identify_delivery_file(path).is_some());
+    let mut sources: HashMap<&'static str, Box<dyn MarketDataSource>> = HashMap::new();
+    let mut records_stored = 0;
+    for path in paths {
+        let started_at = chrono::Local::now().naive_local();
+        let outcome = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|body| {
+                let identified = local::identify_bhavcopy(&path, &body)?;
+                Ok((body, identified))
+            });
+        
+        let (body, identified) = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                // Without a trade date there is nothing to store, but the
+                // attempt is still logged against the file. The exchange is
+                // unknown too, so it is not blamed on one
+                let log = IngestionLogInsert {
+                    source: "local".to_string(),
+                    file_name: Some(path.display().to_string()),
+                    trade_date: None,
+                    records_processed: Some(0),
+                    records_rejected: Some(0),
+                    status: "failed".to_string(),
+                    error_message: Some(e.to_string()),
+                    series_filter: series_filter.to_log_value(),
+                    started_at: Some(started_at),
+                    completed_at: chrono::Local::now().naive_local(),
+                };
+                if let Err(log_err) = db.log_ingestion(&log).await {
+                    warn!("Failed to log ingestion: {}", log_err);
+                }
+                error!("Failed to ingest {}: {}", path.display(), e);
+                report(progress.as_ref(), format!("❌ Failed to ingest {}: {:#}", path.display(), e));
+                if let Some(progress) = &progress {
+                    progress.inc(1);
+                }
+                continue;
+            }
+        };
+        
+        let source = match sources.entry(identified.source) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(source::create_source(identified.source)?),
+        };
+        let date = identified.trade_date;
+        let raw = RawFile {
+            file_name: path.display().to_string(),
+            content_type: None,
+            body,
+        };
+        
+        // Archive the file so that `reparse` can rebuild from it later
+        let file_name = match cache.store(source.name(), date, &raw) {
+            Ok(entry) => entry.log_file_name(),
+            Err(e) => {
+                warn!("Failed to archive {}: {}", path.display(), e);
+                raw.file_name.clone()
+            }
+        };
+        let parsed = source.parse(&raw, date);
+        evict_if_stale(cache, source.name(), date, &parsed);
+        let fetched = FetchedDate {
+            date,
+            started_at,
+            file_name: Some(file_name),
+            parsed,
+        };
+        
+        match store_date(&db, source.as_ref(), &series_filter, &validation, fetched, false, progress.as_ref()).await {
+            Ok(count) => {
+                records_stored += count;
+                report(
+                    progress.as_ref(),
+                    format!("✅ Ingested {} {} records for {} from {}", count, source.exchange(), date, path.display()),
+                );
+            },
+            Err(e) => {
+                error!("Failed to ingest {}: {}", path.display(), e);
+                report(progress.as_ref(), format!("❌ Failed to ingest {}: {}", path.display(), e));
+            }
+        }
+        
+        if let Some(progress) = &progress {
+            progress.inc(1);
+            progress.set_message(format!("{} records stored", records_stored));
+        }
+    }
+    
+    for path in delivery_paths {
+        let started_at = chrono::Local::now().naive_local();
+        let date = local::identify_delivery_file(&path).expect("partitioned on the file name");
+        let raw = std::fs::read(&path).map(|body| RawFile {
+            file_name: path.display().to_string(),
+            content_type: None,
+            body,
+        });
+        let file_name = match &raw {
+            Ok(raw) => match cache.store(delivery::DELIVERY_SOURCE, date, raw) {
+                Ok(entry) => entry.log_file_name(),
+                Err(e) => {
+                    warn!("Failed to archive {}: {}", path.display(), e);
+                    raw.file_name.clone()
+                }
+            },
+            Err(_) => path.display().to_string(),
+        };
+        let parsed = raw.map_err(anyhow::Error::from).and_then(|raw| {
+            let content = source::decode_bhavcopy_payload(&raw.body, None)?;
+            delivery::parse_mto(&content, date)
+        });
+        evict_if_stale(cache, delivery::DELIVERY_SOURCE, date, &parsed);
+        let fetched = FetchedDate { date, started_at, file_name: Some(file_name), parsed };
+        
+        match store_delivery_date(&db, delivery::DELIVERY_SOURCE, &series_filter, fetched, progress.as_ref()).await {
+            Ok(count) => {
+                records_stored += count;
+                report(
+                    progress.as_ref(),
+                    format!("✅ Ingested {} delivery positions for {} from {}", count, date, path.display()),
+                );
+            },
+            Err(e) => {
+                error!("Failed to ingest {}: {}", path.display(), e);
+                report(progress.as_ref(), format!("❌ Failed to ingest {}: {}", path.display(), e));
+            }
+        }
+        
+        if let Some(progress) = &progress {
+            progress.inc(1);
+            progress.set_message(format!("{} records stored", records_stored));
+        }
+    }
+    
+    if let Some(progress) = &progress {
+        progress.finish_with_message(format!("{} records stored", records_stored));
+    }
+    
+    Ok(())
+}
+
+async fn handle_reparse(db_location: &str, cache: &RawFileCache, args: ReparseArgs) -> Result<()> {
+    let validation = args.validation.config();
+    let ReparseArgs { source, from, to, series, .. } = args;
+    let source = source::create_source(&source)?;
+    let series_filter = SeriesFilter::new(&series);
+    
+    let from = match from {
+        Some(from_str) => NaiveDate::parse_from_str(&from_str, "%Y-%m-%d")?,
+        None => NaiveDate::MIN,
+    };
+    let to = match to {
+        Some(to_str) => NaiveDate::parse_from_str(&to_str, "%Y-%m-%d")?,
+        None => NaiveDate::MAX,
+    };
+    
+    let dates = cache.cached_dates(source.name(), from, to)?;
+    if dates.is_empty() {
+        println!("No archived {} files found in {}", source.name(), cache.root().display());
+        return Ok(());
+    }
+    
+    let db = Database::new(db_location).await?;
+    println!("🔁 Reparsing {} archived {} files", dates.len(), source.name());
+    
+    let progress = (dates.len() > 1).then(|| backfill_progress_bar(dates.len()));
+    let mut records_stored = 0;
+    for date in dates {
+        let fetched = fetch_date(source.as_ref(), cache, CacheMode::Offline, date, progress.as_ref()).await;
+        match store_date(&db, source.as_ref(), &series_filter, &validation, fetched, true, progress.as_ref()).await {
+            Ok(count) => {
+                records_stored += count;
+                report(progress.as_ref(), format!("✅ Reparsed {} records for {}", count, date));
+            },
+            Err(e) => {
+                error!("Failed to reparse {}: {}", date, e);
+                report(progress.as_ref(), format!("❌ Failed to reparse {}: {}", date, e));
+            }
+        }
+        
+        if let Some(progress) = &progress {
+            progress.inc(1);
+            progress.set_message(format!("{} records stored", records_stored));
+        }
+    }
+    
+    if let Some(progress) = &progress {
+        progress.finish_with_message(format!("{} records stored", records_stored));
+    }
+    
+    Ok(())
+}
+
+async fn handle_status(db_location: &str) -> Result<()> {
     println!("📊 Market Data Ingestion Status");
     println!("{}", "=".repeat(50));
     
     // Connect to database
-    let db = Database::new("sqlite://market_data.db").await?;
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot show status for database at {}", db_location))?;
     
     // Get recent ingestion logs
     match db.get_ingestion_logs(Some(10)).await {
         Ok(logs) => {
             if logs.is_empty() {
-                println!("No ingestion logs found. Run 'market-data init-db' to initialize the database.");
+                println!("No ingestion logs found. Run 'market-data ingest' to download data.");
                 return Ok(());
             }
             
             println!("Recent ingestion attempts (last 10):");
             println!();
             
+            let mut calendars: HashMap<String, TradingCalendar> = HashMap::new();
             for log in logs {
                 let status_icon = match log.status.as_str() {
                     "success" => "✅",
@@ -198,30 +1427,70 @@ async fn handle_status() -> Result<()> {
                     _ => "❓"
                 };
                 
+                let rejected = match log.records_rejected.unwrap_or(0) {
+                    0 => String::new(),
+                    count => format!(" ({} rejected)", count),
+                };
+                
                 println!(
-                    "{} {} | {} | {} records | {}", 
+                    "{} {} | {} | {} records{} | {}", 
                     status_icon,
                     log.trade_date
                         .map(|d| d.format("%Y-%m-%d").to_string())
                         .unwrap_or("N/A".to_string()),
                     log.source.to_uppercase(),
                     log.records_processed.unwrap_or(0),
+                    rejected,
                     log.completed_at.format("%Y-%m-%d %H:%M:%S")
                 );
                 
                 if let Some(error) = &log.error_message {
                     println!("   Error: {}", error);
                 }
+                if let (None, Some(file_name)) = (log.trade_date, &log.file_name) {
+                    println!("   File: {}", file_name);
+                }
+                
+                // A failed download on a holiday is expected, not an outage
+                if let (Some(trade_date), "failed") = (log.trade_date, log.status.as_str()) {
+                    // Delivery runs are logged as e.g. `nse-mto`
+                    let exchange = log.source.split('-').next().unwrap_or_default().to_uppercase();
+                    if !calendars.contains_key(&exchange) {
+                        calendars.insert(exchange.clone(), db.trading_calendar(&exchange).await?);
+                    }
+                    let calendar = &calendars[&exchange];
+                    if !calendar.is_trading_day(trade_date) {
+                        let reason = calendar.holiday(trade_date).unwrap_or("weekend");
+                        println!("   Note: {} was closed on {} ({})", exchange, trade_date, reason);
+                    }
+                }
             }
             
             // Get database statistics
             let company_count = get_table_count(&db, "companies").await?;
             let price_count = get_table_count(&db, "daily_prices").await?;
+            let delivery_count = get_table_count(&db, "delivery_positions").await?;
             
             println!();
             println!("Database Statistics:");
             println!("- Companies: {}", company_count);
             println!("- Price Records: {}", price_count);
+            println!("- Delivery Records: {}", delivery_count);
+            println!("- Listed on more than one exchange: {}", db.count_cross_listed_securities().await?);
+            
+            println!();
+            println!("By Exchange:");
+            for stats in db.get_exchange_statistics().await? {
+                println!(
+                    "- {}: {} companies | {} price records | latest {}",
+                    stats.exchange,
+                    stats.companies,
+                    stats.price_records,
+                    stats.latest_trade_date
+                        .map(|d| d.format("%Y-%m-%d").to_string())
+                        .unwrap_or("N/A".to_string())
+                );
+            }
         },
         Err(e) => {
             error!("Failed to fetch ingestion logs: {}", e);
@@ -241,20 +1510,632 @@ async fn get_table_count(db: &Database, table_name: &str) -> Result<i64> {
     Ok(row.get("count"))
 }
 
-async fn handle_init_db(db_path: String) -> Result<()> {
-    println!("Initializing database at: {}", db_path);
+async fn handle_init_db(db_location: &str) -> Result<()> {
+    println!("Initializing database at: {}", db_location);
     
-    // Create the database URL
-    let database_url = format!("sqlite://{}", db_path);
+    // Connect to the database (creating the file if needed) and apply the migrations
+    Database::new(db_location).await?;
     
-    // Connect to the database (will create file if it doesn't exist)
-    let db = Database::new(&database_url).await?;
+    println!("✅ Database initialized successfully at: {}", db_location);
+    println!("You can now use the 'ingest' command to start downloading data.");
+    
+    Ok(())
+}
+
+async fn handle_db_migrate(db_location: &str) -> Result<()> {
+    let db = Database::open(db_location).await?;
     
-    // Initialize the schema
+    let before = db.schema_version().await?;
     db.initialize().await?;
+    let after = db.schema_version().await?;
     
-    println!("✅ Database initialized successfully at: {}", db_path);
-    println!("You can now use the 'ingest' command to start downloading data.");
+    if before == after {
+        println!("✅ Database schema is up to date (version {})", after.unwrap_or(0));
+    } else {
+        println!(
+            "✅ Migrated database schema from version {} to {}",
+            before.unwrap_or(0),
+            after.unwrap_or(0)
+        );
+    }
+    
+    Ok(())
+}
+
+async fn handle_db_version(db_location: &str) -> Result<()> {
+    let db = Database::open(db_location).await?;
+    
+    let latest = Database::latest_schema_version();
+    match db.schema_version().await? {
+        Some(version) => {
+            println!("Schema version: {}", version);
+            println!("Latest version: {}", latest);
+            if version < latest {
+                println!("Run 'market-data db migrate' to upgrade it.");
+            }
+        },
+        None => {
+            println!("Schema version: none (database has not been migrated)");
+            println!("Latest version: {}", latest);
+            println!("Run 'market-data db migrate' to initialize it.");
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_holidays_import(db_location: &str, file: &Path, exchange: &str) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Cannot read holiday file {}", file.display()))?;
+    let exchange = exchange.to_ascii_uppercase();
+
+    let is_json = file
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("json"))
+        .unwrap_or_else(|| content.trim_start().starts_with('['));
+    let days = if is_json {
+        calendar::parse_calendar_json(&content, &exchange)?
+    } else {
+        calendar::parse_calendar_csv(&content, &exchange)?
+    };
+
+    if days.is_empty() {
+        return Err(anyhow!("No holidays found in {}", file.display()));
+    }
+
+    let db = Database::new(db_location).await?;
+    let imported = db.upsert_market_days(&days).await?;
+
+    println!("✅ Imported {} holidays and special sessions from {}", imported, file.display());
+    Ok(())
+}
+
+async fn handle_holidays_list(db_location: &str, exchange: &str, year: Option<i32>) -> Result<()> {
+    let exchange = exchange.to_ascii_uppercase();
+    let year = year.unwrap_or_else(|| calendar::exchange_now().year());
+    let from = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(|| anyhow!("Invalid year {}", year))?;
+    let to = NaiveDate::from_ymd_opt(year, 12, 31).ok_or_else(|| anyhow!("Invalid year {}", year))?;
+
+    let db = Database::new(db_location).await?;
+    let calendar = db.trading_calendar(&exchange).await?;
+
+    println!("📅 {} holidays in {}", exchange, year);
+    println!("{}", "=".repeat(50));
+    warn_uncovered_years(&calendar, &exchange, from, to);
+
+    let holidays = calendar.holidays_in_range(from, to);
+    for (date, description) in &holidays {
+        println!("{} {} | {}", date.format("%Y-%m-%d"), date.format("%a"), description);
+    }
+
+    let special_sessions: Vec<_> = from
+        .iter_days()
+        .take_while(|date| *date <= to)
+        .filter_map(|date| calendar.special_session(date).map(|description| (date, description)))
+        .collect();
+    if !special_sessions.is_empty() {
+        println!();
+        println!("Special sessions:");
+        for (date, description) in special_sessions {
+            println!("{} {} | {}", date.format("%Y-%m-%d"), date.format("%a"), description);
+        }
+    }
+
+    println!();
+    println!(
+        "{} trading days, {} weekday holidays",
+        calendar.trading_dates_in_range(from, to).len(),
+        holidays.len()
+    );
+    Ok(())
+}
+
+async fn handle_quality(db_location: &str, args: QualityArgs) -> Result<()> {
+    let parse_date = |value: Option<String>| -> Result<Option<NaiveDate>> {
+        value
+            .map(|value| NaiveDate::parse_from_str(&value, "%Y-%m-%d"))
+            .transpose()
+            .map_err(anyhow::Error::from)
+    };
+    let date = parse_date(args.date)?;
+    let filter = QualityIssueFilter {
+        exchange: args.exchange,
+        symbol: args.symbol,
+        from: date.or(parse_date(args.from)?),
+        to: date.or(parse_date(args.to)?),
+        rule: args.rule,
+        severity: args.severity,
+        limit: Some(args.limit),
+    };
+    
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot list quality issues for database at {}", db_location))?;
+    
+    println!("🔎 Data Quality Issues");
+    println!("{}", "=".repeat(50));
+    
+    let issues = db.get_quality_issues(&filter).await?;
+    if issues.is_empty() {
+        println!("No data quality issues found.");
+        return Ok(());
+    }
+    
+    for issue in &issues {
+        let icon = match issue.severity.as_str() {
+            "rejection" => "❌",
+            _ => "⚠️",
+        };
+        println!(
+            "{} {} | {} | {} {} | {} | {}",
+            icon,
+            issue.trade_date.format("%Y-%m-%d"),
+            issue.exchange,
+            issue.symbol,
+            issue.series,
+            issue.rule,
+            issue.message
+        );
+    }
+    
+    let rejections = issues.iter().filter(|issue| issue.severity == "rejection").count();
+    println!();
+    println!(
+        "{} issues ({} rejections, {} warnings){}",
+        issues.len(),
+        rejections,
+        issues.len() - rejections,
+        if issues.len() as i64 == args.limit { "; raise --limit to see more" } else { "" }
+    );
+    Ok(())
+}
+
+async fn handle_reconcile(db_location: &str, args: ReconcileArgs) -> Result<()> {
+    let parse_date = |value: &Option<String>| -> Result<Option<NaiveDate>> {
+        value
+            .as_deref()
+            .map(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+            .transpose()
+            .map_err(anyhow::Error::from)
+    };
+    let from = parse_date(&args.from)?;
+    let to = parse_date(&args.to)?;
+    
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot reconcile prices in database at {}", db_location))?;
+    
+    let prices = db
+        .get_closing_prices(args.exchange.as_deref(), args.symbol.as_deref(), from, to)
+        .await?;
+    let mut calendars = HashMap::new();
+    for price in &prices {
+        if !calendars.contains_key(&price.exchange) {
+            calendars.insert(price.exchange.clone(), db.trading_calendar(&price.exchange).await?);
+        }
+    }
+    let breaks = reconcile::find_breaks(&prices, &calendars, args.tolerance);
+    
+    match (args.format, &args.output) {
+        (ReportFormat::Table, _) => {
+            for (exchange, calendar) in &calendars {
+                let dates = prices.iter().filter(|price| &price.exchange == exchange).map(|price| price.trade_date);
+                if let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) {
+                    warn_uncovered_years(calendar, exchange, first, last);
+                }
+            }
+        }
+        (ReportFormat::Csv, None) => return reconcile::write_csv(&breaks, std::io::stdout().lock()),
+        (ReportFormat::Json, None) => return reconcile::write_json(&breaks, std::io::stdout().lock()),
+        (format, Some(path)) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Cannot write report to {}", path.display()))?;
+            let writer = std::io::BufWriter::new(file);
+            match format {
+                ReportFormat::Json => reconcile::write_json(&breaks, writer)?,
+                _ => reconcile::write_csv(&breaks, writer)?,
+            }
+            println!("📝 Wrote {} breaks to {}", breaks.len(), path.display());
+            return Ok(());
+        }
+    }
+    
+    println!("🔗 PREVCLOSE Reconciliation");
+    println!("{}", "=".repeat(50));
+    
+    if breaks.is_empty() {
+        println!("✅ Every PREVCLOSE matches the stored close across {} prices", prices.len());
+        return Ok(());
+    }
+    
+    for continuity_break in &breaks {
+        println!(
+            "{} {} | {} {} | close {} on {} vs PREVCLOSE {} | {} | {}",
+            continuity_break.trade_date.format("%Y-%m-%d"),
+            continuity_break.exchange,
+            continuity_break.symbol,
+            continuity_break.series,
+            continuity_break.stored_close,
+            continuity_break.previous_date.format("%Y-%m-%d"),
+            continuity_break.reported_prev_close,
+            continuity_break.cause,
+            continuity_break.detail
+        );
+    }
+    
+    let count = |cause: BreakCause| breaks.iter().filter(|b| b.cause == cause).count();
+    println!();
+    println!(
+        "{} breaks across {} prices: {} split, {} bonus, {} missing day, {} data error",
+        breaks.len(),
+        prices.len(),
+        count(BreakCause::Split),
+        count(BreakCause::Bonus),
+        count(BreakCause::MissingDay),
+        count(BreakCause::DataError)
+    );
+    Ok(())
+}
+
+async fn handle_corporate_actions_import(db_location: &str, file: &Path, exchange: &str) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Cannot read corporate actions file {}", file.display()))?;
+    let exchange = exchange.to_ascii_uppercase();
+    
+    let is_json = file
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("json"))
+        .unwrap_or_else(|| content.trim_start().starts_with('['));
+    let actions = if is_json {
+        corporate_actions::parse_corporate_actions_json(&content, &exchange)?
+    } else {
+        corporate_actions::parse_corporate_actions_csv(&content, &exchange)?
+    };
+    
+    if actions.is_empty() {
+        return Err(anyhow!("No corporate actions found in {}", file.display()));
+    }
+    
+    let db = Database::new(db_location).await?;
+    let imported = db.upsert_corporate_actions(&actions).await?;
+    let unlinked = db.link_corporate_actions().await?;
+    let recomputed = db.refresh_adjustment_factors().await?;
+    
+    let count = |kind: ActionKind| actions.iter().filter(|action| action.terms.kind == kind).count();
+    println!("✅ Imported {} corporate actions from {}", imported, file.display());
+    println!(
+        "   {} splits, {} bonuses, {} dividends, {} rights issues, {} other",
+        count(ActionKind::Split),
+        count(ActionKind::Bonus),
+        count(ActionKind::Dividend),
+        count(ActionKind::Rights),
+        count(ActionKind::Other)
+    );
+    if recomputed > 0 {
+        println!("♻️  Recomputed adjustment factors for {} securities", recomputed);
+    }
+    if unlinked > 0 {
+        println!("⚠️  {} actions have no ISIN yet; they are linked once their listing is ingested and the file is imported again", unlinked);
+    }
+    Ok(())
+}
+
+async fn handle_corporate_actions_list(db_location: &str, filter: &CorporateActionFilter) -> Result<()> {
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot list corporate actions in database at {}", db_location))?;
+    
+    println!("🏢 Corporate Actions");
+    println!("{}", "=".repeat(50));
+    
+    let actions = db.get_corporate_actions(filter).await?;
+    if actions.is_empty() {
+        println!("No corporate actions found. Run 'market-data corporate-actions import' to add them.");
+        return Ok(());
+    }
+    
+    for action in &actions {
+        let factor = action
+            .terms
+            .price_factor()
+            .map(|factor| format!(" (price factor {:.4})", factor))
+            .unwrap_or_default();
+        println!(
+            "{} | {} | {} | {}{} | {}",
+            action.ex_date.format("%Y-%m-%d"),
+            action.exchange,
+            action.symbol,
+            action.terms,
+            factor,
+            action.isin.as_deref().unwrap_or("no ISIN")
+        );
+    }
+    println!();
+    println!("{} corporate actions", actions.len());
+    Ok(())
+}
+
+async fn handle_symbols_import(db_location: &str, file: &Path, exchange: &str) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Cannot read symbol change file {}", file.display()))?;
+    let changes = symbols::parse_symbol_changes_csv(&content, &exchange.to_ascii_uppercase())?;
+    if changes.is_empty() {
+        return Err(anyhow!("No symbol changes found in {}", file.display()));
+    }
+
+    let db = Database::new(db_location).await?;
+    let summary = db.apply_symbol_changes(&changes).await?;
+    let recomputed = db.refresh_adjustment_factors().await?;
+
+    println!("✅ Read {} symbol changes from {}", changes.len(), file.display());
+    println!("   {} applied to stored listings, {} for symbols not ingested", summary.applied, summary.unmatched);
+    if summary.merged > 0 {
+        println!("🔗 Merged {} listings stored under their new symbol into their old listing", summary.merged);
+    }
+    if recomputed > 0 {
+        println!("♻️  Recomputed adjustment factors for {} securities", recomputed);
+    }
+    Ok(())
+}
+
+async fn handle_symbols_history(
+    db_location: &str,
+    symbol: &str,
+    exchange: Option<&str>,
+    date: Option<NaiveDate>,
+) -> Result<()> {
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot read symbol history from database at {}", db_location))?;
+
+    println!("🔤 Symbol History: {}", symbol.to_ascii_uppercase());
+    println!("{}", "=".repeat(50));
+
+    let listings = db.find_listings_by_symbol(exchange, symbol, date).await?;
+    if listings.is_empty() {
+        println!("No listing has traded as {}.", symbol.to_ascii_uppercase());
+        return Ok(());
+    }
+
+    let ids: Vec<i64> = listings.iter().map(|listing| listing.id).collect();
+    let history = db.get_symbol_history(&ids).await?;
+    for listing in &listings {
+        println!(
+            "{} {} {} (listing {}, ISIN {})",
+            listing.exchange,
+            listing.symbol,
+            listing.series,
+            listing.id,
+            listing.isin.as_deref().filter(|isin| !isin.is_empty()).unwrap_or("unknown")
+        );
+        for entry in history.iter().filter(|entry| entry.company_id == listing.id) {
+            let from = entry.valid_from.map(|date| date.to_string()).unwrap_or_else(|| "…".to_string());
+            let to = entry.valid_to.map(|date| date.to_string()).unwrap_or_else(|| "current".to_string());
+            println!(
+                "   {} → {} | {} | {} | {}",
+                from,
+                to,
+                entry.symbol,
+                if entry.isin.is_empty() { "no ISIN" } else { &entry.isin },
+                entry.source
+            );
+        }
+    }
+    println!();
+    println!("{} listings", listings.len());
+    Ok(())
+}
+
+async fn handle_companies_import(db_location: &str, file: &Path, date: Option<NaiveDate>) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Cannot read company file {}", file.display()))?;
+    let db = Database::new(db_location).await?;
+
+    if companies::is_equity_list(&content) {
+        let records = companies::parse_equity_list_csv(&content)?;
+        if records.is_empty() {
+            return Err(anyhow!("No securities found in {}", file.display()));
+        }
+        let as_of = date.unwrap_or_else(|| calendar::exchange_now().date());
+        let summary = db.import_equity_list(&records, as_of).await?;
+
+        println!("✅ Imported {} securities from {} as of {}", summary.imported, file.display(), as_of);
+        if summary.new_listings > 0 {
+            println!("🆕 {} listings had not been ingested yet", summary.new_listings);
+        }
+        if summary.delisted > 0 {
+            println!("⚠️  Flagged {} listings missing from the list as delisted", summary.delisted);
+        }
+        if summary.relisted > 0 {
+            println!("🔁 {} delisted listings are back in the list", summary.relisted);
+        }
+    } else {
+        let classifications = companies::parse_classification_csv(&content)?;
+        if classifications.is_empty() {
+            return Err(anyhow!("No classifications found in {}", file.display()));
+        }
+        let unmatched = db.import_classifications(&classifications).await?;
+
+        println!("✅ Read {} classifications from {}", classifications.len(), file.display());
+        println!("   {} matched stored listings, {} did not", classifications.len() - unmatched, unmatched);
+    }
+    Ok(())
+}
+
+async fn handle_companies_flag(db_location: &str, file: &Path, status: ListingStatus, exchange: &str) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Cannot read status file {}", file.display()))?;
+    let changes = companies::parse_status_csv(&content, status, exchange)?;
+    if changes.is_empty() {
+        return Err(anyhow!("No securities found in {}", file.display()));
+    }
+
+    let db = Database::new(db_location).await?;
+    let unmatched = db.set_listing_status(&changes).await?;
+
+    println!("✅ Flagged {} securities as {}", changes.len() - unmatched, status);
+    if unmatched > 0 {
+        println!("   {} securities have never been ingested", unmatched);
+    }
+    Ok(())
+}
+
+async fn handle_companies_search(db_location: &str, filter: &CompanyFilter) -> Result<()> {
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot search companies in database at {}", db_location))?;
+
+    println!("🏭 Companies");
+    println!("{}", "=".repeat(50));
+
+    let profiles = db.search_companies(filter).await?;
+    if profiles.is_empty() {
+        println!("No listings found.");
+        return Ok(());
+    }
+
+    for profile in &profiles {
+        let status = match profile.status.as_str() {
+            "active" => String::new(),
+            other => format!(" [{}]", other),
+        };
+        println!(
+            "{} | {} | {} | {} | {} | {}{}",
+            profile.exchange,
+            profile.symbol,
+            profile.series,
+            profile.name.as_deref().unwrap_or("unnamed"),
+            profile.industry.as_deref().or(profile.sector.as_deref()).unwrap_or("unclassified"),
+            profile.isin.as_deref().filter(|isin| !isin.is_empty()).unwrap_or("no ISIN"),
+            status
+        );
+    }
+    println!();
+    println!("{} listings", profiles.len());
+    Ok(())
+}
+
+async fn handle_companies_show(db_location: &str, filter: &CompanyFilter) -> Result<()> {
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot read companies from database at {}", db_location))?;
+
+    let key = filter.symbol.as_deref().or(filter.isin.as_deref()).unwrap_or_default();
+    println!("🏭 Company: {}", key.to_ascii_uppercase());
+    println!("{}", "=".repeat(50));
+
+    let profiles = db.search_companies(filter).await?;
+    if profiles.is_empty() {
+        println!("No listing found for {}.", key.to_ascii_uppercase());
+        return Ok(());
+    }
+
+    let ids: Vec<i64> = profiles.iter().map(|profile| profile.id).collect();
+    let history = db.get_symbol_history(&ids).await?;
+    let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+    for profile in &profiles {
+        println!("{} {} {} (listing {})", profile.exchange, profile.symbol, profile.series, profile.id);
+        println!("   Name:          {}", or_unknown(profile.name.clone()));
+        println!("   ISIN:          {}", or_unknown(profile.isin.clone().filter(|isin| !isin.is_empty())));
+        println!("   Sector:        {}", or_unknown(profile.sector.clone()));
+        println!("   Industry:      {}", or_unknown(profile.industry.clone()));
+        println!("   Face value:    {}", or_unknown(profile.face_value.map(|value| value.to_string())));
+        println!("   Paid-up value: {}", or_unknown(profile.paid_up_value.map(|value| value.to_string())));
+        println!("   Market lot:    {}", or_unknown(profile.market_lot.map(|lot| lot.to_string())));
+        println!("   Listed on:     {}", or_unknown(profile.listing_date.map(|date| date.to_string())));
+        let status_date = profile.status_date.map(|date| format!(" since {}", date)).unwrap_or_default();
+        println!("   Status:        {}{}", profile.status, status_date);
+        match (profile.first_trade_date, profile.last_trade_date) {
+            (Some(first), Some(last)) => println!("   Prices:        {} to {}", first, last),
+            _ => println!("   Prices:        none stored"),
+        }
+        for entry in history.iter().filter(|entry| entry.company_id == profile.id) {
+            let from = entry.valid_from.map(|date| date.to_string()).unwrap_or_else(|| "…".to_string());
+            let to = entry.valid_to.map(|date| date.to_string()).unwrap_or_else(|| "current".to_string());
+            println!("   Traded as {} from {} to {}", entry.symbol, from, to);
+        }
+        println!();
+    }
+    println!("{} listings", profiles.len());
+    Ok(())
+}
+
+async fn handle_prices(db_location: &str, args: PricesArgs) -> Result<()> {
+    let parse_date = |value: &Option<String>| -> Result<Option<NaiveDate>> {
+        value
+            .as_deref()
+            .map(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+            .transpose()
+            .map_err(anyhow::Error::from)
+    };
+    let filter = PriceFilter {
+        exchange: args.exchange.clone(),
+        symbol: args.symbol.clone(),
+        series: args.series.clone(),
+        from: parse_date(&args.from)?,
+        to: parse_date(&args.to)?,
+    };
+    
+    let db = Database::open(db_location).await?;
+    db.ensure_initialized()
+        .await
+        .with_context(|| format!("Cannot read prices from database at {}", db_location))?;
+    if args.adjust != Adjustment::Raw {
+        db.refresh_adjustment_factors().await?;
+    }
+    let bars = db.get_adjusted_prices(&filter, args.adjust).await?;
+    
+    match (args.format, &args.output) {
+        (ReportFormat::Table, _) => {}
+        (ReportFormat::Csv, None) => return adjustment::write_csv(&bars, std::io::stdout().lock()),
+        (ReportFormat::Json, None) => return adjustment::write_json(&bars, std::io::stdout().lock()),
+        (format, Some(path)) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Cannot write prices to {}", path.display()))?;
+            let writer = std::io::BufWriter::new(file);
+            match format {
+                ReportFormat::Json => adjustment::write_json(&bars, writer)?,
+                _ => adjustment::write_csv(&bars, writer)?,
+            }
+            println!("📝 Wrote {} {} prices to {}", bars.len(), args.adjust, path.display());
+            return Ok(());
+        }
+    }
+    
+    println!("📈 Daily Prices ({})", args.adjust);
+    println!("{}", "=".repeat(50));
+    
+    if bars.is_empty() {
+        println!("No prices found.");
+        return Ok(());
+    }
     
+    for bar in &bars {
+        let delivery = bar
+            .delivery_pct
+            .map(|pct| format!(" | Dlv {:.2}%", pct))
+            .unwrap_or_default();
+        println!(
+            "{} | {} | {} {} | O {:.2} H {:.2} L {:.2} C {:.2} | Vol {:.0}{}",
+            bar.trade_date.format("%Y-%m-%d"),
+            bar.exchange,
+            bar.symbol,
+            bar.series,
+            bar.open,
+            bar.high,
+            bar.low,
+            bar.close,
+            bar.volume,
+            delivery
+        );
+    }
+    println!();
+    println!("{} prices", bars.len());
     Ok(())
 }
@@ -1,165 +1,271 @@
-use reqwest::Client;
 use anyhow::{Result, anyhow};
-use chrono::{NaiveDate, Local, Datelike};
-use tracing::{info, warn, error};
-use std::io::Cursor;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Datelike};
+use reqwest::header::HeaderMap;
+use tracing::info;
+
+use crate::source::{
+    ColumnMap, ColumnNames, MarketDataSource, ParsedBhavcopy, RawFile, decode_bhavcopy_payload,
+    parse_bhavcopy_csv,
+};
+use crate::calendar::{self, TradingCalendar};
+use crate::delivery;
+use crate::http::{HttpConfig, HttpFetcher};
+
+const NSE_SITE_URL: &str = "https://www.nseindia.com";
+const NSE_ARCHIVE_URL: &str = "https://nsearchives.nseindia.com";
 
 pub struct NseClient {
-    client: Client,
+    http: HttpFetcher,
+    calendar: TradingCalendar,
+    /// Serves the landing page and legacy bhavcopies
+    site_url: String,
+    /// Serves UDiFF bhavcopies
+    archive_url: String,
+}
+
+impl Default for NseClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NseClient {
     pub fn new() -> Self {
-        let client = Client::builder()
-            .user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .build()
-            .expect("Failed to create HTTP client");
-        
-        NseClient { client }
+        Self::with_config(HttpConfig::default())
     }
 
-    pub async fn download_bhavcopy(&self, date: NaiveDate) -> Result<String> {
-        let url = self.construct_bhavcopy_url(date)?;
-        info!("Downloading bhavcopy from: {}", url);
+    pub fn with_config(config: HttpConfig) -> Self {
+        Self::with_urls(config, NSE_SITE_URL, NSE_ARCHIVE_URL)
+    }
 
-        let response = self.client
-            .get(&url)
-            .send()
-            .await?;
+    /// Client that fetches every file, and the session cookie, from
+    /// `base_url` instead of the NSE hosts, e.g. a mirror or a test server.
+    pub fn with_base_url(config: HttpConfig, base_url: &str) -> Self {
+        Self::with_urls(config, base_url, base_url)
+    }
+
+    fn with_urls(config: HttpConfig, site_url: &str, archive_url: &str) -> Self {
+        let site_url = site_url.trim_end_matches('/').to_string();
+        // NSE answers 401/403 to downloads without the cookies set by its homepage
+        let http = HttpFetcher::new(config, HeaderMap::new(), Some(format!("{}/", site_url)));
 
-        if !response.status().is_success() {
-            return Err(anyhow!("Failed to download bhavcopy: HTTP {}", response.status()));
+        NseClient {
+            http,
+            calendar: TradingCalendar::bundled("NSE"),
+            site_url,
+            archive_url: archive_url.trim_end_matches('/').to_string(),
         }
+    }
+
+    pub async fn download_bhavcopy(&self, date: NaiveDate) -> Result<String> {
+        let raw = self.fetch_bhavcopy(date).await?;
+        let content = decode_bhavcopy_payload(&raw.body, raw.content_type.as_deref())?;
 
-        let content = response.text().await?;
-        
         if content.trim().is_empty() {
             return Err(anyhow!("Downloaded file is empty"));
         }
 
-        info!("Successfully downloaded bhavcopy for {}", date);
         Ok(content)
     }
 
-    fn construct_bhavcopy_url(&self, date: NaiveDate) -> Result<String> {
-        // NSE bhavcopy URL format: 
-        // https://www.nseindia.com/content/historical/EQUITIES/{year}/{month}/cm{DD}{MMM}{YYYY}bhav.csv.zip
-        // But this might be outdated, let's try the historical data URL first
+    pub async fn fetch_bhavcopy(&self, date: NaiveDate) -> Result<RawFile> {
+        let url = self.construct_bhavcopy_url(date)?;
+        info!("Downloading bhavcopy from: {}", url);
+
+        let response = self.http.get(&url).await?;
+
+        if response.body.is_empty() {
+            return Err(anyhow!("Downloaded file is empty"));
+        }
+
+        info!("Successfully downloaded bhavcopy for {}", date);
+        Ok(RawFile {
+            file_name: url,
+            content_type: response.content_type,
+            body: response.body,
+        })
+    }
+
+    pub fn construct_bhavcopy_url(&self, date: NaiveDate) -> Result<String> {
+        let url = match BhavcopyFormat::for_date(date) {
+            // Legacy format:
+            // https://www.nseindia.com/content/historical/EQUITIES/{YYYY}/{MMM}/cm{DD}{MMM}{YYYY}bhav.csv.zip
+            BhavcopyFormat::Legacy => {
+                let day = format!("{:02}", date.day());
+                let month_name = match date.month() {
+                    1 => "JAN", 2 => "FEB", 3 => "MAR", 4 => "APR", 5 => "MAY", 6 => "JUN",
+                    7 => "JUL", 8 => "AUG", 9 => "SEP", 10 => "OCT", 11 => "NOV", 12 => "DEC",
+                    _ => return Err(anyhow!("Invalid month")),
+                };
+                let year = date.year();
 
-        let day = format!("{:02}", date.day());
-        let month_name = match date.month() {
-            1 => "JAN", 2 => "FEB", 3 => "MAR", 4 => "APR", 5 => "MAY", 6 => "JUN",
-            7 => "JUL", 8 => "AUG", 9 => "SEP", 10 => "OCT", 11 => "NOV", 12 => "DEC",
-            _ => return Err(anyhow!("Invalid month")),
+                format!(
+                    "{}/content/historical/EQUITIES/{}/{}/cm{}{}{}bhav.csv.zip",
+                    self.site_url, year, month_name, day, month_name, year
+                )
+            },
+            // UDiFF format (from the July 2024 cutover):
+            // https://nsearchives.nseindia.com/content/cm/BhavCopy_NSE_CM_0_0_0_{YYYYMMDD}_F_0000.csv.zip
+            BhavcopyFormat::Udiff => format!(
+                "{}/content/cm/BhavCopy_NSE_CM_0_0_0_{}_F_0000.csv.zip",
+                self.archive_url,
+                date.format("%Y%m%d")
+            ),
         };
-        let month = format!("{:02}", date.month());
-        let year = date.year();
-
-        // Try the historical data URL first
-        let url = format!(
-            "https://www.nseindia.com/content/historical/EQUITIES/{}/{}/cm{}{}{}bhav.csv.zip",
-            year, month, day, month_name, year
-        );
 
         Ok(url)
     }
 
-    pub fn get_trading_dates_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
-        let mut dates = Vec::new();
-        let mut current = from;
-
-        while current <= to {
-            // Skip weekends (Saturday = 6, Sunday = 0 in chrono)
-            let weekday = current.weekday();
-            if weekday != chrono::Weekday::Sat && weekday != chrono::Weekday::Sun {
-                dates.push(current);
-            }
-            current = current.succ_opt().unwrap_or(to);
+    pub async fn fetch_delivery_file(&self, date: NaiveDate) -> Result<RawFile> {
+        let url = self.construct_delivery_url(date);
+        info!("Downloading delivery file from: {}", url);
+
+        let response = self.http.get(&url).await?;
+
+        if response.body.is_empty() {
+            return Err(anyhow!("Downloaded file is empty"));
         }
 
-        dates
+        Ok(RawFile {
+            file_name: url,
+            content_type: response.content_type,
+            body: response.body,
+        })
+    }
+
+    /// https://nsearchives.nseindia.com/archives/equities/mto/MTO_{DDMMYYYY}.DAT
+    pub fn construct_delivery_url(&self, date: NaiveDate) -> String {
+        format!("{}/archives/equities/mto/{}", self.archive_url, delivery::mto_file_name(date))
+    }
+
+    pub fn get_trading_dates_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        self.calendar.trading_dates_in_range(from, to)
     }
 
     pub fn get_latest_trading_date(&self) -> NaiveDate {
-        let today = Local::now().date_naive();
-        let mut date = today;
+        self.calendar.trading_day_on_or_before(calendar::exchange_now().date())
+    }
+}
 
-        // Go back until we find a weekday (trading day)
-        while date.weekday() == chrono::Weekday::Sat || date.weekday() == chrono::Weekday::Sun {
-            date = date.pred_opt().unwrap_or(today);
-        }
+#[async_trait]
+impl MarketDataSource for NseClient {
+    fn name(&self) -> &'static str {
+        "nse"
+    }
 
-        date
+    fn exchange(&self) -> &'static str {
+        "NSE"
     }
+
+    fn calendar(&self) -> &TradingCalendar {
+        &self.calendar
+    }
+
+    fn set_calendar(&mut self, calendar: TradingCalendar) {
+        self.calendar = calendar;
+    }
+
+    fn trading_dates_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        self.get_trading_dates_in_range(from, to)
+    }
+
+    fn latest_trading_date(&self) -> NaiveDate {
+        self.get_latest_trading_date()
+    }
+
+    async fn fetch(&self, date: NaiveDate) -> Result<RawFile> {
+        self.fetch_bhavcopy(date).await
+    }
+
+    fn parse(&self, raw: &RawFile, date: NaiveDate) -> Result<ParsedBhavcopy> {
+        let content = decode_bhavcopy_payload(&raw.body, raw.content_type.as_deref())?;
+        parse_csv_data(&content, date)
+    }
+
+    fn delivery_source(&self) -> Option<&'static str> {
+        Some(delivery::DELIVERY_SOURCE)
+    }
+
+    async fn fetch_delivery(&self, date: NaiveDate) -> Result<RawFile> {
+        self.fetch_delivery_file(date).await
+    }
+}
+
+/// First trading day on which NSE published the CM bhavcopy in UDiFF format
+/// only; earlier dates are served in the legacy layout.
+pub fn udiff_cutover_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 7, 8).expect("valid cutover date")
 }
 
-#[derive(Debug, Clone)]
-pub struct StockRecord {
-    pub symbol: String,
-    pub series: String,
-    pub open: f64,
-    pub high: f64,
-    pub low: f64,
-    pub close: f64,
-    pub last: f64,
-    pub prevclose: f64,
-    pub tottrdqty: i64,
-    pub tottrdval: f64,
-    pub timestamp: NaiveDate,
-    pub totaltrades: i32,
-    pub isin: String,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BhavcopyFormat {
+    /// `cm{DD}{MMM}{YYYY}bhav.csv` with SYMBOL, SERIES, OPEN, ... columns
+    Legacy,
+    /// `BhavCopy_NSE_CM_0_0_0_{YYYYMMDD}_F_0000.csv` with TckrSymb, SctySrs, OpnPric, ... columns
+    Udiff,
 }
 
-impl StockRecord {
-    pub fn from_csv_record(record: &csv::StringRecord, date: NaiveDate) -> Result<Self> {
-        // Expected CSV columns: SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN
-        if record.len() < 13 {
-            return Err(anyhow!("CSV record has insufficient columns: {}", record.len()));
+impl BhavcopyFormat {
+    pub fn for_date(date: NaiveDate) -> Self {
+        if date >= udiff_cutover_date() {
+            BhavcopyFormat::Udiff
+        } else {
+            BhavcopyFormat::Legacy
         }
+    }
 
-        Ok(StockRecord {
-            symbol: record.get(0).unwrap_or("").to_string(),
-            series: record.get(1).unwrap_or("").to_string(),
-            open: record.get(2).unwrap_or("0").parse().unwrap_or(0.0),
-            high: record.get(3).unwrap_or("0").parse().unwrap_or(0.0),
-            low: record.get(4).unwrap_or("0").parse().unwrap_or(0.0),
-            close: record.get(5).unwrap_or("0").parse().unwrap_or(0.0),
-            last: record.get(6).unwrap_or("0").parse().unwrap_or(0.0),
-            prevclose: record.get(7).unwrap_or("0").parse().unwrap_or(0.0),
-            tottrdqty: record.get(8).unwrap_or("0").parse().unwrap_or(0),
-            tottrdval: record.get(9).unwrap_or("0").parse().unwrap_or(0.0),
-            timestamp: date, // Use the passed date instead of parsing from CSV
-            totaltrades: record.get(11).unwrap_or("0").parse().unwrap_or(0),
-            isin: record.get(12).unwrap_or("").to_string(),
-        })
+    pub fn detect(headers: &csv::StringRecord) -> Option<Self> {
+        if headers.iter().any(|h| h == "TckrSymb") {
+            Some(BhavcopyFormat::Udiff)
+        } else if headers.iter().any(|h| h == "SYMBOL") {
+            Some(BhavcopyFormat::Legacy)
+        } else {
+            None
+        }
     }
-}
 
-pub fn parse_csv_data(csv_content: &str, date: NaiveDate) -> Result<Vec<StockRecord>> {
-    let mut reader = csv::Reader::from_reader(Cursor::new(csv_content));
-    let mut records = Vec::new();
-    
-    for result in reader.records() {
-        match result {
-            Ok(record) => {
-                match StockRecord::from_csv_record(&record, date) {
-                    Ok(stock_record) => {
-                        // Filter out invalid records (empty symbols, etc.)
-                        if !stock_record.symbol.is_empty() && stock_record.symbol != "-" {
-                            records.push(stock_record);
-                        }
-                    },
-                    Err(e) => {
-                        warn!("Skipping invalid record: {}", e);
-                    }
-                }
+    fn column_names(&self) -> &'static ColumnNames {
+        match self {
+            BhavcopyFormat::Legacy => &ColumnNames {
+                symbol: "SYMBOL",
+                series: "SERIES",
+                open: "OPEN",
+                high: "HIGH",
+                low: "LOW",
+                close: "CLOSE",
+                last: "LAST",
+                prevclose: "PREVCLOSE",
+                tottrdqty: "TOTTRDQTY",
+                tottrdval: "TOTTRDVAL",
+                totaltrades: "TOTALTRADES",
+                isin: "ISIN",
+                timestamp: "TIMESTAMP",
+            },
+            BhavcopyFormat::Udiff => &ColumnNames {
+                symbol: "TckrSymb",
+                series: "SctySrs",
+                open: "OpnPric",
+                high: "HghPric",
+                low: "LwPric",
+                close: "ClsPric",
+                last: "LastPric",
+                prevclose: "PrvsClsgPric",
+                tottrdqty: "TtlTradgVol",
+                tottrdval: "TtlTrfVal",
+                totaltrades: "TtlNbOfTxsExctd",
+                isin: "ISIN",
+                timestamp: "TradDt",
             },
-            Err(e) => {
-                warn!("Error reading CSV record: {}", e);
-            }
         }
     }
-    
-    info!("Parsed {} stock records for {}", records.len(), date);
-    Ok(records)
+}
+
+pub fn parse_csv_data(csv_content: &str, date: NaiveDate) -> Result<ParsedBhavcopy> {
+    parse_bhavcopy_csv(csv_content, date, "NSE", |headers| {
+        let format = BhavcopyFormat::detect(headers)
+            .ok_or_else(|| anyhow!("Unrecognised bhavcopy header: {:?}", headers))?;
+        info!("Detected {:?} NSE bhavcopy format for {}", format, date);
+        Ok(ColumnMap::resolve(format.column_names(), headers))
+    })
 }
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+
+use crate::calendar::TradingCalendar;
+
+/// Face-value splits seen on Indian exchanges: Rs 10 to Rs 5, 2 or 1 and
+/// Rs 5 or 2 to Rs 1. Their inverses are consolidations.
+const SPLIT_RATIOS: [f64; 3] = [2.0, 5.0, 10.0];
+
+/// Largest bonus terms tried, as in "5:1" (five new shares per one held).
+const MAX_BONUS_TERM: u32 = 5;
+
+/// One stored day of a listing, in the order the reconciliation walks them.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ClosingPrice {
+    pub company_id: i64,
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub isin: Option<String>,
+    pub trade_date: NaiveDate,
+    pub close_price: f64,
+    pub prev_close: f64,
+}
+
+/// Likely reason PREVCLOSE disagrees with the stored close.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakCause {
+    /// The previous close was divided by a face-value split ratio (or
+    /// multiplied, for a consolidation)
+    Split,
+    /// The previous close was adjusted by a bonus issue's ratio
+    Bonus,
+    /// Trading days between the two stored closes have no stored price, so
+    /// PREVCLOSE refers to a close that was never ingested
+    MissingDay,
+    /// No corporate action or gap explains the difference
+    DataError,
+}
+
+impl BreakCause {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakCause::Split => "split",
+            BreakCause::Bonus => "bonus",
+            BreakCause::MissingDay => "missing_day",
+            BreakCause::DataError => "data_error",
+        }
+    }
+}
+
+impl fmt::Display for BreakCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A day whose PREVCLOSE does not match the listing's last stored close.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContinuityBreak {
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub isin: Option<String>,
+    /// Date of the last stored close before `trade_date`
+    pub previous_date: NaiveDate,
+    pub stored_close: f64,
+    pub trade_date: NaiveDate,
+    /// PREVCLOSE as reported in `trade_date`'s bhavcopy
+    pub reported_prev_close: f64,
+    /// `stored_close / reported_prev_close`
+    pub ratio: Option<f64>,
+    pub cause: BreakCause,
+    pub detail: String,
+}
+
+/// Compares each stored day's PREVCLOSE with the same listing's previous
+/// stored close. `prices` must be ordered by company and trade date; the
+/// first day of each listing is only used as the baseline. Breaks are
+/// returned by trade date.
+pub fn find_breaks(
+    prices: &[ClosingPrice],
+    calendars: &HashMap<String, TradingCalendar>,
+    tolerance: f64,
+) -> Vec<ContinuityBreak> {
+    let weekends_only = TradingCalendar::weekends_only();
+    let mut breaks = Vec::new();
+
+    for pair in prices.windows(2) {
+        let (previous, current) = (&pair[0], &pair[1]);
+        if previous.company_id != current.company_id {
+            continue;
+        }
+        if (current.prev_close - previous.close_price).abs() <= tolerance {
+            continue;
+        }
+
+        let calendar = calendars.get(&current.exchange).unwrap_or(&weekends_only);
+        let (cause, detail) = classify(previous, current, calendar);
+        breaks.push(ContinuityBreak {
+            exchange: current.exchange.clone(),
+            symbol: current.symbol.clone(),
+            series: current.series.clone(),
+            isin: current.isin.clone(),
+            previous_date: previous.trade_date,
+            stored_close: previous.close_price,
+            trade_date: current.trade_date,
+            reported_prev_close: current.prev_close,
+            ratio: ratio(previous.close_price, current.prev_close),
+            cause,
+            detail,
+        });
+    }
+
+    breaks.sort_by(|a, b| {
+        (a.trade_date, &a.exchange, &a.symbol, &a.series).cmp(&(b.trade_date, &b.exchange, &b.symbol, &b.series))
+    });
+    breaks
+}
+
+fn ratio(stored_close: f64, reported_prev_close: f64) -> Option<f64> {
+    (reported_prev_close > 0.0).then(|| stored_close / reported_prev_close)
+}
+
+fn classify(previous: &ClosingPrice, current: &ClosingPrice, calendar: &TradingCalendar) -> (BreakCause, String) {
+    if let Some(action) = match_corporate_action(previous.close_price, current.prev_close) {
+        return action;
+    }
+
+    let skipped = match (previous.trade_date.succ_opt(), current.trade_date.pred_opt()) {
+        (Some(after), Some(before)) if after <= before => calendar.trading_dates_in_range(after, before),
+        _ => Vec::new(),
+    };
+    if !skipped.is_empty() {
+        let dates: Vec<String> = skipped.iter().take(5).map(|date| date.to_string()).collect();
+        let more = if skipped.len() > 5 { ", ..." } else { "" };
+        return (
+            BreakCause::MissingDay,
+            format!("{} trading days without a stored price ({}{})", skipped.len(), dates.join(", "), more),
+        );
+    }
+
+    (
+        BreakCause::DataError,
+        format!(
+            "PREVCLOSE {} differs from the stored close {} by {:+.2}",
+            current.prev_close,
+            previous.close_price,
+            current.prev_close - previous.close_price
+        ),
+    )
+}
+
+/// Whether the stored close, divided by a split or bonus ratio, gives the
+/// reported PREVCLOSE to within the price tick or 0.5%.
+fn match_corporate_action(stored_close: f64, reported_prev_close: f64) -> Option<(BreakCause, String)> {
+    if stored_close <= 0.0 || reported_prev_close <= 0.0 {
+        return None;
+    }
+    let matches = |ratio: f64| {
+        let expected = stored_close / ratio;
+        (expected - reported_prev_close).abs() <= (0.05f64).max(expected * 0.005)
+    };
+
+    let bonus = (1..=MAX_BONUS_TERM)
+        .flat_map(|new| (1..=MAX_BONUS_TERM).map(move |held| (new, held)))
+        .find(|(new, held)| matches(f64::from(new + held) / f64::from(*held)));
+
+    for ratio in SPLIT_RATIOS {
+        if matches(ratio) {
+            let also = bonus
+                .map(|(new, held)| format!(", or a {}:{} bonus", new, held))
+                .unwrap_or_default();
+            return Some((BreakCause::Split, format!("Consistent with a 1:{} split{}", ratio, also)));
+        }
+        if matches(1.0 / ratio) {
+            return Some((BreakCause::Split, format!("Consistent with a {}:1 consolidation", ratio)));
+        }
+    }
+
+    bonus.map(|(new, held)| (BreakCause::Bonus, format!("Consistent with a {}:{} bonus", new, held)))
+}
+
+const CSV_HEADER: [&str; 11] = [
+    "exchange",
+    "symbol",
+    "series",
+    "isin",
+    "previous_date",
+    "stored_close",
+    "trade_date",
+    "reported_prev_close",
+    "ratio",
+    "cause",
+    "detail",
+];
+
+/// Writes `breaks` as CSV. The header row is written even when there are
+/// no breaks.
+pub fn write_csv(breaks: &[ContinuityBreak], writer: impl Write) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(writer);
+    writer.write_record(CSV_HEADER)?;
+    for continuity_break in breaks {
+        writer.serialize(continuity_break)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `breaks` as a pretty-printed JSON array.
+pub fn write_json(breaks: &[ContinuityBreak], mut writer: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut writer, breaks)?;
+    writeln!(writer)?;
+    Ok(())
+}
@@ -0,0 +1,552 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Cursor, Read};
+use tracing::{info, warn};
+
+use crate::bse::BseClient;
+use crate::calendar::{self, TradingCalendar};
+use crate::http::HttpConfig;
+use crate::nse::NseClient;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// The file is for a different trade date than the one requested, e.g.
+/// because the exchange served its last available file instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleFile {
+    pub requested: NaiveDate,
+    pub found: NaiveDate,
+}
+
+impl fmt::Display for StaleFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stale file: requested {} but the file is for {}",
+            self.requested, self.found
+        )
+    }
+}
+
+impl std::error::Error for StaleFile {}
+
+/// A file downloaded from a market data source, before it is parsed.
+#[derive(Debug, Clone)]
+pub struct RawFile {
+    /// Where the file came from, recorded as `ingestion_log.file_name`
+    pub file_name: String,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[async_trait]
+pub trait MarketDataSource: Send + Sync {
+    /// Name used for `ingest --source` and recorded as `ingestion_log.source`
+    fn name(&self) -> &'static str;
+
+    /// Exchange code the records are stored under, e.g. `NSE`
+    fn exchange(&self) -> &'static str;
+
+    fn calendar(&self) -> &TradingCalendar;
+
+    /// Replaces the bundled holiday calendar, e.g. with one that includes
+    /// holidays imported into the database.
+    fn set_calendar(&mut self, calendar: TradingCalendar);
+
+    fn trading_dates_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        self.calendar().trading_dates_in_range(from, to)
+    }
+
+    /// The current or most recent trading day in Asia/Kolkata.
+    fn latest_trading_date(&self) -> NaiveDate {
+        self.calendar().trading_day_on_or_before(calendar::exchange_now().date())
+    }
+
+    /// The most recent trading day whose file should be published by `now`
+    /// (IST), given the time of day the source publishes it.
+    fn latest_published_date(&self, now: NaiveDateTime, cutoff: NaiveTime) -> NaiveDate {
+        self.calendar().latest_published_date(now, cutoff)
+    }
+
+    async fn fetch(&self, date: NaiveDate) -> Result<RawFile>;
+
+    fn parse(&self, raw: &RawFile, date: NaiveDate) -> Result<ParsedBhavcopy>;
+
+    /// Name the source's delivery position files are archived and logged
+    /// under, if it publishes them.
+    fn delivery_source(&self) -> Option<&'static str> {
+        None
+    }
+
+    async fn fetch_delivery(&self, _date: NaiveDate) -> Result<RawFile> {
+        Err(anyhow!("{} does not publish delivery data", self.name()))
+    }
+}
+
+type SourceConstructor = fn(HttpConfig) -> Box<dyn MarketDataSource>;
+
+/// Every source that `ingest --source` can dispatch to.
+const SOURCES: &[(&str, SourceConstructor)] = &[
+    ("nse", |config| Box::new(NseClient::with_config(config))),
+    ("bse", |config| Box::new(BseClient::with_config(config))),
+];
+
+pub fn available_sources() -> Vec<&'static str> {
+    SOURCES.iter().map(|(name, _)| *name).collect()
+}
+
+pub fn create_source(name: &str) -> Result<Box<dyn MarketDataSource>> {
+    create_source_with_config(name, HttpConfig::default())
+}
+
+pub fn create_source_with_config(name: &str, config: HttpConfig) -> Result<Box<dyn MarketDataSource>> {
+    let name = name.to_ascii_lowercase();
+    SOURCES
+        .iter()
+        .find(|(source_name, _)| *source_name == name)
+        .map(|(_, constructor)| constructor(config))
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown source '{}'. Available sources: {}",
+                name,
+                available_sources().join(", ")
+            )
+        })
+}
+
+/// Restricts which series are kept from a bhavcopy; an empty filter keeps
+/// every series.
+#[derive(Debug, Clone, Default)]
+pub struct SeriesFilter {
+    series: HashSet<String>,
+}
+
+impl SeriesFilter {
+    pub fn new<I, S>(series: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        SeriesFilter {
+            series: series
+                .into_iter()
+                .map(|s| s.as_ref().trim().to_ascii_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.series.is_empty()
+    }
+
+    /// The selected series, upper case, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.series.iter().map(|s| s.as_str())
+    }
+
+    pub fn matches(&self, series: &str) -> bool {
+        self.series.is_empty() || self.series.contains(&series.to_ascii_uppercase())
+    }
+
+    /// The filter as recorded in `ingestion_log.series_filter`: the series in
+    /// order and comma separated, or `None` for every series.
+    pub fn to_log_value(&self) -> Option<String> {
+        if self.series.is_empty() {
+            return None;
+        }
+        let mut series: Vec<&str> = self.iter().collect();
+        series.sort();
+        Some(series.join(","))
+    }
+
+    /// Whether a run logged with `logged` (see [`Self::to_log_value`])
+    /// stored every series this filter selects.
+    pub fn is_covered_by(&self, logged: Option<&str>) -> bool {
+        match logged {
+            None => true,
+            Some(_) if self.series.is_empty() => false,
+            Some(logged) => {
+                let logged: HashSet<&str> = logged.split(',').collect();
+                self.series.iter().all(|series| logged.contains(series.as_str()))
+            },
+        }
+    }
+
+    /// Drops records outside the filter and returns how many were dropped.
+    pub fn retain(&self, records: &mut Vec<StockRecord>) -> usize {
+        let before = records.len();
+        records.retain(|record| self.matches(&record.series));
+        before - records.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StockRecord {
+    /// Exchange the record was traded on, e.g. "NSE" or "BSE"
+    pub exchange: String,
+    /// Trading symbol; BSE scrips are identified by their numeric scrip code
+    pub symbol: String,
+    pub series: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub last: f64,
+    pub prevclose: f64,
+    pub tottrdqty: i64,
+    pub tottrdval: f64,
+    pub timestamp: NaiveDate,
+    pub totaltrades: i32,
+    pub isin: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldErrorKind {
+    /// The header row has no column with this name
+    MissingColumn,
+    /// The row is shorter than the header, or the value is blank
+    MissingValue,
+    /// The value could not be parsed as a number
+    InvalidNumber(String),
+    /// The value could not be parsed as a trade date
+    InvalidDate(String),
+    /// The row itself could not be read as CSV
+    MalformedRow(String),
+}
+
+impl fmt::Display for FieldErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldErrorKind::MissingColumn => write!(f, "missing column"),
+            FieldErrorKind::MissingValue => write!(f, "missing value"),
+            FieldErrorKind::InvalidNumber(e) => write!(f, "invalid number: {}", e),
+            FieldErrorKind::InvalidDate(e) => write!(f, "invalid date: {}", e),
+            FieldErrorKind::MalformedRow(e) => write!(f, "malformed row: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub column: String,
+    pub raw_value: String,
+    pub kind: FieldErrorKind,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?}): {}", self.column, self.raw_value, self.kind)
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+/// A bhavcopy row that was not converted into a `StockRecord`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    pub line: u64,
+    pub column: String,
+    pub raw_value: String,
+    pub reason: FieldErrorKind,
+}
+
+impl RejectedRow {
+    pub(crate) fn new(line: u64, error: FieldError) -> Self {
+        RejectedRow {
+            line,
+            column: error.column,
+            raw_value: error.raw_value,
+            reason: error.kind,
+        }
+    }
+}
+
+impl fmt::Display for RejectedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} ({:?}): {}", self.line, self.column, self.raw_value, self.reason)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedBhavcopy {
+    pub records: Vec<StockRecord>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// Header names of the `StockRecord` fields in one bhavcopy layout.
+#[derive(Debug)]
+pub struct ColumnNames {
+    pub symbol: &'static str,
+    pub series: &'static str,
+    pub open: &'static str,
+    pub high: &'static str,
+    pub low: &'static str,
+    pub close: &'static str,
+    pub last: &'static str,
+    pub prevclose: &'static str,
+    pub tottrdqty: &'static str,
+    pub tottrdval: &'static str,
+    pub totaltrades: &'static str,
+    pub isin: &'static str,
+    /// Trade date of the row, e.g. `TIMESTAMP` or `TradDt`
+    pub timestamp: &'static str,
+}
+
+/// Column positions resolved from a bhavcopy header row. A column that is
+/// missing from the header resolves to `None`.
+#[derive(Debug, Clone)]
+pub struct ColumnMap {
+    names: &'static ColumnNames,
+    symbol: Option<usize>,
+    series: Option<usize>,
+    open: Option<usize>,
+    high: Option<usize>,
+    low: Option<usize>,
+    close: Option<usize>,
+    last: Option<usize>,
+    prevclose: Option<usize>,
+    tottrdqty: Option<usize>,
+    tottrdval: Option<usize>,
+    totaltrades: Option<usize>,
+    isin: Option<usize>,
+    timestamp: Option<usize>,
+}
+
+impl ColumnMap {
+    pub fn resolve(names: &'static ColumnNames, headers: &csv::StringRecord) -> Self {
+        let position = |name: &str| headers.iter().position(|h| h == name);
+
+        ColumnMap {
+            names,
+            symbol: position(names.symbol),
+            series: position(names.series),
+            open: position(names.open),
+            high: position(names.high),
+            low: position(names.low),
+            close: position(names.close),
+            last: position(names.last),
+            prevclose: position(names.prevclose),
+            tottrdqty: position(names.tottrdqty),
+            tottrdval: position(names.tottrdval),
+            totaltrades: position(names.totaltrades),
+            isin: position(names.isin),
+            timestamp: position(names.timestamp),
+        }
+    }
+}
+
+impl StockRecord {
+    pub fn from_csv_record(
+        record: &csv::StringRecord,
+        columns: &ColumnMap,
+        exchange: &str,
+        date: NaiveDate,
+    ) -> std::result::Result<Self, FieldError> {
+        let names = columns.names;
+        let field = |index: Option<usize>, name: &str| -> std::result::Result<&str, FieldError> {
+            let error = |kind| FieldError {
+                column: name.to_string(),
+                raw_value: String::new(),
+                kind,
+            };
+            let index = index.ok_or_else(|| error(FieldErrorKind::MissingColumn))?;
+            record.get(index).ok_or_else(|| error(FieldErrorKind::MissingValue))
+        };
+
+        Ok(StockRecord {
+            exchange: exchange.to_string(),
+            symbol: field(columns.symbol, names.symbol)?.to_string(),
+            series: field(columns.series, names.series)?.to_string(),
+            open: parse_field(field(columns.open, names.open)?, names.open, parse_decimal)?,
+            high: parse_field(field(columns.high, names.high)?, names.high, parse_decimal)?,
+            low: parse_field(field(columns.low, names.low)?, names.low, parse_decimal)?,
+            close: parse_field(field(columns.close, names.close)?, names.close, parse_decimal)?,
+            last: parse_field(field(columns.last, names.last)?, names.last, parse_decimal)?,
+            prevclose: parse_field(field(columns.prevclose, names.prevclose)?, names.prevclose, parse_decimal)?,
+            tottrdqty: parse_field(field(columns.tottrdqty, names.tottrdqty)?, names.tottrdqty, parse_integer)?,
+            tottrdval: parse_field(field(columns.tottrdval, names.tottrdval)?, names.tottrdval, parse_decimal)?,
+            // Files without a date column are taken to be for the requested date
+            timestamp: match columns.timestamp {
+                Some(_) => parse_date_field(field(columns.timestamp, names.timestamp)?, names.timestamp)?,
+                None => date,
+            },
+            totaltrades: parse_field(field(columns.totaltrades, names.totaltrades)?, names.totaltrades, parse_integer)?,
+            // Older bhavcopies (e.g. BSE's pre-ISIN EQ files) carry no ISIN column at all
+            isin: match columns.isin {
+                Some(_) => field(columns.isin, names.isin)?.to_string(),
+                None => String::new(),
+            },
+        })
+    }
+}
+
+/// Parses a bhavcopy CSV into records for `exchange`, resolving the column
+/// layout from its header row with `resolve_columns`.
+pub fn parse_bhavcopy_csv(
+    csv_content: &str,
+    date: NaiveDate,
+    exchange: &str,
+    resolve_columns: impl Fn(&csv::StringRecord) -> Result<ColumnMap>,
+) -> Result<ParsedBhavcopy> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(Cursor::new(csv_content));
+    let mut parsed = ParsedBhavcopy::default();
+
+    let headers = reader.headers()?.clone();
+    if headers.is_empty() {
+        info!("{} bhavcopy for {} has no header row", exchange, date);
+        return Ok(parsed);
+    }
+    let columns = resolve_columns(&headers)?;
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or(0);
+                let rejected = RejectedRow::new(line, FieldError {
+                    column: String::new(),
+                    raw_value: String::new(),
+                    kind: FieldErrorKind::MalformedRow(e.to_string()),
+                });
+                warn!("Rejected bhavcopy row: {}", rejected);
+                parsed.rejected.push(rejected);
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        match StockRecord::from_csv_record(&record, &columns, exchange, date) {
+            Ok(stock_record) => {
+                // Filter out summary rows (empty symbols, etc.)
+                if !stock_record.symbol.is_empty() && stock_record.symbol != "-" {
+                    parsed.records.push(stock_record);
+                }
+            },
+            Err(e) => {
+                let rejected = RejectedRow::new(line, e);
+                warn!("Rejected bhavcopy row: {}", rejected);
+                parsed.rejected.push(rejected);
+            }
+        }
+    }
+
+    if let Some(record) = parsed.records.iter().find(|record| record.timestamp != date) {
+        return Err(StaleFile {
+            requested: date,
+            found: record.timestamp,
+        }
+        .into());
+    }
+
+    info!(
+        "Parsed {} {} stock records for {} ({} rejected)",
+        parsed.records.len(),
+        exchange,
+        date,
+        parsed.rejected.len()
+    );
+    Ok(parsed)
+}
+
+/// Returns the CSV text of a bhavcopy payload, extracting it first when the
+/// payload is a ZIP archive. Plain CSV bodies are passed through unchanged.
+pub fn decode_bhavcopy_payload(body: &[u8], content_type: Option<&str>) -> Result<String> {
+    let is_zip_content_type = content_type
+        .map(|ct| ct.contains("zip"))
+        .unwrap_or(false);
+
+    if body.starts_with(ZIP_MAGIC) || is_zip_content_type {
+        return extract_csv_from_zip(body);
+    }
+
+    String::from_utf8(body.to_vec())
+        .map_err(|e| anyhow!("Bhavcopy payload is not valid UTF-8 text: {}", e))
+}
+
+pub fn extract_csv_from_zip(body: &[u8]) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(body))
+        .map_err(|e| anyhow!("Failed to open bhavcopy archive: {}", e))?;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if !file.is_file() || !file.name().to_ascii_lowercase().ends_with(".csv") {
+            continue;
+        }
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .map_err(|e| anyhow!("Failed to read {} from bhavcopy archive: {}", file.name(), e))?;
+        info!("Extracted {} ({} bytes) from bhavcopy archive", file.name(), content.len());
+        return Ok(content);
+    }
+
+    Err(anyhow!("Bhavcopy archive does not contain a CSV file"))
+}
+
+
+pub(crate) fn parse_decimal(raw: &str) -> std::result::Result<f64, String> {
+    let value = raw.parse::<f64>().map_err(|e| e.to_string())?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{} is not a finite number", raw))
+    }
+}
+
+pub(crate) fn parse_integer<T: TryFrom<i64>>(raw: &str) -> std::result::Result<T, String> {
+    // Quantities are occasionally published with a trailing ".00"
+    let value = raw.parse::<i64>().or_else(|e| match raw.parse::<f64>() {
+        Ok(value) if value.fract() == 0.0 => Ok(value as i64),
+        _ => Err(e.to_string()),
+    })?;
+    T::try_from(value).map_err(|_| format!("{} is out of range", value))
+}
+
+/// Date formats used by the exchanges: `2025-01-15` (UDiFF), `15-Jan-25`
+/// (BSE legacy) and `15-JAN-2025` (NSE legacy). `%Y` would also accept a
+/// two-digit year, so `%y` has to be tried first.
+const TRADE_DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d-%b-%y", "%d-%b-%Y"];
+
+pub fn parse_trade_date(raw: &str) -> Option<NaiveDate> {
+    TRADE_DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(raw, format).ok())
+}
+
+fn parse_date_field(raw: &str, column: &str) -> std::result::Result<NaiveDate, FieldError> {
+    let error = |kind| FieldError {
+        column: column.to_string(),
+        raw_value: raw.to_string(),
+        kind,
+    };
+
+    if raw.is_empty() {
+        return Err(error(FieldErrorKind::MissingValue));
+    }
+    parse_trade_date(raw)
+        .ok_or_else(|| error(FieldErrorKind::InvalidDate(format!("{} is not a recognised date", raw))))
+}
+
+pub(crate) fn parse_field<T>(
+    raw: &str,
+    column: &str,
+    parse: fn(&str) -> std::result::Result<T, String>,
+) -> std::result::Result<T, FieldError> {
+    let error = |kind| FieldError {
+        column: column.to_string(),
+        raw_value: raw.to_string(),
+        kind,
+    };
+
+    if raw.is_empty() {
+        return Err(error(FieldErrorKind::MissingValue));
+    }
+    parse(raw).map_err(|e| error(FieldErrorKind::InvalidNumber(e)))
+}
@@ -0,0 +1,123 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::io::Cursor;
+
+use crate::corporate_actions::parse_action_date;
+
+/// A ticker change from NSE's symbol change file: the listing traded as
+/// `old_symbol` until the day before `effective_date`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolChange {
+    pub exchange: String,
+    pub company_name: Option<String>,
+    pub old_symbol: String,
+    pub new_symbol: String,
+    pub effective_date: NaiveDate,
+}
+
+/// What importing a symbol change file did.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SymbolChangeSummary {
+    /// Changes that matched a stored listing
+    pub applied: usize,
+    /// Listings stored separately under the new symbol that were merged
+    /// into the listing of the old one
+    pub merged: usize,
+    /// Changes for symbols that have never been ingested
+    pub unmatched: usize,
+}
+
+/// One symbol and ISIN a listing traded under, and when.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct SymbolHistoryEntry {
+    pub company_id: i64,
+    pub exchange: String,
+    pub symbol: String,
+    pub series: String,
+    pub isin: String,
+    /// First trade date under this symbol and ISIN; `None` if unknown
+    pub valid_from: Option<NaiveDate>,
+    /// Last trade date; `None` while it is the listing's current symbol
+    pub valid_to: Option<NaiveDate>,
+    /// `bhavcopy` or `symbol_change`
+    pub source: String,
+}
+
+impl SymbolHistoryEntry {
+    pub fn is_current(&self) -> bool {
+        self.valid_to.is_none()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolChangeRow {
+    #[serde(default, alias = "SM_NAME", alias = "COMPANY NAME", alias = "Company Name")]
+    company_name: Option<String>,
+    #[serde(alias = "SM_KEY_SYMBOL", alias = "OLD SYMBOL", alias = "Old Symbol")]
+    old_symbol: String,
+    #[serde(alias = "SM_NEW_SYMBOL", alias = "NEW SYMBOL", alias = "New Symbol")]
+    new_symbol: String,
+    #[serde(alias = "SM_APPLICABLE_FROM", alias = "DATE OF CHANGE", alias = "Date of Change")]
+    effective_date: String,
+    #[serde(default, alias = "EXCHANGE")]
+    exchange: Option<String>,
+}
+
+impl SymbolChangeRow {
+    fn into_change(self, default_exchange: &str) -> Result<SymbolChange> {
+        let old_symbol = self.old_symbol.trim().to_ascii_uppercase();
+        let new_symbol = self.new_symbol.trim().to_ascii_uppercase();
+        if old_symbol.is_empty() || new_symbol.is_empty() {
+            return Err(anyhow!("Missing old or new symbol"));
+        }
+        let exchange = self
+            .exchange
+            .map(|exchange| exchange.trim().to_ascii_uppercase())
+            .filter(|exchange| !exchange.is_empty())
+            .unwrap_or_else(|| default_exchange.to_string());
+        Ok(SymbolChange {
+            exchange,
+            company_name: self
+                .company_name
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty()),
+            old_symbol,
+            new_symbol,
+            effective_date: parse_action_date(&self.effective_date)?,
+        })
+    }
+}
+
+/// Parses NSE's `symbolchange.csv` (`SM_NAME`, `SM_KEY_SYMBOL`,
+/// `SM_NEW_SYMBOL`, `SM_APPLICABLE_FROM`), with or without its header row,
+/// or the same columns named `company_name,old_symbol,new_symbol,effective_date`.
+/// Changes are returned by effective date, so chained renames apply in order.
+pub fn parse_symbol_changes_csv(content: &str, default_exchange: &str) -> Result<Vec<SymbolChange>> {
+    let has_header = content
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .and_then(|line| line.rsplit(',').next())
+        .is_some_and(|last| parse_action_date(last.trim().trim_matches('"')).is_err());
+    let content = if has_header {
+        content.to_string()
+    } else {
+        format!("company_name,old_symbol,new_symbol,effective_date\n{}", content)
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(Cursor::new(content));
+
+    let first_row = if has_header { 2 } else { 1 };
+    let mut changes = Vec::new();
+    for (index, row) in reader.deserialize::<SymbolChangeRow>().enumerate() {
+        let change = row
+            .map_err(anyhow::Error::from)
+            .and_then(|row| row.into_change(default_exchange))
+            .map_err(|e| anyhow!("Symbol change row {}: {}", index + first_row, e))?;
+        changes.push(change);
+    }
+    changes.sort_by_key(|change| change.effective_date);
+    Ok(changes)
+}
@@ -0,0 +1,286 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::source::StockRecord;
+
+/// A check run on every parsed record before it is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// Open, high, low, close and last prices are above zero
+    PositivePrices,
+    /// Low <= open, close, last <= high
+    PriceRange,
+    /// Traded quantity, traded value and number of trades are not negative
+    NonNegativeVolume,
+    /// Close is within `max_close_change_pct` of the previous close
+    CloseChange,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 4] = [
+        Rule::PositivePrices,
+        Rule::PriceRange,
+        Rule::NonNegativeVolume,
+        Rule::CloseChange,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rule::PositivePrices => "positive_prices",
+            Rule::PriceRange => "price_range",
+            Rule::NonNegativeVolume => "non_negative_volume",
+            Rule::CloseChange => "close_change",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim().to_ascii_lowercase().replace('-', "_");
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.as_str() == name)
+            .ok_or_else(|| anyhow!("Unknown validation rule '{}'", s.trim()))
+    }
+}
+
+/// What a rule does to a record that breaks it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Off,
+    /// Store the record and record an issue
+    Warn,
+    /// Drop the record and record an issue
+    Reject,
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "ignore" => Ok(Action::Off),
+            "warn" | "warning" => Ok(Action::Warn),
+            "reject" => Ok(Action::Reject),
+            other => Err(anyhow!("Unknown validation action '{}', expected off, warn or reject", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Rejection,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Rejection => "rejection",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "warning" | "warn" => Ok(Severity::Warning),
+            "rejection" | "reject" | "rejected" => Ok(Severity::Rejection),
+            other => Err(anyhow!("Unknown severity '{}', expected warning or rejection", other)),
+        }
+    }
+}
+
+/// Which rules run and whether they warn or reject.
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    actions: HashMap<Rule, Action>,
+    /// Largest move from the previous close, in percent, before
+    /// [`Rule::CloseChange`] fires
+    pub max_close_change_pct: f64,
+}
+
+impl Default for ValidationConfig {
+    /// Impossible prices and volumes are rejected; large moves, which can be
+    /// genuine (results, splits, new listings), only warn.
+    fn default() -> Self {
+        let actions = HashMap::from([
+            (Rule::PositivePrices, Action::Reject),
+            (Rule::PriceRange, Action::Reject),
+            (Rule::NonNegativeVolume, Action::Reject),
+            (Rule::CloseChange, Action::Warn),
+        ]);
+        ValidationConfig {
+            actions,
+            max_close_change_pct: 20.0,
+        }
+    }
+}
+
+impl ValidationConfig {
+    pub fn action(&self, rule: Rule) -> Action {
+        self.actions.get(&rule).copied().unwrap_or(Action::Off)
+    }
+
+    pub fn set_action(&mut self, rule: Rule, action: Action) {
+        self.actions.insert(rule, action);
+    }
+}
+
+/// Parses a `rule=action` override such as `close_change=reject`.
+pub fn parse_rule_setting(value: &str) -> Result<(Rule, Action)> {
+    let (rule, action) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid rule setting '{}', expected RULE=off|warn|reject", value))?;
+    Ok((rule.parse()?, action.parse()?))
+}
+
+/// A record that broke a validation rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityIssue {
+    pub exchange: String,
+    pub trade_date: NaiveDate,
+    pub symbol: String,
+    pub series: String,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for QualityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} [{}] {}", self.symbol, self.series, self.rule, self.message)
+    }
+}
+
+/// Records that passed validation and the issues found on the way.
+#[derive(Debug, Default)]
+pub struct Validated {
+    pub records: Vec<StockRecord>,
+    /// Number of records dropped by a rejecting rule
+    pub rejected: usize,
+    pub issues: Vec<QualityIssue>,
+}
+
+impl Validated {
+    pub fn warnings(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .count()
+    }
+}
+
+/// Runs every enabled rule over `records`, dropping those that break a
+/// rejecting rule.
+pub fn validate_records(records: Vec<StockRecord>, config: &ValidationConfig) -> Validated {
+    let mut validated = Validated::default();
+    for record in records {
+        let mut rejected = false;
+        for rule in Rule::ALL {
+            let severity = match config.action(rule) {
+                Action::Off => continue,
+                Action::Warn => Severity::Warning,
+                Action::Reject => Severity::Rejection,
+            };
+            if let Some(message) = check(rule, &record, config) {
+                rejected |= severity == Severity::Rejection;
+                validated.issues.push(QualityIssue {
+                    exchange: record.exchange.clone(),
+                    trade_date: record.timestamp,
+                    symbol: record.symbol.clone(),
+                    series: record.series.clone(),
+                    rule,
+                    severity,
+                    message,
+                });
+            }
+        }
+        if rejected {
+            validated.rejected += 1;
+        } else {
+            validated.records.push(record);
+        }
+    }
+    validated
+}
+
+/// Describes how `record` breaks `rule`, or `None` if it does not.
+fn check(rule: Rule, record: &StockRecord, config: &ValidationConfig) -> Option<String> {
+    match rule {
+        Rule::PositivePrices => {
+            let prices = [
+                ("open", record.open),
+                ("high", record.high),
+                ("low", record.low),
+                ("close", record.close),
+                ("last", record.last),
+            ];
+            let bad: Vec<String> = prices
+                .iter()
+                .filter(|(_, price)| *price <= 0.0)
+                .map(|(name, price)| format!("{} {}", name, price))
+                .collect();
+            (!bad.is_empty()).then(|| format!("Non-positive price: {}", bad.join(", ")))
+        },
+        Rule::PriceRange => {
+            if record.low > record.high {
+                return Some(format!("Low {} is above high {}", record.low, record.high));
+            }
+            let prices = [("open", record.open), ("close", record.close), ("last", record.last)];
+            let outside: Vec<String> = prices
+                .iter()
+                .filter(|(_, price)| *price < record.low || *price > record.high)
+                .map(|(name, price)| format!("{} {}", name, price))
+                .collect();
+            (!outside.is_empty()).then(|| {
+                format!("Outside the day's range {} - {}: {}", record.low, record.high, outside.join(", "))
+            })
+        },
+        Rule::NonNegativeVolume => {
+            let mut bad = Vec::new();
+            if record.tottrdqty < 0 {
+                bad.push(format!("quantity {}", record.tottrdqty));
+            }
+            if record.tottrdval < 0.0 {
+                bad.push(format!("value {}", record.tottrdval));
+            }
+            if record.totaltrades < 0 {
+                bad.push(format!("trades {}", record.totaltrades));
+            }
+            (!bad.is_empty()).then(|| format!("Negative volume: {}", bad.join(", ")))
+        },
+        Rule::CloseChange => {
+            if record.prevclose <= 0.0 {
+                return None;
+            }
+            let change_pct = (record.close - record.prevclose) / record.prevclose * 100.0;
+            (change_pct.abs() > config.max_close_change_pct).then(|| {
+                format!(
+                    "Close {} is {:+.1}% from previous close {} (limit {}%)",
+                    record.close, change_pct, record.prevclose, config.max_close_change_pct
+                )
+            })
+        },
+    }
+}